@@ -0,0 +1,7 @@
+fn main() {
+	// Expose the target triple, e.g. for `gpm env`.
+	println!(
+		"cargo:rustc-env=TARGET={}",
+		std::env::var("TARGET").unwrap()
+	);
+}
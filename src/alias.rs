@@ -0,0 +1,102 @@
+//! Expansion of user-defined command aliases from the `[alias]` table of config.toml.
+
+use crate::config::profile;
+use crate::profile_root;
+use crate::theme::{Role, Themed};
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+
+/// Global options that take a separate value, which must be skipped to find the command.
+const VALUE_FLAGS: [&str; 3] = ["--profile", "--color", "--log-level"];
+
+/// Value of a global `--flag value` or `--flag=value` option in raw arguments.
+pub fn flag_value(args: &[OsString], flag: &str) -> Option<String> {
+	let mut args = args.iter().skip(1).map(|a| a.to_string_lossy());
+	while let Some(arg) = args.next() {
+		if arg == "--" {
+			break;
+		}
+		match arg.strip_prefix(flag) {
+			Some("") => return args.next().map(|v| v.into_owned()),
+			Some(rest) if rest.starts_with('=') => return Some(rest[1..].to_string()),
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Index of the first argument that isn't a global option or its value.
+fn command_index(args: &[OsString]) -> Option<usize> {
+	positional_index(args, 1)
+}
+
+/// Index of the first argument from `start` on that isn't an option or the value of a global one.
+pub fn positional_index(args: &[OsString], start: usize) -> Option<usize> {
+	let mut i = start;
+	while i < args.len() {
+		let arg = args[i].to_string_lossy();
+		if arg == "--" {
+			return None;
+		}
+		if !arg.starts_with('-') {
+			return Some(i);
+		}
+		i += if VALUE_FLAGS.contains(&&*arg) { 2 } else { 1 };
+	}
+	None
+}
+
+/// Aliases of the profile selected in `args`, empty if the configuration can't be read.
+fn load(args: &[OsString]) -> HashMap<String, Vec<String>> {
+	let profile = flag_value(args, "--profile").or_else(|| env::var("GPM_PROFILE").ok());
+	// An invalid name is reported once the arguments are parsed.
+	if profile
+		.as_deref()
+		.is_some_and(|name| profile::validate(name).is_err())
+	{
+		return HashMap::new();
+	}
+	let path = profile_root(profile.as_deref()).join("config.toml");
+	fs::read_to_string(path)
+		.ok()
+		.and_then(|content| content.parse::<toml::Table>().ok())
+		.and_then(|mut table| table.remove("alias"))
+		.and_then(|alias| alias.try_into().ok())
+		.unwrap_or_default()
+}
+
+/// Replace a leading alias in `args` with its arguments, repeatedly, keeping trailing arguments.
+///
+/// `commands` are the built-in subcommand names, which are never expanded.
+pub fn expand(mut args: Vec<OsString>, commands: &[String]) -> Result<Vec<OsString>> {
+	let Some(index) = command_index(&args) else {
+		return Ok(args);
+	};
+	let aliases = load(&args);
+	let mut expanded: Vec<String> = vec![];
+	loop {
+		let name = args[index].to_string_lossy().into_owned();
+		if commands.contains(&name) {
+			return Ok(args);
+		}
+		let Some(replacement) = aliases.get(&name) else {
+			return Ok(args);
+		};
+		if expanded.contains(&name) {
+			expanded.push(name);
+			bail!(
+				"alias loop detected: {}",
+				expanded.join(" -> ").themed(Role::Warning)
+			);
+		}
+		if replacement.is_empty() {
+			bail!("alias '{}' is empty", name.themed(Role::Warning));
+		}
+		expanded.push(name);
+		args.splice(index..=index, replacement.iter().map(OsString::from));
+	}
+}
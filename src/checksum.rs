@@ -0,0 +1,182 @@
+//! Content checksums of installed packages, to detect files changed after install.
+
+use crate::CHECKSUM_PATH;
+
+use anyhow::Result;
+use jwalk::WalkDir;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Hex SHA-256 of each file under `path` keyed by its path relative to `path` with `/` separators,
+/// hashed in parallel. A file package is keyed by its own name.
+///
+/// Symlinks are hashed by their target path instead of followed.
+pub fn files(path: &Path) -> Result<BTreeMap<String, String>> {
+	let entries: Vec<(String, PathBuf)> = if path.is_file() {
+		let name = path.file_name().unwrap_or_default().to_string_lossy();
+		vec![(name.into_owned(), path.to_path_buf())]
+	} else {
+		WalkDir::new(path)
+			.skip_hidden(false)
+			.sort(true)
+			.into_iter()
+			.flatten()
+			.filter(|entry| !entry.file_type().is_dir())
+			.filter_map(|entry| {
+				let full = entry.path();
+				let rel = full.strip_prefix(path).ok()?;
+				let key = rel
+					.components()
+					.map(|c| c.as_os_str().to_string_lossy())
+					.collect::<Vec<_>>()
+					.join("/");
+				Some((key, full))
+			})
+			.collect()
+	};
+	entries
+		.into_par_iter()
+		.map(|(key, full)| Ok((key, hash_file(&full)?)))
+		.collect()
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+	let mut hasher = Sha256::new();
+	if path.symlink_metadata()?.is_symlink() {
+		hasher.update(fs::read_link(path)?.to_string_lossy().as_bytes());
+	} else {
+		io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+	}
+	Ok(hex(&hasher.finalize()))
+}
+
+/// Hex SHA-256 of `data`.
+pub fn sha256_hex(data: impl AsRef<[u8]>) -> String {
+	hex(&Sha256::digest(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Digest of a whole tree, over the sorted relative paths and the hashes of their files.
+pub fn digest(files: &BTreeMap<String, String>) -> String {
+	let mut hasher = Sha256::new();
+	for (path, hash) in files {
+		hasher.update(path.as_bytes());
+		hasher.update([0]);
+		hasher.update(hash.as_bytes());
+		hasher.update([b'\n']);
+	}
+	hex(&hasher.finalize())
+}
+
+/// File listing the hash of every file of a package, in `sha256sum` format.
+fn manifest_path(repo: &str, package: &str) -> PathBuf {
+	CHECKSUM_PATH.join(repo).join(format!("{}.sha256", package))
+}
+
+/// Write the per-file hashes of a package, so `verify` can name the files that changed.
+pub fn save_manifest(repo: &str, package: &str, files: &BTreeMap<String, String>) -> Result<()> {
+	let path = manifest_path(repo, package);
+	fs::create_dir_all(path.parent().unwrap())?;
+	let content: String = files
+		.iter()
+		.map(|(file, hash)| format!("{}  {}\n", hash, file))
+		.collect();
+	fs::write(path, content)?;
+	Ok(())
+}
+
+/// Per-file hashes recorded for a package, `None` if none were saved.
+pub fn load_manifest(repo: &str, package: &str) -> Option<BTreeMap<String, String>> {
+	let content = fs::read_to_string(manifest_path(repo, package)).ok()?;
+	Some(
+		content
+			.lines()
+			.filter_map(|line| line.split_once("  "))
+			.map(|(hash, file)| (file.to_string(), hash.to_string()))
+			.collect(),
+	)
+}
+
+/// Delete the recorded hashes of a package, if any.
+pub fn remove_manifest(repo: &str, package: &str) {
+	let _ = fs::remove_file(manifest_path(repo, package));
+}
+
+/// Files that differ between the recorded and the current hashes.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+	pub modified: Vec<String>,
+	pub added: Vec<String>,
+	pub removed: Vec<String>,
+}
+
+impl Changes {
+	pub fn between(
+		recorded: &BTreeMap<String, String>,
+		current: &BTreeMap<String, String>,
+	) -> Self {
+		let mut changes = Self::default();
+		for (file, hash) in current {
+			match recorded.get(file) {
+				Some(old) if old != hash => changes.modified.push(file.clone()),
+				Some(_) => {}
+				None => changes.added.push(file.clone()),
+			}
+		}
+		changes.removed = recorded
+			.keys()
+			.filter(|file| !current.contains_key(*file))
+			.cloned()
+			.collect();
+		changes
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+		entries
+			.iter()
+			.map(|(k, v)| (k.to_string(), v.to_string()))
+			.collect()
+	}
+
+	#[test]
+	fn sha256_is_lowercase_hex() {
+		assert_eq!(
+			sha256_hex("abc"),
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+		);
+	}
+
+	#[test]
+	fn digest_depends_on_paths_and_contents() {
+		let a = map(&[("bin/x", "1"), ("y", "2")]);
+		assert_eq!(digest(&a), digest(&a.clone()));
+		assert_ne!(digest(&a), digest(&map(&[("bin/x", "1"), ("y", "3")])));
+		assert_ne!(digest(&a), digest(&map(&[("bin/z", "1"), ("y", "2")])));
+	}
+
+	#[test]
+	fn changed_files() {
+		let recorded = map(&[("a", "1"), ("b", "2"), ("c", "3")]);
+		let current = map(&[("a", "1"), ("b", "9"), ("d", "4")]);
+		assert_eq!(
+			Changes::between(&recorded, &current),
+			Changes {
+				modified: vec!["b".into()],
+				added: vec!["d".into()],
+				removed: vec!["c".into()],
+			}
+		);
+	}
+}
@@ -0,0 +1,209 @@
+//! Leftovers of crashed or failed operations, found and removed by `clean`.
+
+use crate::config::main::Config;
+use crate::config::repository::RepoConfig;
+use crate::config::util::{human_size, prompt};
+use crate::du::size;
+use crate::theme::{Role, Themed};
+use crate::{error, remove, GPM_HOME, PROFILE_ROOT, REPO_CONFIG, REPO_PATH};
+
+use anyhow::Result;
+use colored::Colorize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tabwriter::TabWriter;
+
+/// Temporary files and directories of interrupted operations, matched by name.
+///
+/// Operations creating temporary files name them after one of these patterns, so `clean` finds
+/// what they leave behind.
+const ARTIFACTS: [Artifact; 4] = [
+	Artifact {
+		pattern: "*.part",
+		kind: "partial download",
+		in_repositories: true,
+	},
+	Artifact {
+		pattern: ".gpm-tmp-*",
+		kind: "extraction directory",
+		in_repositories: true,
+	},
+	Artifact {
+		pattern: "*.prev",
+		kind: "rollback copy",
+		in_repositories: true,
+	},
+	Artifact {
+		pattern: "gpm.log.*",
+		kind: "old log",
+		in_repositories: false,
+	},
+];
+
+struct Artifact {
+	/// File name, `*` matching any characters
+	pattern: &'static str,
+	kind: &'static str,
+	/// Also found in repositories, not only in the gpm home directory
+	in_repositories: bool,
+}
+
+impl Artifact {
+	fn matches(&self, name: &str) -> bool {
+		match self.pattern.split_once('*') {
+			Some((prefix, suffix)) => {
+				name.len() >= prefix.len() + suffix.len()
+					&& name.starts_with(prefix)
+					&& name.ends_with(suffix)
+			}
+			None => name == self.pattern,
+		}
+	}
+}
+
+/// A leftover found by `scan`.
+struct Leftover {
+	path: PathBuf,
+	kind: &'static str,
+	size: u64,
+}
+
+/// Leftovers at the top level of `dir`, skipping the names `keep` returns true for.
+fn scan_dir(
+	dir: &Path,
+	in_repository: bool,
+	keep: impl Fn(&str) -> bool,
+	found: &mut Vec<Leftover>,
+) {
+	let Ok(entries) = fs::read_dir(dir) else {
+		return;
+	};
+	for entry in entries.flatten() {
+		let name = entry.file_name().to_string_lossy().into_owned();
+		if keep(&name) {
+			continue;
+		}
+		let artifact = ARTIFACTS
+			.iter()
+			.filter(|a| a.in_repositories || !in_repository)
+			.find(|a| a.matches(&name));
+		if let Some(artifact) = artifact {
+			found.push(Leftover {
+				size: size(&entry.path()),
+				path: entry.path(),
+				kind: artifact.kind,
+			});
+		}
+	}
+}
+
+/// Leftovers in the gpm home directory, the active profile and every registered repository.
+///
+/// Only the top level of each directory is searched, so files inside packages are never touched.
+/// Registered packages are skipped even when their name matches a pattern.
+fn scan(gpm_cfg: &Config) -> Vec<Leftover> {
+	let mut found = vec![];
+	scan_dir(&GPM_HOME, false, |_| false, &mut found);
+	if *PROFILE_ROOT != *GPM_HOME {
+		scan_dir(&PROFILE_ROOT, false, |_| false, &mut found);
+	}
+	let mut repo_paths = BTreeSet::new();
+	for name in gpm_cfg.names() {
+		let Ok(path) = gpm_cfg.get_repo_path(name) else {
+			continue;
+		};
+		match RepoConfig::load(name, &path.join(REPO_CONFIG)) {
+			Ok(repo_cfg) => scan_dir(
+				&path,
+				true,
+				|name| name == REPO_CONFIG || repo_cfg.has_package(name),
+				&mut found,
+			),
+			Err(e) => error!("skipped repository '{}' {}", name.themed(Role::Warning), e),
+		}
+		repo_paths.insert(path);
+	}
+	// Directories of repositories removed from the registry but not from the disk.
+	if let Ok(entries) = fs::read_dir(&*REPO_PATH) {
+		for entry in entries.flatten() {
+			let path = entry.path();
+			let empty = fs::read_dir(&path).is_ok_and(|mut dir| dir.next().is_none());
+			if empty && !repo_paths.contains(&*path) {
+				found.push(Leftover {
+					path,
+					kind: "empty repository directory",
+					size: 0,
+				});
+			}
+		}
+	}
+	found
+}
+
+/// List leftovers with their sizes and remove them after confirmation, or only list them on a
+/// dry run.
+pub fn run(gpm_cfg: &Config, dry_run: bool) -> Result<()> {
+	let found = scan(gpm_cfg);
+	if found.is_empty() {
+		println!("{}", "Nothing to clean".themed(Role::Success));
+		return Ok(());
+	}
+	let total: u64 = found.iter().map(|l| l.size).sum();
+	let mut tw = TabWriter::new(vec![]);
+	writeln!(&mut tw, "{}", "Leftovers:".themed(Role::Success))?;
+	for leftover in &found {
+		writeln!(
+			&mut tw,
+			"  {}\t{}\t{}",
+			leftover.path.display().to_string().themed(Role::Path),
+			leftover.kind,
+			human_size(leftover.size)
+		)?;
+	}
+	writeln!(
+		&mut tw,
+		"{}\t\t{}",
+		"Total:".themed(Role::Success),
+		human_size(total)
+	)?;
+	tw.flush()?;
+	print!("{}", String::from_utf8(tw.into_inner().unwrap())?);
+	if dry_run || !prompt(&format!("Remove {} leftovers?", found.len()))? {
+		return Ok(());
+	}
+	for leftover in found {
+		let result = if leftover.path.is_dir() {
+			fs::remove_dir_all(&leftover.path)
+		} else {
+			fs::remove_file(&leftover.path)
+		};
+		match result {
+			Ok(()) => remove!("{}", leftover.path.display()),
+			Err(e) => error!(
+				"failed to remove '{}' {}",
+				leftover.path.display().to_string().themed(Role::Warning),
+				e
+			),
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn patterns() {
+		let [part, tmp, prev, log] = &ARTIFACTS;
+		assert!(part.matches("tool.zip.part"));
+		assert!(!part.matches("tool.zip"));
+		assert!(tmp.matches(".gpm-tmp-1234"));
+		assert!(!tmp.matches("gpm-tmp-1234"));
+		assert!(prev.matches("tool.prev"));
+		assert!(log.matches("gpm.log.1"));
+		assert!(!log.matches("gpm.log"));
+	}
+}
@@ -0,0 +1,83 @@
+//! Shell completion scripts, including shells not covered by `clap_complete`, and man pages.
+
+use clap::ValueEnum;
+use clap_complete::Shell;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CompletionShell {
+	Bash,
+	Elvish,
+	Fish,
+	Nushell,
+	Powershell,
+	Zsh,
+}
+
+/// Write the completion script for `shell` to `out`.
+pub fn generate(shell: CompletionShell, cmd: &mut clap::Command, out: &mut dyn Write) {
+	let shell = match shell {
+		CompletionShell::Bash => Shell::Bash,
+		CompletionShell::Elvish => Shell::Elvish,
+		CompletionShell::Fish => Shell::Fish,
+		CompletionShell::Powershell => Shell::PowerShell,
+		CompletionShell::Zsh => Shell::Zsh,
+		CompletionShell::Nushell => {
+			return clap_complete::generate(clap_complete_nushell::Nushell, cmd, "gpm", out)
+		}
+	};
+	clap_complete::generate(shell, cmd, "gpm", out)
+}
+
+/// How to load a completion script written to `path` in `shell`.
+pub fn source_hint(shell: CompletionShell, path: &Path) -> String {
+	let path = path.display();
+	match shell {
+		CompletionShell::Bash => format!("add 'source {}' to ~/.bashrc", path),
+		CompletionShell::Elvish => format!("add 'eval (slurp < {})' to ~/.config/elvish/rc.elv", path),
+		CompletionShell::Fish => format!(
+			"add 'source {}' to ~/.config/fish/config.fish, or write it to ~/.config/fish/completions/gpm.fish",
+			path
+		),
+		CompletionShell::Nushell => format!("add 'use {} *' to your config.nu", path),
+		CompletionShell::Powershell => format!("add '. {}' to your $PROFILE", path),
+		CompletionShell::Zsh => format!("add 'source {}' to ~/.zshrc after compinit", path),
+	}
+}
+
+/// Render man pages for `cmd` and all of its subcommands into `dir`, returning the files written.
+///
+/// Subcommand pages are named after their path, e.g. `gpm-repo-add.1`.
+pub fn generate_man(cmd: clap::Command, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+	fs::create_dir_all(dir)?;
+	let mut cmd = cmd;
+	cmd.build();
+	let mut written = vec![];
+	render_man(cmd, "gpm", dir, &mut written)?;
+	Ok(written)
+}
+
+fn render_man(
+	cmd: clap::Command,
+	name: &str,
+	dir: &Path,
+	written: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+	let cmd = cmd.name(name.to_string());
+	let path = dir.join(format!("{}.1", name));
+	let mut page = vec![];
+	clap_mangen::Man::new(cmd.clone()).render(&mut page)?;
+	fs::write(&path, page)?;
+	written.push(path);
+	for sub in cmd.get_subcommands().filter(|s| s.get_name() != "help") {
+		render_man(
+			sub.clone(),
+			&format!("{}-{}", name, sub.get_name()),
+			dir,
+			written,
+		)?;
+	}
+	Ok(())
+}
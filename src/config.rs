@@ -1,5 +1,18 @@
-mod util;
+pub mod util;
 
+pub mod backup;
+pub mod batch;
+pub mod code;
+pub mod export;
+pub mod history;
+pub mod hook;
 pub mod main;
+pub mod migrate;
+pub mod names;
+pub mod profile;
 pub mod repository;
+pub mod setup;
+pub mod shim;
+pub mod spec;
+pub mod sync;
 pub mod r#type;
@@ -0,0 +1,152 @@
+//! Rotating backups of configuration files under BACKUP_PATH.
+
+use super::main::Config;
+use crate::theme::{Role, Themed};
+use crate::{add, BACKUP_PATH, PROFILE_ROOT};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use colored::Colorize;
+use once_cell::sync::Lazy;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use tabwriter::TabWriter;
+
+/// Number of backups kept per file when not configured.
+const DEFAULT_RETENTION: usize = 5;
+/// Format of backup file names, sortable by time.
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3f";
+
+static RETENTION: Lazy<usize> = Lazy::new(|| {
+	Config::load()
+		.ok()
+		.and_then(|c| c.backup_retention())
+		.unwrap_or(DEFAULT_RETENTION)
+});
+
+/// Directory holding the backups of `path`.
+fn backup_dir(path: &Path) -> PathBuf {
+	let relative = path.strip_prefix(&*PROFILE_ROOT).unwrap_or(path);
+	BACKUP_PATH.join(key(relative))
+}
+
+/// Directory name for the backups of `path`, its components joined with `_`, in which `%` and `_`
+/// are escaped so different paths never share a name.
+fn key(path: &Path) -> String {
+	path.components()
+		.filter_map(|c| match c {
+			Component::Normal(c) => {
+				Some(c.to_string_lossy().replace('%', "%25").replace('_', "%5F"))
+			}
+			_ => None,
+		})
+		.collect::<Vec<_>>()
+		.join("_")
+}
+
+/// Backups of `path`, newest first.
+fn backups(path: &Path) -> Result<Vec<PathBuf>> {
+	let dir = backup_dir(path);
+	if !dir.is_dir() {
+		return Ok(vec![]);
+	}
+	let mut backups = fs::read_dir(dir)?
+		.map(|e| e.map(|e| e.path()))
+		.collect::<Result<Vec<_>, _>>()?;
+	backups.sort();
+	backups.reverse();
+	Ok(backups)
+}
+
+/// Back up the current content of `path` before it's overwritten with `new_content`.
+///
+/// Nothing is written when the content is unchanged or identical to the latest backup.
+pub fn backup(path: &Path, new_content: &str) -> Result<()> {
+	let Ok(current) = fs::read(path) else {
+		return Ok(());
+	};
+	if current == new_content.as_bytes() {
+		return Ok(());
+	}
+	let backups = backups(path)?;
+	if backups
+		.first()
+		.is_some_and(|latest| fs::read(latest).is_ok_and(|b| b == current))
+	{
+		return Ok(());
+	}
+
+	let dir = backup_dir(path);
+	fs::create_dir_all(&dir)?;
+	fs::write(
+		dir.join(format!("{}.toml", Local::now().format(TIMESTAMP_FORMAT))),
+		current,
+	)?;
+	for old in backups.iter().skip(RETENTION.saturating_sub(1)) {
+		fs::remove_file(old)?;
+	}
+	Ok(())
+}
+
+/// List backups of `path`, numbered from the newest.
+pub fn list(path: &Path) -> Result<String> {
+	let mut tw = TabWriter::new(vec![]);
+	writeln!(
+		&mut tw,
+		"{} {}",
+		"Backups of".themed(Role::Success),
+		path.display().to_string().themed(Role::Success)
+	)?;
+	for (i, backup) in backups(path)?.iter().enumerate() {
+		let meta = fs::metadata(backup)?;
+		let time: DateTime<Local> = meta.modified()?.into();
+		writeln!(
+			&mut tw,
+			"  {}\t{}\t{} bytes",
+			(i + 1).to_string().themed(Role::Name),
+			time.format("%Y-%m-%d %H:%M:%S"),
+			meta.len()
+		)?;
+	}
+	tw.flush()?;
+	Ok(String::from_utf8(tw.into_inner().unwrap())?)
+}
+
+/// Restore backup number `index` (1 is the newest) to `path`, backing up the current content first.
+pub fn restore(path: &Path, index: usize) -> Result<()> {
+	let backups = backups(path)?;
+	let Some(backup) = index.checked_sub(1).and_then(|i| backups.get(i)) else {
+		bail!(
+			"backup {} does not exist, {} backups available",
+			index.to_string().themed(Role::Warning),
+			backups.len()
+		);
+	};
+	let content = fs::read_to_string(backup)?;
+	self::backup(path, &content)?;
+	fs::write(path, content)?;
+	add!(
+		"{}\t{}",
+		path.display().to_string().themed(Role::Name),
+		index
+	);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keys_are_unique() {
+		assert_eq!(key(Path::new("config.toml")), "config.toml");
+		assert_eq!(
+			key(Path::new("repositories/rp/version.toml")),
+			"repositories_rp_version.toml"
+		);
+		assert_ne!(key(Path::new("a_b/c")), key(Path::new("a/b_c")));
+		assert_ne!(key(Path::new("a_/b")), key(Path::new("a/_b")));
+		assert_ne!(key(Path::new("a%5F/b")), key(Path::new("a_/b")));
+	}
+}
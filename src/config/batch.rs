@@ -0,0 +1,136 @@
+//! Results of commands applied to several items.
+
+use super::util::interrupted;
+use crate::note;
+use crate::theme::{Role, Themed};
+
+use anyhow::Result;
+use colored::Colorize;
+use std::fmt::Display;
+use std::io::Write;
+use tabwriter::TabWriter;
+
+/// Collects the outcome of each item in a batch to print a summary afterwards.
+#[derive(Debug, Default)]
+pub struct Summary {
+	succeeded: usize,
+	/// Succeeded items whose tag moved, and those whose tag stayed.
+	changed: usize,
+	unchanged: usize,
+	/// Failed item names with a one-line error.
+	failed: Vec<(String, String)>,
+	skipped: Vec<String>,
+	/// Items left out on purpose, such as by `update --except`.
+	excluded: Vec<String>,
+}
+
+impl Summary {
+	pub fn succeed(&mut self) {
+		self.succeeded += 1;
+	}
+
+	/// Record a succeeded item, `changed` telling whether it changed.
+	pub fn succeed_changed(&mut self, changed: bool) {
+		self.succeed();
+		if changed {
+			self.changed += 1;
+		} else {
+			self.unchanged += 1;
+		}
+	}
+
+	pub fn fail(&mut self, name: &str, error: impl Display) {
+		let error = error.to_string();
+		let line = error.lines().next().unwrap_or_default().to_string();
+		self.failed.push((name.to_string(), line));
+	}
+
+	pub fn skip(&mut self, name: &str) {
+		self.skipped.push(name.to_string());
+	}
+
+	pub fn exclude(&mut self, name: &str) {
+		self.excluded.push(name.to_string());
+	}
+
+	/// Record items left unattempted after stopping at a failure or an interruption.
+	pub fn skip_rest(&mut self, rest: impl Iterator<Item = String>) {
+		let rest: Vec<String> = rest.collect();
+		if !rest.is_empty() {
+			note!(
+				"{}, {} not attempted",
+				if interrupted() {
+					"interrupted"
+				} else {
+					"stopped after the first failure"
+				},
+				rest.join(", ").themed(Role::Warning)
+			);
+			self.skipped.extend(rest);
+		}
+	}
+
+	pub fn has_failures(&self) -> bool {
+		!self.failed.is_empty()
+	}
+
+	fn total(&self) -> usize {
+		self.succeeded + self.failed.len() + self.skipped.len() + self.excluded.len()
+	}
+
+	/// Print the summary, nothing for a single item since its result is already shown.
+	pub fn print(&self) {
+		if self.total() > 1 {
+			print!("{}", self.render().unwrap());
+		}
+	}
+
+	fn render(&self) -> Result<String> {
+		let mut tw = TabWriter::new(vec![]);
+		write!(
+			&mut tw,
+			"{} {} succeeded",
+			"Summary:".themed(Role::Success),
+			self.succeeded
+		)?;
+		if self.changed + self.unchanged > 0 {
+			write!(
+				&mut tw,
+				" ({} changed, {} unchanged)",
+				self.changed, self.unchanged
+			)?;
+		}
+		write!(
+			&mut tw,
+			", {} failed, {} skipped",
+			self.failed.len(),
+			self.skipped.len()
+		)?;
+		if self.excluded.is_empty() {
+			writeln!(&mut tw)?;
+		} else {
+			writeln!(&mut tw, ", {} excluded", self.excluded.len())?;
+		}
+		for (name, error) in &self.failed {
+			writeln!(&mut tw, "  {}\t{}", name.themed(Role::Warning), error)?;
+		}
+		if !self.skipped.is_empty() {
+			writeln!(
+				&mut tw,
+				"  {}\t{}",
+				"skipped:".bright_black(),
+				self.skipped.join(", ")
+			)?;
+		}
+		if !self.excluded.is_empty() {
+			writeln!(
+				&mut tw,
+				"  {}\t{}",
+				"excluded:".bright_black(),
+				self.excluded.join(", ")
+			)?;
+		}
+		tw.flush()?;
+		Ok(String::from_utf8(tw.into_inner().unwrap())?)
+	}
+}
@@ -0,0 +1,147 @@
+//! Stable codes of errors, shown as `[E001]` before the message, for scripts reading stderr.
+//!
+//! Codes are part of the compatibility contract, they are never renumbered or reused.
+
+use clap::ValueEnum;
+use serde_json::json;
+use std::fmt;
+
+/// Codes with their ids and names, in the order of the ids.
+const CODES: [(Code, &str, &str); 13] = [
+	(Code::RepoNotFound, "E001", "repo-not-found"),
+	(Code::RepoExists, "E002", "repo-exists"),
+	(Code::RepoReadonly, "E003", "repo-readonly"),
+	(Code::PackageNotFound, "E004", "package-not-found"),
+	(Code::PackageExists, "E005", "package-exists"),
+	(Code::GroupNotFound, "E006", "group-not-found"),
+	(Code::TypeMissing, "E010", "type-missing"),
+	(Code::TypeExists, "E011", "type-exists"),
+	(Code::ShellMissing, "E012", "shell-missing"),
+	(Code::ArgsInvalid, "E013", "args-invalid"),
+	(Code::ScriptFailed, "E020", "script-failed"),
+	(Code::NameInvalid, "E030", "name-invalid"),
+	(Code::ConfigInvalid, "E040", "config-invalid"),
+];
+
+/// What went wrong, independent of the wording of the message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Code {
+	RepoNotFound,
+	RepoExists,
+	/// Modifying a repository marked read-only
+	RepoReadonly,
+	PackageNotFound,
+	PackageExists,
+	GroupNotFound,
+	TypeMissing,
+	TypeExists,
+	/// The shell of a type isn't registered
+	ShellMissing,
+	/// Too few or too many args for the parameters of a type
+	ArgsInvalid,
+	/// The script of a type couldn't be started
+	ScriptFailed,
+	/// A name that can't be used as a file name
+	NameInvalid,
+	/// A configuration file that doesn't parse
+	ConfigInvalid,
+}
+
+/// How errors are printed on stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum ErrorFormat {
+	#[default]
+	Human,
+	/// One JSON object per error and line
+	Json,
+}
+
+impl Code {
+	fn entry(self) -> &'static (Code, &'static str, &'static str) {
+		CODES.iter().find(|(code, ..)| *code == self).unwrap()
+	}
+
+	/// `E001` and so on.
+	pub fn id(self) -> &'static str {
+		self.entry().1
+	}
+
+	/// Kebab-case name, e.g. `repo-not-found`.
+	pub fn name(self) -> &'static str {
+		self.entry().2
+	}
+}
+
+/// The bracketed id put before messages.
+impl fmt::Display for Code {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "[{}]", self.id())
+	}
+}
+
+/// The first code tagged in `text`, with the text without its tag.
+fn untag(text: &str) -> Option<(Code, String)> {
+	CODES
+		.iter()
+		.filter_map(|(code, id, _)| {
+			let tag = format!("[{}] ", id);
+			text.find(&tag)
+				.map(|at| (at, *code, text.replacen(&tag, "", 1)))
+		})
+		.min_by_key(|(at, ..)| *at)
+		.map(|(_, code, text)| (code, text))
+}
+
+/// An error as a JSON object, the code taken from the first of `message` and `context` tagged
+/// with one.
+///
+/// `context` holds the causes of the error, outermost first.
+pub fn to_json(message: &str, context: &[String]) -> serde_json::Value {
+	let mut code = None;
+	let mut texts: Vec<String> = std::iter::once(message)
+		.chain(context.iter().map(String::as_str))
+		.map(String::from)
+		.collect();
+	for text in &mut texts {
+		if let Some((found, untagged)) = untag(text) {
+			code = Some(found);
+			*text = untagged;
+			break;
+		}
+	}
+	json!({
+		"code": code.map(Code::id),
+		"name": code.map(Code::name),
+		"message": texts[0],
+		"context": &texts[1..],
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ids_are_unique() {
+		for (i, (code, id, name)) in CODES.iter().enumerate() {
+			assert_eq!(code.id(), *id);
+			assert_eq!(code.name(), *name);
+			assert!(CODES[i + 1..].iter().all(|(_, other, _)| other > id));
+		}
+	}
+
+	#[test]
+	fn json_takes_the_tagged_cause() {
+		let value = to_json(
+			"failed to import",
+			&[format!(
+				"{} package 'rg' already exists",
+				Code::PackageExists
+			)],
+		);
+		assert_eq!(value["code"], "E005");
+		assert_eq!(value["name"], "package-exists");
+		assert_eq!(value["context"][0], "package 'rg' already exists");
+		assert!(to_json("plain", &[])["code"].is_null());
+	}
+}
@@ -0,0 +1,237 @@
+//! Export and import of the complete installation state, to move it to another machine.
+
+use super::batch::Summary;
+use super::main::Config;
+use super::r#type::{Param, TypeConfig};
+use super::repository::RepoConfig;
+use super::util::{load_toml, timestamp};
+use crate::theme::{Role, Themed};
+use crate::{error, note, REPO_CONFIG, REPO_PATH};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the exported document, raised on incompatible changes.
+const VERSION: u32 = 1;
+
+/// Repositories, their packages, types and shells in one document.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+	version: u32,
+	/// When the document was exported, RFC 3339
+	exported_at: String,
+	/// Key: shell name, Value: its arguments
+	#[serde(default)]
+	shell: BTreeMap<String, Vec<String>>,
+	/// Key: type name, Value: type properties
+	#[serde(default)]
+	types: BTreeMap<String, Type>,
+	/// Key: repository name, Value: repository properties and packages
+	#[serde(default)]
+	repositories: BTreeMap<String, Repository>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Type {
+	pub ext: String,
+	pub shell: String,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub params: Vec<Param>,
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub env: BTreeMap<String, String>,
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub no_artifact: bool,
+	/// Content of the script, asked for on import when missing
+	pub script: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Repository {
+	/// Relative to the repositories directory when inside it
+	pub path: String,
+	pub description: Option<String>,
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub readonly: bool,
+	pub confirm_update_all: Option<bool>,
+	/// Key: package name, Value: package details
+	#[serde(default)]
+	pub packages: BTreeMap<String, Package>,
+	/// Key: group name, Value: names of member packages
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub groups: BTreeMap<String, Vec<String>>,
+}
+
+/// What is needed to install a package again, without the state of the last run.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Package {
+	pub r#type: String,
+	pub args: Vec<String>,
+	pub cwd: Option<String>,
+	pub description: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub no_verify: bool,
+}
+
+/// What `import` does with entries that already exist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+	/// Fail the entry, leaving the existing one
+	Report,
+	Overwrite,
+	Skip,
+}
+
+impl Conflict {
+	/// Whether to replace the existing `kind` `name`, failing when conflicts are reported.
+	pub(super) fn replace(self, kind: &str, name: &str) -> Result<bool> {
+		match self {
+			Conflict::Report => bail!(
+				"{} '{}' already exists, pass '--overwrite' or '--skip-existing'",
+				kind,
+				name.themed(Role::Warning)
+			),
+			Conflict::Overwrite => Ok(true),
+			Conflict::Skip => Ok(false),
+		}
+	}
+}
+
+/// Path of a repository as exported, relative to the repositories directory when inside it.
+fn portable_path(path: &Path) -> String {
+	match path.strip_prefix(&*REPO_PATH) {
+		Ok(relative) => relative
+			.components()
+			.map(|c| c.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/"),
+		Err(_) => path.display().to_string(),
+	}
+}
+
+/// Count the outcome of importing `kind` `item`, `Ok(false)` when skipped as existing.
+///
+/// Returns whether it failed.
+fn record(summary: &mut Summary, kind: &str, item: &str, result: Result<bool>) -> bool {
+	match result {
+		Ok(true) => summary.succeed(),
+		Ok(false) => {
+			note!("skipped existing {} '{}'", kind, item.themed(Role::Warning));
+			summary.skip(item);
+		}
+		Err(e) => {
+			error!(e);
+			summary.fail(item, e);
+			return true;
+		}
+	}
+	false
+}
+
+/// Export the configuration, the registry of every repository and the types with their scripts.
+pub fn export(output: Option<&Path>) -> Result<()> {
+	let gpm_cfg = Config::load()?;
+	let type_cfg = TypeConfig::load()?;
+	let (shell, types) = type_cfg.export();
+	let mut repositories = BTreeMap::new();
+	for name in gpm_cfg.names() {
+		let path = gpm_cfg.get_repo_path(name)?;
+		let mut repo = gpm_cfg.export(name);
+		repo.path = portable_path(&path);
+		(repo.packages, repo.groups) = RepoConfig::load(name, &path.join(REPO_CONFIG))?.export();
+		repositories.insert(name.to_string(), repo);
+	}
+	let document = toml::to_string(&Document {
+		version: VERSION,
+		exported_at: timestamp(),
+		shell,
+		types,
+		repositories,
+	})?;
+	match output {
+		Some(output) => {
+			fs::write(output, document)?;
+			note!(
+				"exported to '{}'",
+				output.display().to_string().themed(Role::Path)
+			);
+		}
+		None => print!("{}", document),
+	}
+	Ok(())
+}
+
+/// Recreate what an export holds, registering packages as pending for `install`.
+///
+/// Every shell, type, repository and package is imported on its own, a failure or a conflict
+/// under `Conflict::Report` doesn't stop the others.
+pub fn import(path: &Path, conflict: Conflict) -> Result<Summary> {
+	let document: Document = load_toml(path)?;
+	if document.version > VERSION {
+		bail!(
+			"'{}' has version {}, this gpm reads up to {}, update gpm first",
+			path.display().to_string().themed(Role::Warning),
+			document.version,
+			VERSION
+		);
+	}
+	let mut summary = Summary::default();
+	let mut type_cfg = TypeConfig::load()?;
+	// Shells registered the same way, like the default one, are left alone.
+	for (name, args) in document.shell {
+		if type_cfg.has_shell(&name, &args) {
+			continue;
+		}
+		let result = type_cfg.import_shell(&name, args, conflict);
+		record(&mut summary, "shell", &name, result);
+	}
+	for (name, r#type) in document.types {
+		let result = type_cfg.import(&name, r#type, conflict);
+		record(&mut summary, "type", &name, result);
+	}
+	type_cfg.save()?;
+
+	let mut gpm_cfg = Config::load()?;
+	for (name, repo) in document.repositories {
+		let path = PathBuf::from(&repo.path);
+		let path = if path.is_relative() {
+			REPO_PATH.join(path)
+		} else {
+			path
+		};
+		let result = gpm_cfg.import(&name, &path, &repo, conflict);
+		if record(&mut summary, "repository", &name, result) {
+			continue;
+		}
+		let repo_cfg_path = gpm_cfg.get_repo_path(&name)?.join(REPO_CONFIG);
+		let mut repo_cfg = RepoConfig::load(&name, &repo_cfg_path)?;
+		for (package, details) in repo.packages {
+			let groups: Vec<_> = repo
+				.groups
+				.iter()
+				.filter(|(_, members)| members.contains(&package))
+				.map(|(group, _)| group.as_str())
+				.collect();
+			let result = repo_cfg.import(&package, details, &groups, conflict);
+			record(
+				&mut summary,
+				"package",
+				&format!("{}/{}", name, package),
+				result,
+			);
+		}
+		repo_cfg.save(&repo_cfg_path)?;
+	}
+	gpm_cfg.save()?;
+	summary.print();
+	Ok(summary)
+}
@@ -0,0 +1,334 @@
+//! Reading the history journal written by `HistoryEntry::record`.
+
+use super::main::Config;
+use super::r#type::TypeConfig;
+use super::repository::RepoConfig;
+use super::util::{
+	config_checksum, quote_args, truncate, HistoryEntry, Outcome, DESCRIPTION_WIDTH,
+};
+use crate::theme::{Role, Themed};
+use crate::{status, GPM_CONFIG, HISTORY_FILE, OLD_HISTORY_FILE, TYPES_CONFIG};
+
+use anyhow::{bail, Context, Result};
+use chrono::DateTime;
+use colored::Colorize;
+use std::fs;
+use std::io::Write;
+use tabwriter::TabWriter;
+
+/// Which entries of the journal to show.
+#[derive(Debug, Default)]
+pub struct HistoryFilter<'a> {
+	pub repo: Option<&'a str>,
+	pub package: Option<&'a str>,
+	/// Number of most recent entries
+	pub limit: usize,
+}
+
+/// All entries in the journal, oldest first, reaching into the rotated file.
+pub fn entries() -> Result<Vec<HistoryEntry>> {
+	let mut entries = vec![];
+	for path in [&*OLD_HISTORY_FILE, &*HISTORY_FILE] {
+		let Ok(content) = fs::read_to_string(path) else {
+			continue;
+		};
+		for (i, line) in content.lines().enumerate() {
+			if line.trim().is_empty() {
+				continue;
+			}
+			entries.push(
+				serde_json::from_str(line)
+					.with_context(|| format!("invalid entry at {}:{}", path.display(), i + 1))?,
+			);
+		}
+	}
+	Ok(entries)
+}
+
+fn select(filter: &HistoryFilter) -> Result<Vec<HistoryEntry>> {
+	let mut entries: Vec<HistoryEntry> = entries()?
+		.into_iter()
+		.filter(|e| filter.repo.is_none_or(|r| e.repo.as_deref() == Some(r)))
+		.filter(|e| {
+			filter
+				.package
+				.is_none_or(|p| e.package.as_deref() == Some(p))
+		})
+		.collect();
+	let start = entries.len().saturating_sub(filter.limit);
+	Ok(entries.split_off(start))
+}
+
+/// Render the selected entries as a table, oldest first.
+pub fn list(filter: &HistoryFilter) -> Result<String> {
+	let entries = select(filter)?;
+	let mut tw = TabWriter::new(vec![]);
+	writeln!(&mut tw, "{}", "History:".themed(Role::Success))?;
+	for entry in &entries {
+		let time = DateTime::parse_from_rfc3339(&entry.time)
+			.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+			.unwrap_or_else(|_| entry.time.clone());
+		let outcome = match entry.outcome {
+			Outcome::Ok => "ok".themed(Role::Success),
+			Outcome::Failed => "failed".themed(Role::Error),
+			Outcome::Skipped => "skipped".themed(Role::Warning),
+		};
+		writeln!(
+			&mut tw,
+			"  {}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+			time.bright_black(),
+			entry.operation,
+			entry.repo.as_deref().unwrap_or_default().themed(Role::Name),
+			entry
+				.package
+				.as_deref()
+				.unwrap_or_default()
+				.themed(Role::Name),
+			entry
+				.r#type
+				.as_deref()
+				.unwrap_or_default()
+				.themed(Role::Type),
+			truncate(&quote_args(&entry.args), DESCRIPTION_WIDTH),
+			outcome,
+			entry.error.as_deref().unwrap_or_default().bright_white()
+		)?;
+	}
+	tw.flush()?;
+	let mut result = String::from_utf8(tw.into_inner().unwrap())?;
+	result.push_str(&format!(
+		"{} entr{}\n",
+		entries.len(),
+		if entries.len() == 1 { "y" } else { "ies" }
+	));
+	Ok(result)
+}
+
+/// Selected entries as a JSON array, oldest first.
+pub fn list_json(filter: &HistoryFilter) -> Result<String> {
+	Ok(serde_json::to_string_pretty(&select(filter)?)? + "\n")
+}
+
+/// Operations running the script of a package, shown in its history only when the etag moved.
+const RUNS: [&str; 3] = ["install", "update", "reinstall"];
+
+/// Entries of package `package` in `repo`, without runs that left the etag as it was.
+fn package_entries(repo: &str, package: &str, limit: usize) -> Result<Vec<HistoryEntry>> {
+	let mut entries: Vec<HistoryEntry> = entries()?
+		.into_iter()
+		.filter(|e| e.repo.as_deref() == Some(repo) && e.package.as_deref() == Some(package))
+		.filter(|e| {
+			!RUNS.contains(&e.operation.as_str())
+				|| (e.outcome == Outcome::Ok && e.etag != e.previous_etag)
+		})
+		.collect();
+	let start = entries.len().saturating_sub(limit);
+	Ok(entries.split_off(start))
+}
+
+/// Render the history of a package, when it was added, updated to another etag and removed.
+pub fn package(repo: &str, package: &str, limit: usize) -> Result<String> {
+	let entries = package_entries(repo, package, limit)?;
+	if entries.is_empty() {
+		bail!(
+			"no history of package '{}' in '{}'",
+			package.themed(Role::Warning),
+			repo.themed(Role::Name)
+		);
+	}
+	let mut tw = TabWriter::new(vec![]);
+	writeln!(
+		&mut tw,
+		"{} {}",
+		"History of".themed(Role::Success),
+		package.themed(Role::Name)
+	)?;
+	for entry in &entries {
+		let time = DateTime::parse_from_rfc3339(&entry.time)
+			.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+			.unwrap_or_else(|_| entry.time.clone());
+		let etag = match (&entry.previous_etag, &entry.etag) {
+			(Some(previous), Some(etag)) => format!("{} -> {}", previous.bright_black(), etag),
+			(None, Some(etag)) => etag.to_string(),
+			_ => String::new(),
+		};
+		let outcome = match entry.outcome {
+			Outcome::Ok => "".normal(),
+			Outcome::Failed => "failed".themed(Role::Error),
+			Outcome::Skipped => "skipped".themed(Role::Warning),
+		};
+		writeln!(
+			&mut tw,
+			"  {}\t{}\t{}\t{}",
+			time.bright_black(),
+			entry.operation,
+			etag,
+			outcome
+		)?;
+	}
+	tw.flush()?;
+	Ok(String::from_utf8(tw.into_inner().unwrap())?)
+}
+
+/// History of a package as a JSON array, oldest first.
+pub fn package_json(repo: &str, package: &str, limit: usize) -> Result<String> {
+	Ok(serde_json::to_string_pretty(&package_entries(repo, package, limit)?)? + "\n")
+}
+
+/// Operations `undo` can reverse when recorded with the state before them.
+const REVERSIBLE: [&str; 6] = [
+	"add",
+	"remove-registry",
+	"remove-tag",
+	"set-etag",
+	"type add",
+	"type remove-registry",
+];
+
+/// What an entry changed, e.g. `repo/package` or the type name.
+fn subject(entry: &HistoryEntry) -> String {
+	match (&entry.repo, &entry.package, &entry.r#type) {
+		(Some(repo), Some(package), _) => format!("{}/{}", repo, package),
+		(Some(repo), None, _) => repo.clone(),
+		(None, _, Some(r#type)) => r#type.clone(),
+		_ => entry.args.first().cloned().unwrap_or_default(),
+	}
+}
+
+/// Explain why an operation can't be undone and how to revert it by hand.
+fn irreversible(entry: &HistoryEntry) -> String {
+	let hint = match (entry.operation.as_str(), &entry.package) {
+		("update" | "install" | "reinstall", _) => {
+			"it ran the install script, update the package again instead".to_string()
+		}
+		("remove", Some(package)) => {
+			let mut command = vec![
+				"gpm",
+				"repo",
+				entry.repo.as_deref().unwrap_or_default(),
+				"add",
+			];
+			command.push(package);
+			command.extend(entry.r#type.as_deref());
+			command.extend(entry.args.iter().map(String::as_str));
+			format!(
+				"its files were deleted, add it again with '{}'",
+				quote_args(&command)
+			)
+		}
+		("remove" | "type remove", _) => "its files were deleted, add it again".to_string(),
+		("relocate", _) => "its directory was moved, relocate it back".to_string(),
+		_ if REVERSIBLE.contains(&entry.operation.as_str()) => {
+			"it was recorded without undo information".to_string()
+		}
+		_ => "run the opposite command to revert it".to_string(),
+	};
+	format!(
+		"'{}' of '{}' at {} is not automatically reversible, {}",
+		entry.operation,
+		subject(entry).themed(Role::Warning),
+		entry.time,
+		hint
+	)
+}
+
+/// The entries of the most recent operation that wasn't undone yet.
+///
+/// Entries saved together, e.g. several packages removed at once, are undone together.
+fn last_operation(entries: &[HistoryEntry]) -> Result<&[HistoryEntry]> {
+	let same_save = |a: &HistoryEntry, b: &HistoryEntry| match (&a.undo, &b.undo) {
+		(Some(a), Some(b)) => a.config == b.config && a.checksum == b.checksum,
+		_ => false,
+	};
+	let mut undone = 0;
+	let mut end = entries.len();
+	while end > 0 {
+		let entry = &entries[end - 1];
+		if entry.outcome != Outcome::Ok || entry.operation == "clone" {
+			end -= 1;
+			continue;
+		}
+		if entry.operation == "undo" {
+			undone += 1;
+			end -= 1;
+			continue;
+		}
+		if entry.undo.is_none() {
+			bail!(irreversible(entry));
+		}
+		let mut start = end - 1;
+		while start > 0
+			&& entries[start - 1].outcome == Outcome::Ok
+			&& same_save(&entries[start - 1], entry)
+		{
+			start -= 1;
+		}
+		if undone == 0 {
+			return Ok(&entries[start..end]);
+		}
+		undone -= 1;
+		end = start;
+	}
+	bail!("nothing to undo")
+}
+
+/// Reverse the most recent registry operation, refusing when its configuration changed since.
+pub fn undo() -> Result<()> {
+	let entries = entries()?;
+	let group = last_operation(&entries)?;
+	let last = group.last().unwrap();
+	let undo = last.undo.as_ref().unwrap();
+	let checksum = config_checksum(&undo.config)
+		.with_context(|| format!("failed to read '{}'", undo.config.display()))?;
+	if checksum != undo.checksum {
+		bail!(
+			"'{}' has been modified since '{}' at {}, refusing to undo",
+			undo.config.display().to_string().themed(Role::Warning),
+			last.operation,
+			last.time
+		);
+	}
+	status!(
+		"undoing".bright_blue(),
+		"'{}' of {} at {}",
+		last.operation,
+		group
+			.iter()
+			.map(|e| subject(e).themed(Role::Name).to_string())
+			.collect::<Vec<_>>()
+			.join(", "),
+		last.time
+	);
+
+	let reversed = group.iter().rev();
+	if undo.config == *GPM_CONFIG {
+		let mut gpm_cfg = Config::load()?;
+		for entry in reversed {
+			gpm_cfg.undo(entry, &entry.undo.as_ref().unwrap().before)?;
+		}
+		gpm_cfg.save()?;
+	} else if undo.config == *TYPES_CONFIG {
+		let mut type_cfg = TypeConfig::load()?;
+		for entry in reversed {
+			type_cfg.undo(entry, &entry.undo.as_ref().unwrap().before)?;
+		}
+		type_cfg.save()?;
+	} else {
+		let mut repo_cfg =
+			RepoConfig::load(last.repo.as_deref().unwrap_or_default(), &undo.config)?;
+		for entry in reversed {
+			repo_cfg.undo(entry, &entry.undo.as_ref().unwrap().before)?;
+		}
+		repo_cfg.save(&undo.config)?;
+	}
+
+	let mut args = vec![last.operation.clone()];
+	args.extend(group.iter().map(subject));
+	let entry = HistoryEntry::new("undo").args(&args);
+	match &last.repo {
+		Some(repo) => entry.repo(repo),
+		None => entry,
+	}
+	.record();
+	Ok(())
+}
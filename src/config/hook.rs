@@ -0,0 +1,102 @@
+//! Commands from the `[hooks]` section of config.toml, run after operations complete.
+
+use crate::theme::{Role, Themed};
+use crate::{environment, error, status};
+
+use colored::Colorize;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// Skip hooks, set once in `main` from `--no-hooks`.
+pub static DISABLED: OnceCell<bool> = OnceCell::new();
+
+/// Shell commands run after an operation.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	post_update: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	post_add: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	post_remove: Option<String>,
+}
+
+/// Operation a hook runs after.
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+	/// `update`, `install` and `retry-failed` of packages
+	Update,
+	/// Adding a repository or a package
+	Add,
+	/// Removing repositories or packages
+	Remove,
+}
+
+impl Event {
+	fn key(self) -> &'static str {
+		match self {
+			Event::Update => "post_update",
+			Event::Add => "post_add",
+			Event::Remove => "post_remove",
+		}
+	}
+}
+
+impl Hooks {
+	pub fn is_empty(&self) -> bool {
+		self.post_update.is_none() && self.post_add.is_none() && self.post_remove.is_none()
+	}
+
+	fn command(&self, event: Event) -> Option<&str> {
+		match event {
+			Event::Update => self.post_update.as_deref(),
+			Event::Add => self.post_add.as_deref(),
+			Event::Remove => self.post_remove.as_deref(),
+		}
+		.filter(|c| !c.trim().is_empty())
+	}
+
+	/// Run the hook of `event` through the system shell.
+	///
+	/// `GPM_PACKAGES` holds the space separated package names, empty for repository operations.
+	/// A failing hook is reported but never changes the exit code of gpm.
+	pub fn run(&self, event: Event, ok: bool, repo: &str, packages: &[String]) {
+		if DISABLED.get().copied().unwrap_or_default() {
+			return;
+		}
+		let Some(command) = self.command(event) else {
+			return;
+		};
+		let (shell, flag) = if cfg!(windows) {
+			("cmd", "/C")
+		} else {
+			("sh", "-c")
+		};
+		let result = if ok { "ok" } else { "failed" };
+		status!("hook:".bright_blue(), "{}", command);
+		let status = Command::new(shell)
+			.arg(flag)
+			.arg(command)
+			.envs(environment::vars())
+			.env("GPM_RESULT", result)
+			.env("GPM_REPO", repo)
+			.env("GPM_PACKAGES", packages.join(" "))
+			.status();
+		tracing::info!(hook = event.key(), %result, status = ?status, "hook executed");
+		match status {
+			Ok(status) if status.success() => {}
+			Ok(status) => error!(
+				"hook '{}' exited with {}",
+				event.key().themed(Role::Warning),
+				status
+			),
+			Err(e) => error!(
+				"failed to run hook '{}' {}",
+				event.key().themed(Role::Warning),
+				e
+			),
+		}
+	}
+}
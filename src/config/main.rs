@@ -1,29 +1,73 @@
 //! Handling main configuration file at GPM_CONFIG.
 
+use super::batch::Summary;
+use super::code::Code;
+use super::export::{self, Conflict};
+use super::hook::Hooks;
+use super::names::NameMap;
 use super::repository;
-use super::util::{prompt, sort_keys};
-use crate::{add, error, remove, GPM_CONFIG, REPO_CONFIG, REPO_PATH};
+use super::util::{
+	copy_dir_all, create_dir_all, edit_toml, first_names, interrupted, load_toml, not_found,
+	prompt, prompt_batch, prompt_word, quote_args, save_toml, sort_keys, truncate, validate_name,
+	HistoryEntry, Porcelain, TableFormat, DESCRIPTION_WIDTH,
+};
+use crate::theme::Theme;
+use crate::theme::{Role, Themed};
+use crate::{add, error, label, remove, GPM_CONFIG, REPO_CONFIG, REPO_PATH};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use colored::Colorize;
+use path_clean::PathClean;
 use serde::{Deserialize, Serialize};
 use std::collections::{hash_map::Entry, BTreeMap, HashMap};
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{fmt, fs};
 use tabwriter::TabWriter;
 
 // Separate from the Config struct to allow more flexibility in the future.
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct TomlConfig {
+	/// Number of backups kept per configuration file
+	backup_retention: Option<usize>,
+	/// Ask before `update --all`, unless a repository overrides it
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	confirm_update_all: bool,
+	/// Flags whose values are masked in printed commands and logs
+	#[serde(skip_serializing_if = "Option::is_none")]
+	redact_flags: Option<Vec<String>>,
+	/// Key: alias name, Value: arguments it expands to
+	#[serde(
+		default,
+		skip_serializing_if = "HashMap::is_empty",
+		serialize_with = "sort_keys"
+	)]
+	alias: HashMap<String, Vec<String>>,
+	/// Commands run after operations complete
+	#[serde(default, skip_serializing_if = "Hooks::is_empty")]
+	hooks: Hooks,
+	/// Key: role, Value: color name
+	#[serde(default, skip_serializing_if = "Theme::is_default")]
+	theme: Theme,
+	/// Match repository, package and type names regardless of case, on by default on Windows
+	#[serde(skip_serializing_if = "Option::is_none")]
+	case_insensitive_names: Option<bool>,
 	/// Key: repository name, Value: repository properties
-	#[serde(serialize_with = "sort_keys")]
+	#[serde(default, serialize_with = "sort_keys")]
 	repositories: HashMap<String, TomlRepositoryProp>,
 }
 
 impl From<Config> for TomlConfig {
 	fn from(main_config: Config) -> Self {
 		Self {
+			backup_retention: main_config.backup_retention,
+			confirm_update_all: main_config.confirm_update_all,
+			redact_flags: main_config.redact_flags,
+			alias: main_config.alias,
+			hooks: main_config.hooks,
+			theme: main_config.theme,
+			case_insensitive_names: main_config.case_insensitive_names,
 			repositories: main_config
 				.repositories
 				.into_iter()
@@ -34,112 +78,594 @@ impl From<Config> for TomlConfig {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct TomlRepositoryProp {
 	/// Key: repository name, Value: repository properties
 	path: Box<str>,
+	description: Option<String>,
+	/// Refuse mutating commands unless forced
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	readonly: bool,
+	/// Ask before `update --all`, overriding the global setting
+	confirm_update_all: Option<bool>,
 }
 
 impl From<RepositoryProp> for TomlRepositoryProp {
 	fn from(repo: RepositoryProp) -> Self {
 		Self {
 			path: repo.path.to_string_lossy().into(),
+			description: repo.description,
+			readonly: repo.readonly,
+			confirm_update_all: repo.confirm_update_all,
 		}
 	}
 }
 
 /// GPM configuration.
 pub struct Config {
-	repositories: HashMap<String, RepositoryProp>,
+	repositories: NameMap<RepositoryProp>,
+	backup_retention: Option<usize>,
+	confirm_update_all: bool,
+	redact_flags: Option<Vec<String>>,
+	/// Key: alias name, Value: arguments it expands to
+	alias: HashMap<String, Vec<String>>,
+	hooks: Hooks,
+	theme: Theme,
+	case_insensitive_names: Option<bool>,
 }
 
 impl Config {
-	fn new() -> Self {
+	pub(super) fn new() -> Self {
 		Self {
-			repositories: HashMap::new(),
+			repositories: NameMap::default(),
+			backup_retention: None,
+			confirm_update_all: false,
+			redact_flags: None,
+			alias: HashMap::new(),
+			hooks: Hooks::default(),
+			theme: Theme::default(),
+			case_insensitive_names: None,
 		}
 	}
 
+	/// Number of backups kept per configuration file, if configured.
+	pub fn backup_retention(&self) -> Option<usize> {
+		self.backup_retention
+	}
+
+	/// Patterns of flags whose values are redacted, if configured.
+	pub fn redact_flags(&self) -> Option<&[String]> {
+		self.redact_flags.as_deref()
+	}
+
+	/// Commands run after operations complete.
+	pub fn hooks(&self) -> &Hooks {
+		&self.hooks
+	}
+
 	/// Load the configuration, or calls `new()` if it doesn't exist.
 	pub fn load() -> Result<Self> {
 		if !GPM_CONFIG.exists() {
 			Ok(Self::new())
 		} else {
-			toml::from_str::<TomlConfig>(&fs::read_to_string(&*GPM_CONFIG)?)
-				.map(Into::into)
-				.map_err(Into::into)
+			load_toml::<TomlConfig>(&GPM_CONFIG).map(Into::into)
+		}
+	}
+
+	/// Open the configuration in the editor, creating it first if it doesn't exist.
+	pub fn edit() -> Result<()> {
+		if !GPM_CONFIG.exists() {
+			Self::new().save()?;
 		}
+		edit_toml::<TomlConfig>(&GPM_CONFIG)
 	}
 
 	/// Save the configuration.
 	pub fn save(self) -> Result<()> {
-		fs::write(&*GPM_CONFIG, toml::to_string(&TomlConfig::from(self))?).map_err(Into::into)
+		save_toml(&GPM_CONFIG, &TomlConfig::from(self))
 	}
 
 	/// Add a repository to the configuration.
 	///
 	/// `path` is the absolute path.
-	pub fn add(&mut self, name: String, path: &Path) -> Result<()> {
+	pub fn add(
+		&mut self,
+		name: String,
+		path: &Path,
+		description: Option<String>,
+		readonly: bool,
+		allow_weird_names: bool,
+		force: bool,
+	) -> Result<()> {
+		validate_name(&name, allow_weird_names)?;
+		if !force {
+			if let Some((other, relation)) = self.overlap(path, None) {
+				bail!(
+					"path '{}' is {} repository '{}', pass '--force' to add it anyway",
+					path.display().to_string().themed(Role::Warning),
+					relation,
+					other.themed(Role::Warning)
+				);
+			}
+		}
+		if let Entry::Vacant(e) = self.repositories.entry(name.clone()) {
+			let prop = RepositoryProp::new(&name, path, description, readonly);
+			HistoryEntry::new("add")
+				.repo(&name)
+				.args(&[path.to_string_lossy()])
+				.result(&prop)
+				.reversible(&GPM_CONFIG, ())
+				.record();
+			e.insert(prop?);
+			add!(
+				"{}\t{}",
+				name.themed(Role::Name),
+				path.display().to_string().themed(Role::Path)
+			);
+			Ok(())
+		} else {
+			bail!(
+				"{} repository '{}' already exists",
+				Code::RepoExists,
+				self.repositories.registered(&name).themed(Role::Warning)
+			)
+		}
+	}
+
+	/// Register the directory `path` as repository `name` without creating it or its registry,
+	/// which the caller saves.
+	pub(super) fn adopt(&mut self, name: String, path: &Path) -> Result<()> {
+		validate_name(&name, true)?;
+		if let Some((other, relation)) = self.overlap(path, None) {
+			bail!(
+				"path '{}' is {} repository '{}'",
+				path.display().to_string().themed(Role::Warning),
+				relation,
+				other.themed(Role::Warning)
+			);
+		}
 		if let Entry::Vacant(e) = self.repositories.entry(name.clone()) {
-			e.insert(RepositoryProp::new(path)?);
-			add!("{}\t{}", name.bright_cyan(), path.to_str().unwrap());
+			e.insert(RepositoryProp {
+				path: path.into(),
+				description: None,
+				readonly: false,
+				confirm_update_all: None,
+			});
+			add!(
+				"{}\t{}",
+				name.themed(Role::Name),
+				path.display().to_string().themed(Role::Path)
+			);
 			Ok(())
 		} else {
-			bail!("repository '{}' already exists", name.bright_yellow())
+			bail!(
+				"{} repository '{}' already exists",
+				Code::RepoExists,
+				self.repositories.registered(&name).themed(Role::Warning)
+			)
+		}
+	}
+
+	/// Move a repository directory to `new_path` and update its registered path.
+	///
+	/// Falls back to copy and delete when the directory can't be renamed, e.g. across devices.
+	/// The configuration is saved, on failure the repository is left at its original location.
+	pub fn relocate(mut self, name: &str, new_path: &Path) -> Result<()> {
+		let name = &self.repositories.registered(name);
+		let result = self.move_repository(name, new_path).and_then(|old_path| {
+			self.save().inspect_err(|_| {
+				if let Err(e) = move_back(new_path, &old_path) {
+					error!(
+						"failed to move '{}' back to '{}' {}",
+						new_path.display().to_string().themed(Role::Warning),
+						old_path.display(),
+						e
+					);
+				}
+			})?;
+			add!(
+				"{}\t{} -> {}",
+				name.themed(Role::Name),
+				old_path.display(),
+				new_path.display()
+			);
+			Ok(())
+		});
+		HistoryEntry::new("relocate")
+			.repo(name)
+			.args(&[new_path.to_string_lossy()])
+			.result(&result)
+			.record();
+		result
+	}
+
+	/// Move the directory of repository `name` and update its path, returning the old path.
+	fn move_repository(&mut self, name: &str, new_path: &Path) -> Result<PathBuf> {
+		let old_path = self.get_repo_path(name)?;
+		if new_path.exists() {
+			bail!(
+				"'{}' already exists",
+				new_path.display().to_string().themed(Role::Warning)
+			);
+		}
+		if new_path.starts_with(&old_path) {
+			bail!(
+				"'{}' is inside the repository",
+				new_path.display().to_string().themed(Role::Warning)
+			);
+		}
+		if let Some((other, relation)) = self.overlap(new_path, Some(name)) {
+			bail!(
+				"path '{}' is {} repository '{}'",
+				new_path.display().to_string().themed(Role::Warning),
+				relation,
+				other.themed(Role::Warning)
+			);
 		}
+		if let Some(parent) = new_path.parent() {
+			create_dir_all(parent)?;
+		}
+
+		let verify = || {
+			repository::RepoConfig::load(name, &new_path.join(REPO_CONFIG))
+				.map_err(|e| anyhow!("repository does not load at the new location {}", e))
+		};
+		if fs::rename(&old_path, new_path).is_ok() {
+			if let Err(e) = verify() {
+				fs::rename(new_path, &old_path)?;
+				return Err(e);
+			}
+		} else {
+			if let Err(e) = copy_dir_all(&old_path, new_path).and_then(|_| verify().map(|_| ())) {
+				let _ = fs::remove_dir_all(new_path);
+				return Err(e);
+			}
+			if let Err(e) = fs::remove_dir_all(&old_path) {
+				error!(
+					"failed to remove old directory '{}' {}",
+					old_path.display().to_string().themed(Role::Warning),
+					e
+				);
+			}
+		}
+
+		self.repositories.get_mut(name).unwrap().path = new_path.into();
+		Ok(old_path.into())
+	}
+
+	/// Find a repository other than `skip` whose path overlaps with `path`, returning its name and
+	/// the relation.
+	fn overlap(&self, path: &Path, skip: Option<&str>) -> Option<(&str, &'static str)> {
+		let path = normalize(path);
+		let mut repos: Vec<_> = self
+			.repositories
+			.iter()
+			.filter(|(name, _)| Some(name.as_str()) != skip)
+			.collect();
+		repos.sort_by_key(|(name, _)| *name);
+		repos.into_iter().find_map(|(name, repo)| {
+			relation(&path, &normalize(&repo.path)).map(|r| (name.as_str(), r))
+		})
+	}
+
+	/// Check the configuration for problems, returning a description of each.
+	pub fn check(&self) -> Vec<String> {
+		let mut repos: Vec<_> = self
+			.repositories
+			.iter()
+			.map(|(name, repo)| (name, normalize(&repo.path)))
+			.collect();
+		repos.sort();
+		let mut problems = vec![];
+		for (i, (name, path)) in repos.iter().enumerate() {
+			for (other, other_path) in &repos[i + 1..] {
+				if let Some(relation) = relation(path, other_path) {
+					problems.push(format!(
+						"repository '{}' is {} repository '{}'",
+						name.themed(Role::Warning),
+						relation,
+						other.themed(Role::Warning)
+					));
+				}
+			}
+		}
+		problems
 	}
 
 	/// Remove repositories from the configuration.
-	pub fn remove(&mut self, names: Vec<String>) {
-		for name in names {
+	pub fn remove(&mut self, names: Vec<String>) -> Summary {
+		let mut summary = Summary::default();
+		let mut names = names.into_iter();
+		for name in names.by_ref() {
+			let name = self.repositories.registered(&name);
 			match self.repositories.get(&name) {
 				Some(repo) => match repo.remove() {
-					Ok(()) => remove!(
-						"{}\t{}",
-						name.bright_cyan(),
-						self.repositories
-							.remove(&name)
-							.unwrap()
-							.path
-							.to_str()
-							.unwrap()
-					),
+					Ok(()) => {
+						HistoryEntry::new("remove").repo(&name).record();
+						remove!(
+							"{}\t{}",
+							name.themed(Role::Name),
+							self.repositories.remove(&name).unwrap().path.display()
+						);
+						summary.succeed();
+					}
 					Err(e) => {
-						error!("failed to remove package '{}' {}", name.bright_yellow(), e);
-						match prompt("Remove from registry?") {
-							Ok(true) => remove!(
-								"{}\t{}",
-								name.bright_cyan(),
-								self.repositories
-									.remove(&name)
-									.unwrap()
-									.path
-									.to_str()
-									.unwrap()
-							),
-							Ok(false) => {}
-							Err(e) => error!("{}", e),
+						error!(
+							"failed to remove package '{}' {}",
+							name.themed(Role::Warning),
+							e
+						);
+						HistoryEntry::new("remove").repo(&name).failed(&e).record();
+						match prompt_batch("Remove from registry?") {
+							Ok(true) => {
+								self.unregistered(&name).record();
+								remove!(
+									"{}\t{}",
+									name.themed(Role::Name),
+									self.repositories.remove(&name).unwrap().path.display()
+								);
+								summary.succeed();
+							}
+							Ok(false) => {
+								HistoryEntry::new("remove-registry")
+									.repo(&name)
+									.skipped()
+									.record();
+								summary.skip(&name);
+							}
+							Err(e) => {
+								error!("{}", e);
+								summary.fail(&name, e);
+							}
 						}
 					}
 				},
-				None => error!("repository '{}' does not exist", name.bright_yellow()),
+				None => {
+					error!(not_found("repository", &name, self.repositories.keys()));
+					summary.fail(&name, "does not exist");
+				}
+			}
+			if interrupted() {
+				break;
+			}
+		}
+		summary.skip_rest(names);
+		summary.print();
+		summary
+	}
+
+	/// Properties of a repository for `export`, without its packages.
+	pub(super) fn export(&self, name: &str) -> export::Repository {
+		let repo = &self.repositories[name];
+		export::Repository {
+			path: repo.path.display().to_string(),
+			description: repo.description.clone(),
+			readonly: repo.readonly,
+			confirm_update_all: repo.confirm_update_all,
+			packages: BTreeMap::new(),
+			groups: BTreeMap::new(),
+		}
+	}
+
+	/// Register an exported repository at `path`, `Ok(false)` when skipped as existing.
+	///
+	/// Overwriting an existing repository replaces its properties and keeps its location, and a
+	/// directory that already holds a registry is registered as it is.
+	pub(super) fn import(
+		&mut self,
+		name: &str,
+		path: &Path,
+		repo: &export::Repository,
+		conflict: Conflict,
+	) -> Result<bool> {
+		if let Some(existing) = self.repositories.key(name).cloned() {
+			if !conflict.replace("repository", &existing)? {
+				return Ok(false);
 			}
+		} else if path.join(REPO_CONFIG).exists() {
+			validate_name(name, true)?;
+			self.repositories.insert(
+				name.to_string(),
+				RepositoryProp {
+					path: path.into(),
+					description: None,
+					readonly: false,
+					confirm_update_all: None,
+				},
+			);
+		} else {
+			self.add(name.to_string(), path, None, false, true, false)?;
 		}
+		let prop = self.repositories.get_mut(name).unwrap();
+		prop.description = repo.description.clone();
+		prop.readonly = repo.readonly;
+		prop.confirm_update_all = repo.confirm_update_all;
+		Ok(true)
+	}
+
+	/// Ask before removing every repository, requiring the word `all` to be typed.
+	pub fn confirm_remove_all(&self, registry: bool) -> Result<bool> {
+		let names = self.names();
+		prompt_word(
+			&format!(
+				"Remove all {} repositor{} ({}){}?",
+				names.len(),
+				if names.len() == 1 { "y" } else { "ies" },
+				first_names(names).themed(Role::Warning),
+				if registry {
+					""
+				} else {
+					" and delete their directories with every package"
+				}
+			),
+			"all",
+		)
+	}
+
+	/// Set or clear the description of a repository.
+	pub fn describe(&mut self, name: &str, description: Option<String>) -> Result<()> {
+		match self.repositories.get_mut(name) {
+			Some(repo) => {
+				HistoryEntry::new("describe")
+					.repo(name)
+					.args(description.as_slice())
+					.record();
+				repo.description = description.filter(|d| !d.is_empty());
+				Ok(())
+			}
+			None => bail!(not_found("repository", name, self.repositories.keys())),
+		}
+	}
+
+	/// Mark or unmark a repository as read-only.
+	pub fn set_readonly(&mut self, name: &str, readonly: bool) -> Result<()> {
+		match self.repositories.get_mut(name) {
+			Some(repo) => {
+				repo.readonly = readonly;
+				HistoryEntry::new("set-readonly")
+					.repo(name)
+					.args(&[readonly.to_string()])
+					.record();
+				Ok(())
+			}
+			None => bail!(not_found("repository", name, self.repositories.keys())),
+		}
+	}
+
+	/// Get the path of a repository, suggesting registered names if it doesn't exist.
+	pub fn get_repo_path(&self, name: &str) -> Result<Box<Path>> {
+		match self.repositories.get(name) {
+			Some(repo) => Ok(repo.path.clone()),
+			None => {
+				let mut names: Vec<_> = self.repositories.keys().map(String::as_str).collect();
+				names.sort();
+				let mut msg = not_found("repository", name, &names);
+				if names.is_empty() {
+					msg.push_str("\n  no repositories registered, add one with 'gpm add <NAME>'");
+				} else {
+					msg.push_str(&format!(
+						"\n  registered repositories: {}",
+						names.join(", ")
+					));
+				}
+				bail!(msg)
+			}
+		}
+	}
+
+	/// Whether a repository is marked read-only.
+	pub fn is_readonly(&self, name: &str) -> bool {
+		self.repositories
+			.get(name)
+			.is_some_and(|repo| repo.readonly)
+	}
+
+	/// Whether `update --all` asks for confirmation in a repository.
+	pub fn confirms_update_all(&self, name: &str) -> bool {
+		self.repositories
+			.get(name)
+			.and_then(|repo| repo.confirm_update_all)
+			.unwrap_or(self.confirm_update_all)
+	}
+
+	/// Registered spelling of repository `name`.
+	pub fn registered(&self, name: &str) -> String {
+		self.repositories.registered(name)
+	}
+
+	/// Sorted repository names.
+	pub fn names(&self) -> Vec<&str> {
+		let mut names: Vec<_> = self.repositories.keys().map(String::as_str).collect();
+		names.sort();
+		names
+	}
+
+	/// Repositories whose directory does not exist, sorted by name.
+	pub fn missing_repositories(&self) -> Vec<(&str, &Path)> {
+		let mut missing: Vec<_> = self
+			.repositories
+			.iter()
+			.filter(|(_, repo)| !repo.path.exists())
+			.map(|(name, repo)| (name.as_str(), &*repo.path))
+			.collect();
+		missing.sort();
+		missing
+	}
+
+	/// Reverse a recorded `add` or `remove-registry` of a repository, `before` is the entry before it.
+	///
+	/// Deleting the directory of an added repository is left to the user.
+	pub fn undo(&mut self, entry: &HistoryEntry, before: &serde_json::Value) -> Result<()> {
+		let name = entry.repo.as_deref().unwrap_or_default();
+		match entry.operation.as_str() {
+			"add" => {
+				let Some(repo) = self.repositories.remove(name) else {
+					bail!(not_found("repository", name, self.repositories.keys()));
+				};
+				remove!("{}\t{}", name.themed(Role::Name), repo.path.display());
+				if repo.path.exists() && prompt(&format!("Delete '{}'?", repo.path.display()))? {
+					repo.remove()?;
+				}
+			}
+			"remove-registry" => {
+				if self.repositories.contains_key(name) {
+					bail!(
+						"{} repository '{}' already exists",
+						Code::RepoExists,
+						self.repositories.registered(name).themed(Role::Warning)
+					);
+				}
+				let repo = RepositoryProp::from(serde_json::from_value::<TomlRepositoryProp>(
+					before.clone(),
+				)?);
+				add!("{}\t{}", name.themed(Role::Name), repo.path.display());
+				self.repositories.insert(name.to_string(), repo);
+			}
+			op => bail!("'{}' can't be undone", op),
+		}
+		Ok(())
 	}
 
 	/// Remove registry entries.
 	pub fn remove_registry(&mut self, names: Vec<String>) {
 		for name in names {
+			let name = self.repositories.registered(&name);
 			match self.repositories.remove(&name) {
-				Some(_) => remove!("{}", name.bright_cyan()),
-				None => error!("repository '{}' does not exist", name.bright_yellow()),
+				Some(repo) => {
+					HistoryEntry::new("remove-registry")
+						.repo(&name)
+						.reversible(&GPM_CONFIG, TomlRepositoryProp::from(repo))
+						.record();
+					remove!("{}", name.themed(Role::Name));
+				}
+				None => error!(not_found("repository", &name, self.repositories.keys())),
 			}
 		}
 	}
+
+	/// History entry for removing repository `name` from the registry, which `undo` can re-insert.
+	fn unregistered(&self, name: &str) -> HistoryEntry {
+		let before = self
+			.repositories
+			.get(name)
+			.cloned()
+			.map(TomlRepositoryProp::from);
+		HistoryEntry::new("remove-registry")
+			.repo(name)
+			.reversible(&GPM_CONFIG, before)
+	}
 }
 
 impl From<TomlConfig> for Config {
 	fn from(main_config: TomlConfig) -> Self {
 		Self {
+			backup_retention: main_config.backup_retention,
+			confirm_update_all: main_config.confirm_update_all,
+			redact_flags: main_config.redact_flags,
+			alias: main_config.alias,
+			hooks: main_config.hooks,
+			theme: main_config.theme,
+			case_insensitive_names: main_config.case_insensitive_names,
 			repositories: main_config
 				.repositories
 				.into_iter()
@@ -149,23 +675,173 @@ impl From<TomlConfig> for Config {
 	}
 }
 
-impl fmt::Display for Config {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Config {
+	/// Define an alias, `commands` are the built-in subcommands it must not shadow.
+	pub fn set_alias(
+		&mut self,
+		name: String,
+		args: Vec<String>,
+		commands: &[String],
+	) -> Result<()> {
+		validate_name(&name, false)?;
+		if commands.contains(&name) {
+			bail!(
+				"alias '{}' would shadow the built-in command",
+				name.themed(Role::Warning)
+			);
+		}
+		if args.is_empty() {
+			bail!(
+				"alias '{}' needs at least one argument",
+				name.themed(Role::Warning)
+			);
+		}
+		add!("{}\t{}", name.themed(Role::Name), quote_args(&args));
+		HistoryEntry::new("alias set")
+			.args(&[std::slice::from_ref(&name), args.as_slice()].concat())
+			.record();
+		self.alias.insert(name, args);
+		Ok(())
+	}
+
+	/// Remove an alias.
+	pub fn unset_alias(&mut self, name: &str) -> Result<()> {
+		match self.alias.remove(name) {
+			Some(_) => {
+				HistoryEntry::new("alias unset").args(&[name]).record();
+				remove!("{}", name.themed(Role::Name));
+				Ok(())
+			}
+			None => bail!(not_found("alias", name, self.alias.keys())),
+		}
+	}
+
+	/// List aliases with their expansion.
+	pub fn aliases(&self) -> String {
 		let mut tw = TabWriter::new(vec![]);
-		writeln!(&mut tw, "{}", "Repositories:".bright_green()).unwrap();
+		writeln!(&mut tw, "{}", "Aliases:".themed(Role::Success)).unwrap();
+		let btree_map: BTreeMap<_, _> = self.alias.iter().collect();
+		for (name, args) in &btree_map {
+			writeln!(
+				&mut tw,
+				"  {}\t{}",
+				name.themed(Role::Name),
+				quote_args(args)
+			)
+			.unwrap();
+		}
+		tw.flush().unwrap();
+		String::from_utf8(tw.into_inner().unwrap()).unwrap()
+	}
+
+	/// List repositories with their packages nested beneath.
+	///
+	/// A repository whose registry fails to load is shown with the error instead.
+	pub fn tree(&self) -> String {
+		let mut tw = TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", "Repositories:".themed(Role::Success)).unwrap();
 		let btree_map: BTreeMap<_, _> = self.repositories.iter().collect();
-		for (name, ns) in &btree_map {
+		for (name, repo) in &btree_map {
 			writeln!(
 				&mut tw,
 				"  {}\t{}",
-				name.bright_cyan(),
-				ns.path.to_str().unwrap()
+				name.themed(Role::Name),
+				repo.path.display().to_string().themed(Role::Path)
+			)
+			.unwrap();
+			match repository::RepoConfig::load(name, &repo.path.join(REPO_CONFIG)) {
+				Ok(repo_cfg) => {
+					for (package, r#type) in repo_cfg.types() {
+						writeln!(&mut tw, "    {}\t{}", package, r#type.themed(Role::Type))
+							.unwrap();
+					}
+				}
+				Err(e) => {
+					let e = e.to_string();
+					writeln!(
+						&mut tw,
+						"    {}\t{}",
+						"error:".themed(Role::Error).bold(),
+						e.lines().next().unwrap_or_default()
+					)
+					.unwrap();
+				}
+			}
+		}
+		tw.flush().unwrap();
+		String::from_utf8(tw.into_inner().unwrap()).unwrap()
+	}
+}
+
+impl Config {
+	/// List repositories as CSV or TSV, `verify` also parses each registry for the `status`
+	/// column.
+	pub fn table(&self, format: TableFormat, verify: bool) -> String {
+		format.table(
+			&["name", "path", "description", "readonly", "status"],
+			&self.table_rows(verify),
+		)
+	}
+
+	/// List repositories in the columns of `table`.
+	pub fn porcelain(&self, porcelain: Porcelain, verify: bool) -> String {
+		porcelain.render(&self.table_rows(verify))
+	}
+
+	fn table_rows(&self, verify: bool) -> Vec<Vec<String>> {
+		let btree_map: BTreeMap<_, _> = self.repositories.iter().collect();
+		btree_map
+			.into_iter()
+			.map(|(name, repo)| {
+				vec![
+					name.clone(),
+					repo.path.display().to_string(),
+					repo.description.clone().unwrap_or_default(),
+					repo.readonly.to_string(),
+					repo.problem(name, verify)
+						.map_or("ok", |(problem, _)| problem)
+						.to_string(),
+				]
+			})
+			.collect()
+	}
+
+	/// List repositories, marking those whose directory or registry is missing, `verify` also
+	/// marks registries that don't parse.
+	pub fn list(&self, verify: bool) -> String {
+		let mut tw = TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", "Repositories:".themed(Role::Success)).unwrap();
+		let btree_map: BTreeMap<_, _> = self.repositories.iter().collect();
+		for (name, ns) in &btree_map {
+			writeln!(
+				&mut tw,
+				"  {}{}{}\t{}\t{}",
+				name.themed(Role::Name),
+				if ns.readonly {
+					label(" 🔒", " (readonly)")
+				} else {
+					""
+				},
+				ns.problem(name, verify)
+					.map(|(problem, role)| format!(" ({})", problem).themed(role).to_string())
+					.unwrap_or_default(),
+				ns.path.display().to_string().themed(Role::Path),
+				truncate(
+					ns.description.as_deref().unwrap_or_default(),
+					DESCRIPTION_WIDTH
+				)
+				.bright_white()
 			)
 			.unwrap();
 		}
 		tw.flush().unwrap();
-		let result = String::from_utf8(tw.into_inner().unwrap()).unwrap();
-		write!(f, "{}", result)
+		String::from_utf8(tw.into_inner().unwrap()).unwrap()
+	}
+}
+
+impl fmt::Display for Config {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.list(false))
 	}
 }
 
@@ -176,42 +852,84 @@ impl Default for Config {
 }
 
 /// Property of a repository in the GPM configuration.
+#[derive(Clone)]
 struct RepositoryProp {
 	/// Full path to the repository directory
 	path: Box<Path>,
+	description: Option<String>,
+	readonly: bool,
+	confirm_update_all: Option<bool>,
 }
 
 impl RepositoryProp {
 	/// Create a new repository property, creating the repository directory and configuration file.
-	fn new(path: &Path) -> Result<Self> {
-		fs::create_dir_all(path)?;
+	fn new(name: &str, path: &Path, description: Option<String>, readonly: bool) -> Result<Self> {
+		create_dir_all(path)?;
 		let cfg_path = path.join(REPO_CONFIG);
-		repository::RepoConfig::new(path).save(&cfg_path)?;
+		repository::RepoConfig::new(name, path)?.save(&cfg_path)?;
 		Ok(Self {
 			path: REPO_PATH.join(path).into_boxed_path(),
+			description: description.filter(|d| !d.is_empty()),
+			readonly,
+			confirm_update_all: None,
 		})
 	}
 
+	/// What makes the repository unusable with the role to show it in, `verify` also parses
+	/// the registry.
+	fn problem(&self, name: &str, verify: bool) -> Option<(&'static str, Role)> {
+		let cfg_path = self.path.join(REPO_CONFIG);
+		if !self.path.exists() {
+			Some(("missing", Role::Error))
+		} else if !cfg_path.exists() {
+			Some(("no registry", Role::Warning))
+		} else if verify && repository::RepoConfig::load(name, &cfg_path).is_err() {
+			Some(("invalid registry", Role::Error))
+		} else {
+			None
+		}
+	}
+
 	fn remove(&self) -> Result<()> {
 		fs::remove_dir_all(&self.path)?;
 		Ok(())
 	}
 }
 
+/// Canonicalize a path if it exists, otherwise clean it.
+fn normalize(path: &Path) -> PathBuf {
+	dunce::canonicalize(path).unwrap_or_else(|_| path.clean())
+}
+
+/// Describe how `path` overlaps with `other`, if it does.
+/// Move a relocated repository back when the configuration couldn't be saved.
+fn move_back(from: &Path, to: &Path) -> Result<()> {
+	if fs::rename(from, to).is_err() {
+		copy_dir_all(from, to)?;
+		fs::remove_dir_all(from)?;
+	}
+	Ok(())
+}
+
+fn relation(path: &Path, other: &Path) -> Option<&'static str> {
+	if path == other {
+		Some("the same directory as")
+	} else if other.starts_with(path) {
+		Some("a parent of")
+	} else if path.starts_with(other) {
+		Some("inside")
+	} else {
+		None
+	}
+}
+
 impl From<TomlRepositoryProp> for RepositoryProp {
 	fn from(repo: TomlRepositoryProp) -> Self {
 		Self {
 			path: Path::new(&*repo.path).into(),
+			description: repo.description,
+			readonly: repo.readonly,
+			confirm_update_all: repo.confirm_update_all,
 		}
 	}
 }
-
-pub fn get_repo_path(name: &str) -> Box<Path> {
-	Config::load()
-		.unwrap_or_default()
-		.repositories
-		.get(name)
-		.unwrap()
-		.path
-		.clone()
-}
@@ -0,0 +1,405 @@
+//! Conversion of the configurations of releases with namespaces instead of repositories, run by
+//! `init --migrate`.
+//!
+//! Those releases listed the namespaces in the `[namespaces]` table of config.toml, each with the
+//! `path` of its directory, relative paths being inside the gpm home directory. A namespace kept
+//! its packages in NAMESPACES_CONFIG there, each with a `url` telling how it's installed, one of
+//! `{ Git = "<URL>" }`, `{ Zip = "<URL>" }`, `{ Exe = "<URL>" }` and `{ Local = "<PATH>" }`, and
+//! the `version` installed.
+
+use super::main::Config;
+use super::r#type::TypeConfig;
+use super::repository::RepoConfig;
+use super::util::{create_dir_all, expand_tilde};
+use crate::theme::{Role, Themed};
+use crate::{error, note, BACKUP_PATH, GPM_CONFIG, PROFILE_ROOT, REPO_CONFIG, TYPES_CONFIG};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Registry of a namespace, in its directory.
+const NAMESPACES_CONFIG: &str = "namespace.toml";
+
+/// Kinds of `url` of a package, with the type its packages get.
+const URL_TYPES: [(&str, &str); 4] = [
+	("Git", "git"),
+	("Zip", "zip"),
+	("Exe", "exe"),
+	("Local", "local"),
+];
+
+/// A package of a namespace as a package of a repository.
+struct Package<'a> {
+	r#type: &'static str,
+	args: Box<[String]>,
+	tag: Option<String>,
+	/// Fields left out
+	ignored: Vec<&'a str>,
+}
+
+/// Whether config.toml is from a release with namespaces.
+pub fn detect() -> bool {
+	read_table(&GPM_CONFIG).is_ok_and(|table| table.contains_key("namespaces"))
+}
+
+/// Directory the originals are moved to.
+pub fn backup_path() -> PathBuf {
+	BACKUP_PATH.join("namespaces")
+}
+
+/// Convert the namespaces to repositories and their packages to types and args, moving the
+/// original files to `backup_path()`, and print what needs attention.
+///
+/// Packages keep their files and the installed version as their tag, and are pending when the
+/// files are missing. Types without a built-in script are left for the user to register.
+/// Everything is converted before anything is written, and a failed write undoes the others so
+/// the migration can be run again.
+pub fn run() -> Result<()> {
+	let mut old_config = read_table(&GPM_CONFIG)?;
+	let Some(namespaces) = old_config.remove("namespaces") else {
+		bail!(
+			"'{}' has no namespaces, nothing to migrate",
+			GPM_CONFIG.display().to_string().themed(Role::Warning)
+		);
+	};
+	let backup = backup_path();
+	if backup.exists() {
+		bail!(
+			"'{}' already exists, move it away to migrate again",
+			backup.display().to_string().themed(Role::Warning)
+		);
+	}
+
+	let mut attention = vec![];
+	for key in old_config.keys() {
+		attention.push(format!(
+			"setting '{}' of config.toml is not converted, see '{}'",
+			key.themed(Role::Warning),
+			backup.join("config.toml").display()
+		));
+	}
+	let namespaces = match namespaces {
+		toml::Value::Table(namespaces) => namespaces,
+		_ => {
+			attention.push("'namespaces' of config.toml is not a table".to_string());
+			toml::Table::new()
+		}
+	};
+
+	let mut gpm_cfg = Config::new();
+	// Key: type, Value: packages of that type as `repository/package`
+	let mut needed: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+	// Directories with their registries, and the names of the namespaces with their
+	// NAMESPACES_CONFIG
+	let mut registries = vec![];
+	let mut originals = vec![];
+	let mut package_count = 0;
+	for (name, namespace) in &namespaces {
+		let path = match namespace_path(namespace) {
+			Ok(Some(path)) => path,
+			Ok(None) => {
+				attention.push(format!(
+					"namespace '{}' has no path, add its directory with 'gpm add'",
+					name.themed(Role::Warning)
+				));
+				continue;
+			}
+			Err(e) => {
+				attention.push(format!(
+					"namespace '{}' is not converted, {}",
+					name.themed(Role::Warning),
+					e
+				));
+				continue;
+			}
+		};
+		let old_path = path.join(NAMESPACES_CONFIG);
+		let old = if old_path.exists() {
+			match read_table(&old_path) {
+				Ok(old) => old,
+				Err(e) => {
+					attention.push(format!(
+						"namespace '{}' is not converted, {}",
+						name.themed(Role::Warning),
+						e
+					));
+					continue;
+				}
+			}
+		} else {
+			toml::Table::new()
+		};
+		if path.join(REPO_CONFIG).exists() {
+			attention.push(format!(
+				"namespace '{}' is not converted, '{}' already has a {}",
+				name.themed(Role::Warning),
+				path.display(),
+				REPO_CONFIG
+			));
+			continue;
+		}
+		if let Err(e) = gpm_cfg.adopt(name.clone(), &path) {
+			attention.push(format!(
+				"namespace '{}' is not converted, {}",
+				name.themed(Role::Warning),
+				e
+			));
+			continue;
+		}
+
+		let mut repo_cfg = RepoConfig::new(name, &path)?;
+		for (key, value) in &old {
+			if key != "packages" {
+				attention.push(format!(
+					"setting '{}' of namespace '{}' is not converted",
+					key.themed(Role::Warning),
+					name
+				));
+				continue;
+			}
+			let Some(packages) = value.as_table() else {
+				attention.push(format!("packages of namespace '{}' are not a table", name));
+				continue;
+			};
+			for (package, value) in packages {
+				let location = format!("{}/{}", name, package);
+				match convert(value) {
+					Ok(Package {
+						r#type,
+						args,
+						tag,
+						ignored,
+					}) => {
+						if let Err(e) = repo_cfg.adopt(package.clone(), r#type.into(), args, tag) {
+							attention.push(format!(
+								"package '{}' is not converted, {}",
+								location.themed(Role::Warning),
+								e
+							));
+							continue;
+						}
+						for key in ignored {
+							attention.push(format!(
+								"field '{}' of package '{}' is not converted",
+								key.themed(Role::Warning),
+								location
+							));
+						}
+						needed.entry(r#type).or_default().push(location);
+						package_count += 1;
+					}
+					Err(e) => attention.push(format!(
+						"package '{}' is not converted, {}",
+						location.themed(Role::Warning),
+						e
+					)),
+				}
+			}
+		}
+		if old_path.exists() {
+			originals.push((name.as_str(), old_path));
+		}
+		registries.push((path, repo_cfg));
+	}
+
+	let count = registries.len();
+	write(
+		&backup,
+		gpm_cfg,
+		registries,
+		&originals,
+		&needed,
+		&mut attention,
+	)?;
+	for (_, old_path) in &originals {
+		if let Err(e) = fs::remove_file(old_path) {
+			attention.push(format!(
+				"'{}' is backed up but not removed, {}",
+				old_path.display(),
+				e
+			));
+		}
+	}
+
+	println!(
+		"{} {} namespace{} to repositories with {} package{}, the originals are in {}",
+		"Migrated".themed(Role::Success),
+		count,
+		if count == 1 { "" } else { "s" },
+		package_count,
+		if package_count == 1 { "" } else { "s" },
+		backup.display().to_string().themed(Role::Path)
+	);
+	for item in &attention {
+		note!("{}", item);
+	}
+	Ok(())
+}
+
+/// Back up the originals, then write the registries, the built-in types and config.toml,
+/// undoing all of it when a step fails.
+fn write(
+	backup: &Path,
+	gpm_cfg: Config,
+	registries: Vec<(PathBuf, RepoConfig)>,
+	originals: &[(&str, PathBuf)],
+	needed: &BTreeMap<&str, Vec<String>>,
+	attention: &mut Vec<String>,
+) -> Result<()> {
+	let types_before = fs::read(&*TYPES_CONFIG).ok();
+	let mut written = vec![];
+	let mut replaced = false;
+	let result = (|| -> Result<()> {
+		create_dir_all(backup)?;
+		fs::copy(&*GPM_CONFIG, backup.join("config.toml"))?;
+		for (name, old_path) in originals {
+			let namespace_backup = backup.join(name);
+			create_dir_all(&namespace_backup)?;
+			fs::copy(old_path, namespace_backup.join(NAMESPACES_CONFIG))?;
+		}
+
+		for (path, repo_cfg) in registries {
+			create_dir_all(&path)?;
+			let cfg_path = path.join(REPO_CONFIG);
+			written.push(cfg_path.clone());
+			repo_cfg.save(&cfg_path)?;
+		}
+
+		let mut type_cfg = TypeConfig::load()?;
+		let mut registered = false;
+		for (r#type, packages) in needed {
+			if type_cfg.has_type(r#type) {
+				continue;
+			}
+			if type_cfg.add_builtin(r#type)? {
+				registered = true;
+			} else {
+				attention.push(format!(
+					"type '{}' is not registered, write its script with 'gpm type add {}' for {}",
+					r#type.themed(Role::Warning),
+					r#type,
+					packages.join(", ")
+				));
+			}
+		}
+		if registered {
+			type_cfg.save()?;
+		}
+
+		replaced = true;
+		fs::remove_file(&*GPM_CONFIG)?;
+		gpm_cfg.save()
+	})();
+	if result.is_err() && undo(backup, &written, types_before, replaced) {
+		note!(
+			"nothing was migrated, '{}' is unchanged",
+			GPM_CONFIG.display()
+		);
+	}
+	result
+}
+
+/// Remove the `written` registries, restore types.toml and config.toml if it was `replaced`, and
+/// remove the backup unless something couldn't be restored. Returns whether everything was.
+fn undo(backup: &Path, written: &[PathBuf], types_before: Option<Vec<u8>>, replaced: bool) -> bool {
+	let mut steps: Vec<(&Path, io::Result<()>)> = written
+		.iter()
+		.filter(|path| path.exists())
+		.map(|path| (path.as_path(), fs::remove_file(path)))
+		.collect();
+	let types = match types_before {
+		Some(content) => fs::write(&*TYPES_CONFIG, content),
+		None if TYPES_CONFIG.exists() => fs::remove_file(&*TYPES_CONFIG),
+		None => Ok(()),
+	};
+	steps.push((&TYPES_CONFIG, types));
+	if replaced {
+		let config = fs::copy(backup.join("config.toml"), &*GPM_CONFIG).map(drop);
+		steps.push((&GPM_CONFIG, config));
+	}
+	let mut failed = false;
+	for (path, result) in steps {
+		if let Err(e) = result {
+			error!("failed to undo the changes to '{}' {}", path.display(), e);
+			failed = true;
+		}
+	}
+	if failed {
+		note!("the originals are kept in '{}'", backup.display());
+	} else if let Err(e) = fs::remove_dir_all(backup) {
+		error!("failed to remove '{}' {}", backup.display(), e);
+		failed = true;
+	}
+	!failed
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+	Ok(fs::read_to_string(path)?.parse()?)
+}
+
+/// Directory of a namespace, given as a path or a table with a `path`.
+fn namespace_path(namespace: &toml::Value) -> Result<Option<PathBuf>> {
+	let path = match namespace {
+		toml::Value::String(path) => path,
+		toml::Value::Table(table) => match table.get("path").and_then(|p| p.as_str()) {
+			Some(path) => path,
+			None => return Ok(None),
+		},
+		_ => return Ok(None),
+	};
+	Ok(Some(PROFILE_ROOT.join(expand_tilde(Path::new(path))?)))
+}
+
+/// Type, args and tag of a package of a namespace.
+fn convert(package: &toml::Value) -> Result<Package<'_>> {
+	let Some(package) = package.as_table() else {
+		bail!("it is not a table");
+	};
+	let Some(url) = package.get("url") else {
+		bail!("it has no url");
+	};
+	let (kind, value) = match url
+		.as_table()
+		.map(|url| url.iter().collect::<Vec<_>>())
+		.as_deref()
+	{
+		Some([(kind, value)]) => (kind.as_str(), *value),
+		_ => bail!("its url '{}' is not one of {}", url, kinds()),
+	};
+	let Some((_, r#type)) = URL_TYPES.iter().find(|(k, _)| *k == kind) else {
+		bail!("its url kind '{}' is not one of {}", kind, kinds());
+	};
+	let Some(value) = value.as_str() else {
+		bail!("its {} url is not a string", kind);
+	};
+	let tag = match package.get("version") {
+		Some(version) => match version.as_str() {
+			Some(version) if !version.is_empty() => Some(version.to_string()),
+			Some(_) => None,
+			None => bail!("its version is not a string"),
+		},
+		None => None,
+	};
+	let ignored = package
+		.keys()
+		.map(String::as_str)
+		.filter(|key| !["url", "version"].contains(key))
+		.collect();
+	Ok(Package {
+		r#type,
+		args: Box::new([value.to_string()]),
+		tag,
+		ignored,
+	})
+}
+
+fn kinds() -> String {
+	URL_TYPES
+		.iter()
+		.map(|(kind, _)| *kind)
+		.collect::<Vec<_>>()
+		.join(", ")
+}
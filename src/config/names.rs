@@ -0,0 +1,163 @@
+//! Maps keyed by registered names, matching names regardless of case when configured.
+
+use super::util::raw_config;
+
+use once_cell::sync::OnceCell;
+use std::collections::hash_map::{self, Entry, HashMap};
+use std::ops::{Deref, DerefMut};
+
+/// Match names regardless of case, from `case_insensitive_names` in config.toml. Set once in
+/// `main`.
+static CASE_INSENSITIVE: OnceCell<bool> = OnceCell::new();
+
+/// Read `case_insensitive_names` from config.toml, on by default on Windows where the file system
+/// ignores case.
+pub fn init() {
+	let enabled = raw_config()
+		.and_then(|table| table.get("case_insensitive_names")?.as_bool())
+		.unwrap_or(cfg!(windows));
+	CASE_INSENSITIVE.set(enabled).unwrap();
+}
+
+pub fn case_insensitive() -> bool {
+	CASE_INSENSITIVE.get().copied().unwrap_or(cfg!(windows))
+}
+
+/// Map from registered names, whose lookups ignore case when case-insensitive names are enabled.
+///
+/// Keys keep the spelling they were registered with, and a name differing only by case from a
+/// registered one finds it instead of adding another entry.
+#[derive(Debug, Clone)]
+pub struct NameMap<V>(HashMap<String, V>);
+
+impl<V> Default for NameMap<V> {
+	fn default() -> Self {
+		Self(HashMap::new())
+	}
+}
+
+impl<V> NameMap<V> {
+	/// Registered key matching `name`.
+	pub fn key(&self, name: &str) -> Option<&String> {
+		match self.0.get_key_value(name) {
+			Some((key, _)) => Some(key),
+			None if case_insensitive() => {
+				let name = name.to_lowercase();
+				self.0.keys().find(|key| key.to_lowercase() == name)
+			}
+			None => None,
+		}
+	}
+
+	pub fn get(&self, name: &str) -> Option<&V> {
+		self.0.get(self.key(name)?)
+	}
+
+	pub fn get_mut(&mut self, name: &str) -> Option<&mut V> {
+		let key = self.key(name)?.clone();
+		self.0.get_mut(&key)
+	}
+
+	pub fn contains_key(&self, name: &str) -> bool {
+		self.key(name).is_some()
+	}
+
+	pub fn remove(&mut self, name: &str) -> Option<V> {
+		let key = self.key(name)?.clone();
+		self.0.remove(&key)
+	}
+
+	/// Entry of `name`, occupied when it matches a registered name.
+	pub fn entry(&mut self, name: String) -> Entry<'_, String, V> {
+		let key = self.key(&name).cloned().unwrap_or(name);
+		self.0.entry(key)
+	}
+
+	/// Registered spelling of `name`, unchanged when it isn't registered.
+	pub fn registered(&self, name: &str) -> String {
+		self.key(name)
+			.map_or_else(|| name.to_string(), String::clone)
+	}
+}
+
+impl<V> Deref for NameMap<V> {
+	type Target = HashMap<String, V>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl<V> DerefMut for NameMap<V> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl<V> FromIterator<(String, V)> for NameMap<V> {
+	fn from_iter<I: IntoIterator<Item = (String, V)>>(iter: I) -> Self {
+		Self(iter.into_iter().collect())
+	}
+}
+
+impl<V> IntoIterator for NameMap<V> {
+	type Item = (String, V);
+	type IntoIter = hash_map::IntoIter<String, V>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.into_iter()
+	}
+}
+
+impl<'a, V> IntoIterator for &'a NameMap<V> {
+	type Item = (&'a String, &'a V);
+	type IntoIter = hash_map::Iter<'a, String, V>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.iter()
+	}
+}
+
+impl<'a, V> IntoIterator for &'a mut NameMap<V> {
+	type Item = (&'a String, &'a mut V);
+	type IntoIter = hash_map::IterMut<'a, String, V>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.iter_mut()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn map() -> NameMap<u8> {
+		CASE_INSENSITIVE.get_or_init(|| true);
+		NameMap::from_iter([("Tools".to_string(), 1)])
+	}
+
+	#[test]
+	fn lookup_ignores_case() {
+		let map = map();
+		assert_eq!(map.get("tools"), Some(&1));
+		assert_eq!(map.get("TOOLS"), Some(&1));
+		assert_eq!(map.registered("tOOls"), "Tools");
+		assert_eq!(map.registered("other"), "other");
+		assert!(!map.contains_key("tool"));
+	}
+
+	#[test]
+	fn add_conflicts_with_other_case() {
+		let mut map = map();
+		assert!(matches!(map.entry("tools".to_string()), Entry::Occupied(_)));
+		assert!(matches!(map.entry("tool".to_string()), Entry::Vacant(_)));
+		assert_eq!(map.len(), 1);
+	}
+
+	#[test]
+	fn remove_by_other_case() {
+		let mut map = map();
+		assert_eq!(map.remove("TOOLS"), Some(1));
+		assert!(map.is_empty());
+	}
+}
@@ -0,0 +1,117 @@
+//! Handling profiles under GPM_HOME/profiles.
+
+use super::util::{prompt, validate_name};
+use crate::theme::{Role, Themed};
+use crate::{add, error, remove, PROFILE, PROFILES_PATH};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::fs;
+use std::io::Write;
+use tabwriter::TabWriter;
+
+/// Name displayed for the unnamed default profile.
+const DEFAULT_PROFILE: &str = "default";
+
+/// Check that `name` can name a profile, `default` is taken by the unnamed default profile.
+pub fn validate(name: &str) -> Result<()> {
+	validate_name(name, false)?;
+	if name == DEFAULT_PROFILE {
+		bail!(
+			"'{}' is the default profile, omit --profile to use it",
+			name.themed(Role::Warning)
+		);
+	}
+	Ok(())
+}
+
+/// Check the profile selected with `--profile`, which has to exist unless `create` is set for
+/// commands creating it.
+pub fn select(name: &str, create: bool) -> Result<()> {
+	validate(name)?;
+	if !create && !PROFILES_PATH.join(name).is_dir() {
+		bail!(
+			"profile '{}' does not exist, create it with 'gpm profile create {}'",
+			name.themed(Role::Warning),
+			name
+		);
+	}
+	Ok(())
+}
+
+/// Create a new profile with its directories.
+pub fn create(name: &str) -> Result<()> {
+	validate(name)?;
+	let path = PROFILES_PATH.join(name);
+	if path.exists() {
+		bail!("profile '{}' already exists", name.themed(Role::Warning));
+	}
+	fs::create_dir_all(path.join("repositories"))?;
+	fs::create_dir_all(path.join("scripts"))?;
+	add!("{}\t{}", name.themed(Role::Name), path.display());
+	Ok(())
+}
+
+/// Remove profiles and everything under them.
+pub fn remove(names: Vec<String>) {
+	for name in names {
+		if let Err(e) = validate(&name) {
+			error!(e);
+			continue;
+		}
+		let path = PROFILES_PATH.join(&name);
+		if !path.is_dir() {
+			error!("profile '{}' does not exist", name.themed(Role::Warning));
+			continue;
+		}
+		match prompt(&format!(
+			"Remove profile '{}' and all its repositories?",
+			name.themed(Role::Warning)
+		)) {
+			Ok(true) => match fs::remove_dir_all(&path) {
+				Ok(()) => remove!("{}", name.themed(Role::Name)),
+				Err(e) => error!(
+					"failed to remove profile '{}' {}",
+					name.themed(Role::Warning),
+					e
+				),
+			},
+			Ok(false) => {}
+			Err(e) => error!(e),
+		}
+	}
+}
+
+/// List all profiles, marking the active one.
+pub fn list() -> Result<String> {
+	let mut names = vec![];
+	if PROFILES_PATH.is_dir() {
+		for entry in fs::read_dir(&*PROFILES_PATH)? {
+			let entry = entry?;
+			if entry.file_type()?.is_dir() {
+				names.push(entry.file_name().to_string_lossy().into_owned());
+			}
+		}
+	}
+	names.sort();
+
+	let active = PROFILE.get().and_then(Option::as_deref);
+	let mut tw = TabWriter::new(vec![]);
+	writeln!(&mut tw, "{}", "Profiles:".themed(Role::Success))?;
+	writeln!(
+		&mut tw,
+		"  {}\t{}",
+		DEFAULT_PROFILE.themed(Role::Name),
+		if active.is_none() { "*" } else { "" }
+	)?;
+	for name in &names {
+		writeln!(
+			&mut tw,
+			"  {}\t{}",
+			name.themed(Role::Name),
+			if active == Some(name) { "*" } else { "" }
+		)?;
+	}
+	tw.flush()?;
+	Ok(String::from_utf8(tw.into_inner().unwrap())?)
+}
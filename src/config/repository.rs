@@ -1,23 +1,64 @@
 //! Handling packages under repositories.
 
-use super::r#type::TypeConfig;
-use super::util::{prompt, sort_keys};
-use crate::{add, clone, error, remove, REPO_PATH};
+use super::batch::Summary;
+use super::code::Code;
+use super::export::{self, Conflict};
+use super::names::{case_insensitive, NameMap};
+use super::r#type::{self, Target, TypeConfig};
+use super::shim::ShimConfig;
+use super::spec::Spec;
+use super::util::{
+	contract_tilde, did_you_mean, edit_toml, expand_tilde, first_names, human_size, ignores_case,
+	interrupted, load_toml, not_found, powershell_string, prompt, prompt_batch, quote_args,
+	relative_time, save_toml, shell_quote, sort_keys, timestamp, truncate, validate_name,
+	HistoryEntry, OfflineError, Porcelain, TableFormat, DESCRIPTION_WIDTH,
+};
+use crate::checksum::{self, Changes};
+use crate::copy::Copier;
+use crate::du;
+use crate::redact;
+use crate::theme::{Role, Themed};
+use crate::{
+	add, change, clone, error, label, note, plain, remove, status, verbosity, Verbosity,
+	REPO_CONFIG, REPO_PATH,
+};
 
-use anyhow::{anyhow, bail, Result};
-use colored::Colorize;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::ValueEnum;
+use colored::{ColoredString, Colorize};
+use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
-use std::collections::{hash_map::Entry, BTreeMap, HashMap};
-use std::io::Write;
-use std::path::Path;
+use serde_json::json;
+use std::cmp::Reverse;
+use std::collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::{env, fmt, fs, io};
 
+/// Width of the tag column in `list --long`.
+const TAG_WIDTH: usize = 16;
+/// The args column is never truncated below this width, however narrow the terminal.
+const MIN_ARGS_WIDTH: usize = 16;
+/// Spaces tabwriter puts between columns.
+const COLUMN_PADDING: usize = 2;
+/// Updates taking at least this long are annotated with their duration.
+const SLOW_UPDATE: Duration = Duration::from_secs(5);
+/// Number of packages shown in the summary of slow updates.
+const SLOWEST_SHOWN: usize = 5;
+
 // Separate from the Config struct to allow more flexibility in the future.
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct TomlRepoConfig {
 	/// Key: package name, Value: package details
-	#[serde(serialize_with = "sort_keys")]
+	#[serde(default, serialize_with = "sort_keys")]
 	packages: HashMap<String, TomlPackage>,
+	/// Key: group name, Value: names of member packages
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	groups: BTreeMap<String, Vec<String>>,
 }
 
 impl From<RepoConfig> for TomlRepoConfig {
@@ -28,17 +69,83 @@ impl From<RepoConfig> for TomlRepoConfig {
 				.into_iter()
 				.map(|(name, package)| (name, package.into()))
 				.collect(),
+			groups: repo.groups,
 		}
 	}
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct TomlPackage {
 	r#type: String,
 	args: Box<[String]>,
 	/// Tag of the package
 	tag: Option<String>,
 	cwd: Option<String>,
+	description: Option<String>,
+	/// Labels used to select packages
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	tags: Vec<String>,
+	/// When the package was added, RFC 3339
+	#[serde(skip_serializing_if = "Option::is_none")]
+	added_at: Option<String>,
+	/// When an update last changed the tag, RFC 3339
+	#[serde(skip_serializing_if = "Option::is_none")]
+	updated_at: Option<String>,
+	/// Result of the last run of the script, omitted when it succeeded
+	#[serde(default, skip_serializing_if = "State::is_ok")]
+	state: State,
+	/// Size of the installed files after the last run
+	#[serde(skip_serializing_if = "Option::is_none")]
+	size_bytes: Option<u64>,
+	/// SHA-256 digest of the installed files after the last run, checked by `verify`
+	#[serde(skip_serializing_if = "Option::is_none")]
+	checksum: Option<String>,
+	/// Don't record a checksum, for packages that modify their own files
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	no_verify: bool,
+	/// First line of the error of the last failed run
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<String>,
+	/// When the last run failed, RFC 3339
+	#[serde(skip_serializing_if = "Option::is_none")]
+	failed_at: Option<String>,
+}
+
+/// Result of the last run of a package's script.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+	#[default]
+	Ok,
+	Failed,
+	/// Registered without running the script, see `install`
+	Pending,
+}
+
+impl State {
+	fn is_ok(&self) -> bool {
+		*self == State::Ok
+	}
+
+	/// Marker after the package name in `list`, empty when ok.
+	fn marker(&self) -> ColoredString {
+		match self {
+			State::Ok => "".normal(),
+			State::Failed => label(" ✗", " (failed)").themed(Role::Error),
+			State::Pending => label(" …", " (pending)").themed(Role::Warning),
+		}
+	}
+}
+
+impl fmt::Display for State {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			State::Ok => write!(f, "ok"),
+			State::Failed => write!(f, "failed"),
+			State::Pending => write!(f, "pending"),
+		}
+	}
 }
 
 impl From<Package> for TomlPackage {
@@ -48,6 +155,16 @@ impl From<Package> for TomlPackage {
 			args: package.args,
 			tag: package.tag,
 			cwd: package.cwd,
+			description: package.description,
+			tags: package.tags,
+			added_at: package.added_at,
+			updated_at: package.updated_at,
+			size_bytes: package.size_bytes,
+			checksum: package.checksum,
+			no_verify: package.no_verify,
+			state: package.state,
+			error: package.error,
+			failed_at: package.failed_at,
 		}
 	}
 }
@@ -55,251 +172,2195 @@ impl From<Package> for TomlPackage {
 #[derive(Debug)]
 pub struct RepoConfig {
 	/// Key: package name, Value: package details
-	packages: HashMap<String, Package>,
+	packages: NameMap<Package>,
+	/// Key: group name, Value: names of member packages
+	groups: BTreeMap<String, Vec<String>>,
 	type_config: TypeConfig,
+	/// Name of the repository, used in the history journal
+	name: String,
 	/// Path to the repository
 	path: Box<Path>,
 }
 
 impl RepoConfig {
-	/// Create a empty config, panic if failed to load TypeConfig.
-	pub fn new(path: &Path) -> Self {
-		Self {
-			packages: HashMap::new(),
-			type_config: TypeConfig::load().expect("failed to load type config"),
+	/// Create a empty config.
+	pub fn new(name: &str, path: &Path) -> Result<Self> {
+		Ok(Self {
+			packages: NameMap::default(),
+			groups: BTreeMap::new(),
+			type_config: TypeConfig::load()?,
+			name: name.to_string(),
 			path: REPO_PATH.join(path).into_boxed_path(),
+		})
+	}
+
+	/// Load repository `name` from a TOML file at path.
+	pub fn load(name: &str, path: &Path) -> Result<Self> {
+		let config = load_toml::<TomlRepoConfig>(path)?;
+		Ok(Self {
+			packages: config
+				.packages
+				.into_iter()
+				.map(|(name, package)| (name, package.into()))
+				.collect(),
+			groups: config.groups,
+			type_config: TypeConfig::load()?,
+			name: name.to_string(),
+			path: path.parent().unwrap().into(),
+		})
+	}
+
+	/// Packages without the state of their last run, and the groups, for `export`.
+	pub(super) fn export(
+		&self,
+	) -> (
+		BTreeMap<String, export::Package>,
+		BTreeMap<String, Vec<String>>,
+	) {
+		let packages = self
+			.packages
+			.iter()
+			.map(|(name, package)| {
+				let package = export::Package {
+					r#type: package.r#type.clone(),
+					args: package.args.to_vec(),
+					cwd: package.cwd.clone(),
+					description: package.description.clone(),
+					tags: package.tags.clone(),
+					no_verify: package.no_verify,
+				};
+				(name.clone(), package)
+			})
+			.collect();
+		(packages, self.groups.clone())
+	}
+
+	/// Register an exported package as pending in `groups`, `Ok(false)` when skipped as existing.
+	pub(super) fn import(
+		&mut self,
+		name: &str,
+		package: export::Package,
+		groups: &[&str],
+		conflict: Conflict,
+	) -> Result<bool> {
+		validate_name(name, true)?;
+		if !self.type_config.has_type(&package.r#type) {
+			bail!(not_found("type", &package.r#type, self.type_config.names()));
 		}
+		let existing = self
+			.packages
+			.key(name)
+			.or_else(|| self.case_collision(name))
+			.cloned();
+		if let Some(existing) = existing {
+			if !conflict.replace("package", &existing)? {
+				return Ok(false);
+			}
+			self.forget(&existing);
+		}
+		let r#type = self.type_config.registered(&package.r#type);
+		let mut imported = Package::new(r#type, package.args.into(), None, package.description)?;
+		imported.cwd = package.cwd;
+		imported.tags = package.tags;
+		imported.no_verify = package.no_verify;
+		imported.state = State::Pending;
+		add!(
+			"{}\t{}\t{}{}",
+			name.themed(Role::Name),
+			imported.r#type.themed(Role::Type),
+			quote_args(&redact::args(&imported.args)),
+			"\t(pending)".themed(Role::Warning)
+		);
+		for group in groups {
+			let members = self.groups.entry(group.to_string()).or_default();
+			if !members.iter().any(|m| m == name) {
+				members.push(name.to_string());
+				members.sort();
+			}
+		}
+		self.packages.insert(name.to_string(), imported);
+		Ok(true)
 	}
 
-	/// Load from a TOML file at path.
-	pub fn load(path: &Path) -> Result<Self> {
-		toml::from_str::<TomlRepoConfig>(&fs::read_to_string(path).map_err(|e| {
-			anyhow!(
-				"failed to load config at '{}' {}",
-				path.display().to_string().bright_yellow(),
-				e
-			)
-		})?)
-		.map(|repo| (repo, path.parent().unwrap()).into())
-		.map_err(Into::into)
+	/// Store the `cwd` of packages added before it was kept relative to the home directory as
+	/// `~/...`, returning how many changed.
+	pub fn contract_cwds(&mut self) -> usize {
+		self.packages
+			.values_mut()
+			.map(Package::contract_cwd)
+			.filter(|changed| *changed)
+			.count()
+	}
+
+	/// Whether a package is registered.
+	pub fn has_package(&self, name: &str) -> bool {
+		self.packages.contains_key(name)
+	}
+
+	/// Registered package differing from `name` only by case, when the repository directory
+	/// ignores case so both would install to the same place.
+	fn case_collision(&self, name: &str) -> Option<&String> {
+		let lower = name.to_lowercase();
+		let existing = self
+			.packages
+			.keys()
+			.find(|key| *key != name && key.to_lowercase() == lower)?;
+		ignores_case(&self.path).then_some(existing)
+	}
+
+	/// Packages whose names differ only by case, sharing a directory when the file system ignores
+	/// case.
+	pub fn check(&self) -> Vec<String> {
+		let mut names: Vec<&String> = self.packages.keys().collect();
+		names.sort_by_key(|name| name.to_lowercase());
+		let collisions: Vec<_> = names
+			.windows(2)
+			.filter(|pair| pair[0].to_lowercase() == pair[1].to_lowercase())
+			.collect();
+		if collisions.is_empty() || !(case_insensitive() || ignores_case(&self.path)) {
+			return vec![];
+		}
+		collisions
+			.into_iter()
+			.map(|pair| {
+				format!(
+					"packages '{}' and '{}' of repository '{}' differ only by case, they share a directory, remove one",
+					pair[0].themed(Role::Warning),
+					pair[1].themed(Role::Warning),
+					self.name.themed(Role::Warning)
+				)
+			})
+			.collect()
+	}
+
+	/// Registered spelling of package `name`.
+	pub fn registered(&self, name: &str) -> String {
+		self.packages.registered(name)
+	}
+
+	/// Start a history entry for an operation on package `name` of this repository.
+	fn history(&self, operation: &str, name: &str) -> HistoryEntry {
+		let entry = HistoryEntry::new(operation).repo(&self.name).package(name);
+		match self.packages.get(name) {
+			Some(package) => entry.r#type(&package.r#type).args(&package.args),
+			None => entry,
+		}
+	}
+
+	/// Drop package `name` from the registry and from the groups it belongs to.
+	fn forget(&mut self, name: &str) -> Option<Package> {
+		self.groups.retain(|_, members| {
+			members.retain(|m| m != name);
+			!members.is_empty()
+		});
+		checksum::remove_manifest(&self.name, name);
+		self.packages.remove(name)
+	}
+
+	/// Point out shims of a deleted package and offer to remove them.
+	fn unlink_shims(&self, name: &str) {
+		let result = ShimConfig::load().and_then(|mut shim_cfg| {
+			let shims = shim_cfg.of_package(&self.name, name);
+			if shims.is_empty() {
+				return Ok(());
+			}
+			note!(
+				"package '{}' had shims: {}",
+				name.themed(Role::Warning),
+				shims.join(", ")
+			);
+			if prompt("Remove its shims?")? {
+				shim_cfg.unlink(&shims);
+				shim_cfg.save()?;
+			}
+			Ok(())
+		});
+		if let Err(e) = result {
+			error!(e);
+		}
+	}
+
+	/// History entry for removing package `name` from the registry, which `undo` can re-insert.
+	///
+	/// Secrets in the args are masked like everywhere else in the journal.
+	fn unregistered(&self, name: &str) -> HistoryEntry {
+		let before = self.packages.get(name).cloned().map(|package| {
+			let mut package = TomlPackage::from(package);
+			package.args = redact::mask(&package.args).into();
+			package
+		});
+		self.history("remove-registry", name)
+			.reversible(&self.path.join(REPO_CONFIG), before)
+	}
+
+	/// Open the TOML file at path in the editor.
+	pub fn edit(path: &Path) -> Result<()> {
+		edit_toml::<TomlRepoConfig>(path)
 	}
 
 	/// Save to a TOML file at path.
 	pub fn save(self, path: &Path) -> Result<()> {
-		fs::write(path, toml::to_string(&TomlRepoConfig::from(self))?).map_err(Into::into)
+		save_toml(path, &TomlRepoConfig::from(self))
 	}
 
-	/// Add a package and execute the script.
+	/// Add a package and execute the script, or only register it as pending.
 	pub fn add(
 		&mut self,
 		name: String,
 		r#type: String,
 		args: Box<[String]>,
-		cwd: bool,
+		cwd: Option<PathBuf>,
+		description: Option<String>,
+		options: AddOptions,
 	) -> Result<()> {
+		validate_name(&name, options.allow_weird_names)?;
+		self.type_config.check_args(&r#type, &args)?;
+		let r#type = self.type_config.registered(&r#type);
+		if let Some(existing) = self.case_collision(&name) {
+			bail!(
+				"{} package '{}' differs from '{}' only by case, both would install to the same directory",
+				Code::PackageExists,
+				name.themed(Role::Warning),
+				existing.themed(Role::Warning)
+			);
+		}
 		if let Entry::Vacant(e) = self.packages.entry(name.clone()) {
-			let mut package = Package::new(r#type.clone(), args.clone(), cwd);
-			package.add(&name, &self.path, &self.type_config)?;
+			let has_cwd = cwd.is_some();
+			let mut package = Package::new(r#type.clone(), args.clone(), cwd, description)?;
+			package.no_verify = options.no_verify;
+			let result = if options.register_only {
+				package.state = State::Pending;
+				Ok(())
+			} else {
+				let before = entries(&self.path);
+				let result = package.add(
+					Target {
+						name: &name,
+						repo: &self.name,
+						repo_path: &self.path,
+					},
+					&self.type_config,
+				);
+				if result.is_err() {
+					remove_created(&self.path, &name, &before);
+				}
+				result
+			};
+			HistoryEntry::new("add")
+				.repo(&self.name)
+				.package(&name)
+				.r#type(&r#type)
+				.args(&args)
+				.etag(None, package.tag.as_deref())
+				.result(&result)
+				.reversible(&self.path.join(REPO_CONFIG), ())
+				.record();
+			result?;
 			add!(
-				"{}\t{}\t{}{}",
-				name.bright_cyan(),
-				r#type.bright_purple(),
-				args.join(", "),
-				(if cwd { "\t(cwd)" } else { "" }).bright_white()
+				"{}\t{}\t{}{}{}",
+				name.themed(Role::Name),
+				r#type.themed(Role::Type),
+				quote_args(&redact::args(&args)),
+				(if has_cwd { "\t(cwd)" } else { "" }).bright_white(),
+				(if options.register_only {
+					"\t(pending)"
+				} else {
+					""
+				})
+				.themed(Role::Warning)
 			);
 			e.insert(package);
 			Ok(())
 		} else {
-			bail!("package '{}' already exists", name.bright_yellow())
+			bail!(
+				"{} package '{}' already exists",
+				Code::PackageExists,
+				self.packages.registered(&name).themed(Role::Warning)
+			)
 		}
 	}
 
-	/// Remove packages.
-	pub fn remove(&mut self, names: Vec<String>) {
-		for name in names {
+	/// Register a package installed by an earlier release without running the script, as
+	/// pending when its files are missing.
+	pub fn adopt(
+		&mut self,
+		name: String,
+		r#type: String,
+		args: Box<[String]>,
+		tag: Option<String>,
+	) -> Result<()> {
+		validate_name(&name, true)?;
+		if self.has_package(&name) {
+			bail!(
+				"{} package '{}' already exists",
+				Code::PackageExists,
+				self.packages.registered(&name).themed(Role::Warning)
+			);
+		}
+		let mut package = Package::new(r#type, args, None, None)?;
+		package.tag = tag;
+		if installed_path(&self.path, &name).is_none() {
+			package.state = State::Pending;
+		}
+		add!(
+			"{}\t{}\t{}{}",
+			name.themed(Role::Name),
+			package.r#type.themed(Role::Type),
+			quote_args(&redact::args(&package.args)),
+			(if package.state == State::Pending {
+				"\t(pending)"
+			} else {
+				""
+			})
+			.themed(Role::Warning)
+		);
+		self.packages.insert(name, package);
+		Ok(())
+	}
+
+	/// Add every package of `specs`, after checking them all so a mistake adds none.
+	///
+	/// Packages already registered are skipped when `skip_existing`, failed otherwise, a failing
+	/// one doesn't stop the rest. Returns the summary and the names added.
+	pub fn add_batch(
+		&mut self,
+		specs: Vec<Spec>,
+		options: AddOptions,
+		skip_existing: bool,
+	) -> Result<(Summary, Vec<String>)> {
+		let mut invalid = 0;
+		let mut seen = NameMap::default();
+		for spec in &specs {
+			let result = validate_name(&spec.name, options.allow_weird_names).and_then(|_| {
+				if !self.type_config.has_type(&spec.r#type) {
+					bail!(not_found("type", &spec.r#type, self.type_config.names()));
+				}
+				self.type_config.check_args(&spec.r#type, &spec.args)?;
+				if let Some(first) = seen.insert(spec.name.clone(), &spec.location) {
+					bail!(
+						"package '{}' is also on {}",
+						spec.name.themed(Role::Warning),
+						first
+					);
+				}
+				Ok(())
+			});
+			if let Err(e) = result {
+				error!("{}: {}", spec.location, e);
+				invalid += 1;
+			}
+		}
+		if invalid > 0 {
+			bail!(
+				"{} of {} entries are invalid, nothing was added",
+				invalid,
+				specs.len()
+			);
+		}
+
+		let mut summary = Summary::default();
+		let mut added = vec![];
+		let mut specs = specs.into_iter();
+		for spec in specs.by_ref() {
+			if skip_existing && self.has_package(&spec.name) {
+				note!(
+					"skipped '{}', already registered",
+					self.registered(&spec.name).themed(Role::Warning)
+				);
+				summary.skip(&spec.name);
+				continue;
+			}
+			match self.add(
+				spec.name.clone(),
+				spec.r#type,
+				spec.args.into_boxed_slice(),
+				spec.cwd,
+				spec.description,
+				options,
+			) {
+				Ok(()) => {
+					summary.succeed();
+					added.push(spec.name);
+				}
+				Err(e) => {
+					error!("{}: {}", spec.location, e);
+					summary.fail(&spec.name, e);
+				}
+			}
+			if interrupted() {
+				break;
+			}
+		}
+		summary.skip_rest(specs.map(|spec| spec.name));
+		Ok((summary, added))
+	}
+
+	/// Remove packages, `fail_fast` stops at the first failure.
+	pub fn remove(&mut self, names: Vec<String>, fail_fast: bool) -> Summary {
+		let mut summary = Summary::default();
+		let mut names = names.into_iter();
+		for name in names.by_ref() {
 			match self.packages.get(&name) {
 				Some(package) => match package.remove(&name, &self.path) {
 					std::result::Result::Ok(()) => {
-						self.packages.remove(&name);
-						remove!("{}", name.bright_cyan());
+						self.history("remove", &name).record();
+						self.forget(&name);
+						remove!("{}", name.themed(Role::Name));
+						self.unlink_shims(&name);
+						summary.succeed();
 					}
 					Err(e) => {
-						error!("failed to remove package '{}' {}", name.bright_yellow(), e);
-						match prompt("Remove from registry?") {
+						error!(
+							"failed to remove package '{}' {}",
+							name.themed(Role::Warning),
+							e
+						);
+						self.history("remove", &name).failed(&e).record();
+						match prompt_batch("Remove from registry?") {
 							Ok(true) => {
-								self.packages.remove(&name);
-								remove!("{}", name.bright_cyan());
+								self.unregistered(&name).record();
+								self.forget(&name);
+								remove!("{}", name.themed(Role::Name));
+								summary.succeed();
+							}
+							Ok(false) => {
+								self.history("remove-registry", &name).skipped().record();
+								summary.skip(&name);
+							}
+							Err(e) => {
+								error!(e);
+								summary.fail(&name, e);
 							}
-							Ok(false) => {}
-							Err(e) => error!(e),
 						}
 					}
 				},
-				None => error!("package '{}' does not exist", name.bright_yellow()),
+				None => {
+					error!(not_found("package", &name, self.packages.keys()));
+					summary.fail(&name, "does not exist");
+				}
+			}
+			if (fail_fast && summary.has_failures()) || interrupted() {
+				break;
 			}
 		}
+		summary.skip_rest(names);
+		summary.print();
+		summary
 	}
 
 	/// Remove packages from the registry.
 	pub fn remove_registry(&mut self, names: Vec<String>) {
 		for name in names {
-			match self.packages.remove(&name) {
-				Some(_) => remove!("{}", name.bright_cyan()),
-				None => error!("package '{}' does not exist", name.bright_yellow()),
+			if !self.packages.contains_key(&name) {
+				error!(not_found("package", &name, self.packages.keys()));
+				continue;
+			}
+			self.unregistered(&name).record();
+			self.forget(&name);
+			remove!("{}", name.themed(Role::Name));
+		}
+	}
+
+	/// Reverse a recorded `add`, `remove-registry`, `remove-tag` or `set-etag`, `before` is the
+	/// state before it.
+	///
+	/// Files of an added package are only deleted after a prompt.
+	pub fn undo(&mut self, entry: &HistoryEntry, before: &serde_json::Value) -> Result<()> {
+		let name = entry.package.as_deref().unwrap_or_default();
+		match entry.operation.as_str() {
+			"add" => {
+				let Some(package) = self.forget(name) else {
+					bail!(not_found("package", name, self.packages.keys()));
+				};
+				remove!("{}", name.themed(Role::Name));
+				if let Some(path) = installed_path(&self.path, name) {
+					if prompt(&format!("Delete '{}'?", path.display()))? {
+						package.remove(name, &self.path)?;
+					}
+				}
+			}
+			"remove-registry" => {
+				if self.packages.contains_key(name) {
+					bail!(
+						"{} package '{}' already exists",
+						Code::PackageExists,
+						self.packages.registered(name).themed(Role::Warning)
+					);
+				}
+				let package = Package::from(serde_json::from_value::<TomlPackage>(before.clone())?);
+				if redact::is_masked(&package.args) {
+					bail!(
+						"package '{}' can't be restored, secrets in its args were masked in the history, add it again with 'gpm repo {} add'",
+						name.themed(Role::Warning),
+						self.name
+					);
+				}
+				add!(
+					"{}\t{}\t{}",
+					name.themed(Role::Name),
+					package.r#type.themed(Role::Type),
+					quote_args(&redact::args(&package.args))
+				);
+				self.packages.insert(name.to_string(), package);
+			}
+			"remove-tag" => {
+				let tags: HashMap<String, String> = serde_json::from_value(before.clone())?;
+				for (name, tag) in tags {
+					if let Some(package) = self.packages.get_mut(&name) {
+						add!("{}\t{}", name.themed(Role::Name), tag);
+						package.tag = Some(tag);
+					}
+				}
+			}
+			"set-etag" => {
+				let Some(package) = self.packages.get_mut(name) else {
+					bail!(not_found("package", name, self.packages.keys()));
+				};
+				package.tag = serde_json::from_value(before.clone())?;
+				add!(
+					"{}\t{}",
+					name.themed(Role::Name),
+					package.tag.as_deref().unwrap_or("(none)")
+				);
+			}
+			op => bail!("'{}' can't be undone", op),
+		}
+		Ok(())
+	}
+
+	/// Set or clear the description of a package.
+	pub fn describe(&mut self, name: &str, description: Option<String>) -> Result<()> {
+		let entry = HistoryEntry::new("describe")
+			.repo(&self.name)
+			.package(name)
+			.args(description.as_slice());
+		match self.packages.get_mut(name) {
+			Some(package) => {
+				package.description = description.filter(|d| !d.is_empty());
+				entry.record();
+				Ok(())
+			}
+			None => bail!(not_found("package", name, self.packages.keys())),
+		}
+	}
+
+	/// Path to the repository.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Installed path of a package, which must exist on disk.
+	///
+	/// Falls back to a single file named after the package with any extension, e.g. `name.exe`.
+	pub fn package_path(&self, name: &str) -> Result<PathBuf> {
+		let Some(name) = self.packages.key(name) else {
+			bail!(not_found("package", name, self.packages.keys()));
+		};
+		match installed_path(&self.path, name) {
+			Some(path) => Ok(dunce::canonicalize(path)?),
+			None => bail!(
+				"package '{}' is not installed at '{}'",
+				name.themed(Role::Warning),
+				self.path.join(name).display()
+			),
+		}
+	}
+
+	/// Summary of the repository: package counts by state and the total recorded size.
+	pub fn info(&self) -> Result<String> {
+		let failed = self.with_state(State::Failed).len();
+		let pending = self.with_state(State::Pending).len();
+		let sizes: Vec<u64> = self
+			.packages
+			.values()
+			.filter_map(|p| p.size_bytes)
+			.collect();
+		let unknown = self.packages.len() - sizes.len();
+		let mut tw = tabwriter::TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}\t{}", "Name:".themed(Role::Success), self.name)?;
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Path:".themed(Role::Success),
+			self.path.display()
+		)?;
+		writeln!(
+			&mut tw,
+			"{}\t{} ({} ok, {} pending, {} failed)",
+			"Packages:".themed(Role::Success),
+			self.packages.len(),
+			self.packages.len() - failed - pending,
+			pending,
+			failed
+		)?;
+		writeln!(
+			&mut tw,
+			"{}\t{}{}",
+			"Size:".themed(Role::Success),
+			human_size(sizes.iter().sum()),
+			if unknown > 0 {
+				format!(" ({} unknown, see --refresh-sizes)", unknown)
+			} else {
+				String::new()
+			}
+		)?;
+		tw.flush()?;
+		Ok(String::from_utf8(tw.into_inner().unwrap())?)
+	}
+
+	/// Measure the installed size of every package again.
+	pub fn refresh_sizes(&mut self) {
+		for (name, package) in &mut self.packages {
+			package.size_bytes = installed_path(&self.path, name).map(|p| du::size(&p));
+		}
+		note!("measured {} packages", self.packages.len());
+	}
+
+	/// Variable definitions of package paths in `format`, for `names` or every installed package.
+	///
+	/// `export` makes them environment variables, e.g. for `eval "$(gpm repo tools env --export)"`.
+	pub fn env(&self, names: Vec<String>, format: EnvFormat, export: bool) -> Result<String> {
+		let all = names.is_empty();
+		let names = if all {
+			let mut names: Vec<String> = self.packages.keys().cloned().collect();
+			names.sort();
+			names
+		} else {
+			names
+		};
+		let mut vars: BTreeMap<String, &str> = BTreeMap::new();
+		let mut result = String::new();
+		for name in &names {
+			let path = match self.package_path(name) {
+				Ok(path) => path,
+				Err(e) if all => {
+					note!("skipping {}", e);
+					continue;
+				}
+				Err(e) => return Err(e),
+			};
+			let var = var_name(&["GPM", &self.name, name]);
+			if let Some(other) = vars.insert(var.clone(), name) {
+				bail!(
+					"packages '{}' and '{}' both map to '{}'",
+					other.themed(Role::Warning),
+					name.themed(Role::Warning),
+					var
+				);
+			}
+			let path = path.to_string_lossy();
+			let line = match (format, export) {
+				(EnvFormat::Sh, false) => format!("{}={}", var, shell_quote(&path)),
+				(EnvFormat::Sh, true) => format!("export {}={}", var, shell_quote(&path)),
+				(EnvFormat::Ps1, false) => format!("${} = {}", var, powershell_string(&path)),
+				(EnvFormat::Ps1, true) => format!("$env:{} = {}", var, powershell_string(&path)),
+				(EnvFormat::Dotenv, false) => format!("{}={}", var, dotenv_quote(&path)),
+				(EnvFormat::Dotenv, true) => format!("export {}={}", var, dotenv_quote(&path)),
+			};
+			result.push_str(&line);
+			result.push('\n');
+		}
+		Ok(result)
+	}
+
+	/// Show full details of a package.
+	pub fn show(&self, name: &str) -> Result<String> {
+		let package = match self.packages.get(name) {
+			Some(package) => package,
+			None => bail!(not_found("package", name, self.packages.keys())),
+		};
+		let mut tw = tabwriter::TabWriter::new(vec![]);
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Name:".themed(Role::Success),
+			name.themed(Role::Name)
+		)?;
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Type:".themed(Role::Success),
+			package.r#type.themed(Role::Type)
+		)?;
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Args:".themed(Role::Success),
+			quote_args(&package.args)
+		)?;
+		let target = Target {
+			name,
+			repo: &self.name,
+			repo_path: &self.path,
+		};
+		let resolved = r#type::resolve_args(&package.args, &target, &package.r#type);
+		if *resolved != *package.args {
+			writeln!(
+				&mut tw,
+				"{}\t{}",
+				"Resolved:".themed(Role::Success),
+				quote_args(&resolved)
+			)?;
+		}
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Tag:".themed(Role::Success),
+			package.tag.as_deref().unwrap_or_default()
+		)?;
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Cwd:".themed(Role::Success),
+			package.cwd().unwrap_or_default()
+		)?;
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Path:".themed(Role::Success),
+			self.path.join(name).display()
+		)?;
+		writeln!(
+			&mut tw,
+			"{}\t{}",
+			"Description:".themed(Role::Success),
+			package.description.as_deref().unwrap_or_default()
+		)?;
+		if let Some(size) = package.size_bytes {
+			writeln!(
+				&mut tw,
+				"{}\t{}",
+				"Size:".themed(Role::Success),
+				human_size(size)
+			)?;
+		}
+		if package.state == State::Pending {
+			writeln!(
+				&mut tw,
+				"{}\t{}",
+				"State:".themed(Role::Success),
+				package.state.to_string().themed(Role::Warning)
+			)?;
+		} else if package.state == State::Failed {
+			writeln!(
+				&mut tw,
+				"{}\t{} at {}: {}",
+				"State:".themed(Role::Success),
+				package.state.to_string().themed(Role::Error),
+				package.failed_at.as_deref().unwrap_or_default(),
+				package.error.as_deref().unwrap_or_default()
+			)?;
+		}
+		for (label, time) in [
+			("Added:", &package.added_at),
+			("Updated:", &package.updated_at),
+		] {
+			if let Some(time) = time {
+				let ago = DateTime::parse_from_rfc3339(time)
+					.map(|t| format!(" ({})", relative_time(t)))
+					.unwrap_or_default();
+				writeln!(&mut tw, "{}\t{}{}", label.themed(Role::Success), time, ago)?;
+			}
+		}
+		tw.flush()?;
+		Ok(String::from_utf8(tw.into_inner().unwrap())?)
+	}
+
+	/// Add labels to a package.
+	pub fn tag(&mut self, name: &str, tags: Vec<String>) -> Result<()> {
+		let package = match self.packages.get_mut(name) {
+			Some(package) => package,
+			None => bail!(not_found("package", name, self.packages.keys())),
+		};
+		let mut added = vec![];
+		for tag in tags {
+			if !package.tags.contains(&tag) {
+				add!("{}\t{}", name.themed(Role::Name), tag.themed(Role::Type));
+				package.tags.push(tag.clone());
+				added.push(tag);
+			}
+		}
+		if !added.is_empty() {
+			HistoryEntry::new("tag")
+				.repo(&self.name)
+				.package(name)
+				.args(&added)
+				.record();
+		}
+		Ok(())
+	}
+
+	/// Remove labels from a package.
+	pub fn untag(&mut self, name: &str, tags: Vec<String>) -> Result<()> {
+		let package = match self.packages.get_mut(name) {
+			Some(package) => package,
+			None => bail!(not_found("package", name, self.packages.keys())),
+		};
+		let mut removed = vec![];
+		for tag in tags {
+			match package.tags.iter().position(|t| *t == tag) {
+				Some(i) => {
+					package.tags.remove(i);
+					remove!("{}\t{}", name.themed(Role::Name), tag.themed(Role::Type));
+					removed.push(tag);
+				}
+				None => error!(
+					"package '{}' is not tagged '{}'",
+					name.themed(Role::Warning),
+					tag.themed(Role::Warning)
+				),
+			}
+		}
+		if !removed.is_empty() {
+			HistoryEntry::new("untag")
+				.repo(&self.name)
+				.package(name)
+				.args(&removed)
+				.record();
+		}
+		Ok(())
+	}
+
+	/// Select packages labeled with `tag`, intersected with `names` unless it's empty.
+	///
+	/// Names that are not registered are kept so the caller can report them.
+	pub fn filter_tag(&self, names: Vec<String>, tag: &str) -> Vec<String> {
+		if !self.packages.values().any(|p| p.has_tag(tag)) {
+			note!("no package is tagged '{}'", tag.themed(Role::Warning));
+			return vec![];
+		}
+		let mut selected: Vec<String> = if names.is_empty() {
+			self.packages
+				.iter()
+				.filter(|(_, p)| p.has_tag(tag))
+				.map(|(name, _)| name.clone())
+				.collect()
+		} else {
+			names
+				.into_iter()
+				.filter(|name| self.packages.get(name).is_none_or(|p| p.has_tag(tag)))
+				.collect()
+		};
+		selected.sort();
+		if selected.is_empty() {
+			note!("no package matches tag '{}'", tag.themed(Role::Warning));
+		}
+		selected
+	}
+
+	/// Add packages to `group`, creating it, all of them must exist.
+	pub fn group_add(&mut self, group: &str, names: Vec<String>) -> Result<()> {
+		if let Some(name) = names.iter().find(|n| !self.packages.contains_key(n)) {
+			bail!(not_found("package", name, self.packages.keys()));
+		}
+		let members = self.groups.entry(group.to_string()).or_default();
+		let mut added = vec![];
+		for name in names {
+			if !members.contains(&name) {
+				add!("{}\t{}", group.themed(Role::Type), name.themed(Role::Name));
+				members.push(name.clone());
+				added.push(name);
 			}
 		}
+		members.sort();
+		if !added.is_empty() {
+			HistoryEntry::new("group add")
+				.repo(&self.name)
+				.args(&[&[group.to_string()], &added[..]].concat())
+				.record();
+		}
+		Ok(())
+	}
+
+	/// Remove packages from `group`, or the whole group when `names` is empty.
+	///
+	/// A group left without members is deleted.
+	pub fn group_remove(&mut self, group: &str, names: Vec<String>) -> Result<()> {
+		let Some(members) = self.groups.get_mut(group) else {
+			bail!(not_found("group", group, self.groups.keys()));
+		};
+		let removed = if names.is_empty() {
+			std::mem::take(members)
+		} else {
+			let mut removed = vec![];
+			for name in names {
+				match members.iter().position(|m| *m == name) {
+					Some(i) => removed.push(members.remove(i)),
+					None => error!(
+						"package '{}' is not in group '{}'",
+						name.themed(Role::Warning),
+						group.themed(Role::Warning)
+					),
+				}
+			}
+			removed
+		};
+		for name in &removed {
+			remove!("{}\t{}", group.themed(Role::Type), name.themed(Role::Name));
+		}
+		if members.is_empty() {
+			self.groups.remove(group);
+		}
+		if !removed.is_empty() {
+			HistoryEntry::new("group remove")
+				.repo(&self.name)
+				.args(&[&[group.to_string()], &removed[..]].concat())
+				.record();
+		}
+		Ok(())
+	}
+
+	/// Render groups with their members.
+	pub fn group_list(&self) -> Result<String> {
+		let mut tw = tabwriter::TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", "Groups:".themed(Role::Success))?;
+		for (group, members) in &self.groups {
+			writeln!(
+				&mut tw,
+				"  {}\t{}",
+				group.themed(Role::Type),
+				members.join(", ")
+			)?;
+		}
+		tw.flush()?;
+		Ok(String::from_utf8(tw.into_inner().unwrap())?)
+	}
+
+	/// `names` followed by the members of `groups` not already among them.
+	pub fn with_groups(&self, mut names: Vec<String>, groups: &[String]) -> Result<Vec<String>> {
+		for group in groups {
+			let Some(members) = self.groups.get(group) else {
+				bail!(not_found("group", group, self.groups.keys()));
+			};
+			for member in members {
+				if !names.contains(member) {
+					names.push(member.clone());
+				}
+			}
+		}
+		Ok(names)
+	}
+
+	/// Packages selected by `options`, in the requested order.
+	fn rows(&self, options: &ListOptions) -> Vec<(&String, &Package)> {
+		let mut rows: Vec<_> = self
+			.packages
+			.iter()
+			.filter(|(_, p)| options.tag.is_none_or(|t| p.has_tag(t)))
+			.filter(|(_, p)| options.r#type.is_none_or(|t| p.r#type == t))
+			.collect();
+		rows.sort_by_key(|(name, _)| *name);
+		match options.sort {
+			SortKey::Name => {}
+			SortKey::Type => rows.sort_by_key(|(_, p)| &p.r#type),
+			SortKey::Updated => rows.sort_by_key(|(name, p)| {
+				Reverse(p.changed_at().map(|t| t.to_utc()).or_else(|| {
+					fs::metadata(self.path.join(name))
+						.and_then(|m| m.modified())
+						.ok()
+						.map(DateTime::<Utc>::from)
+				}))
+			}),
+		}
+		rows
+	}
+
+	/// List packages selected by `options`, followed by a count line.
+	pub fn list(&self, options: &ListOptions) -> String {
+		if let Some(t) = options.r#type {
+			if !self.type_config.has_type(t) {
+				note!(
+					"type '{}' is not in types.toml, matching the stored type name",
+					t.themed(Role::Warning)
+				);
+			}
+		}
+		let rows = self.rows(options);
+		let args_width = if options.full {
+			None
+		} else {
+			args_width(&rows)
+		};
+		let mut tw = tabwriter::TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", "Packages:".themed(Role::Success)).unwrap();
+		for (name, package) in &rows {
+			let details = if options.long {
+				format!(
+					"\t{}\t{}\t{}\t{}",
+					truncate(package.tag.as_deref().unwrap_or_default(), TAG_WIDTH).bright_white(),
+					if installed_path(&self.path, name).is_some() {
+						"installed".themed(Role::Success)
+					} else {
+						"missing".themed(Role::Error)
+					},
+					package.size_bytes.map(human_size).unwrap_or_default(),
+					package
+						.changed_at()
+						.map(relative_time)
+						.unwrap_or_default()
+						.bright_black()
+				)
+			} else {
+				String::new()
+			};
+			writeln!(
+				&mut tw,
+				"  {}{}\t{}\t{}\t{}{}\t{}\t{}",
+				name.themed(Role::Name),
+				package.state.marker(),
+				package.r#type.themed(Role::Type),
+				match args_width {
+					Some(width) => truncate(&quote_args(&package.args), width),
+					None => quote_args(&package.args),
+				},
+				package.cwd().unwrap_or_default().bright_white(),
+				details,
+				package.tags.join(", ").bright_blue(),
+				truncate(
+					package.description.as_deref().unwrap_or_default(),
+					DESCRIPTION_WIDTH
+				)
+				.bright_white()
+			)
+			.unwrap();
+		}
+		tw.flush().unwrap();
+		let mut result = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+		let filtered = self.packages.len() - rows.len();
+		result.push_str(&format!(
+			"{} package{}",
+			rows.len(),
+			if rows.len() == 1 { "" } else { "s" }
+		));
+		if filtered > 0 {
+			result.push_str(&format!(", {} filtered out", filtered));
+		}
+		result.push('\n');
+		result
+	}
+
+	/// Package names with their type, sorted by name.
+	pub fn types(&self) -> Vec<(&str, &str)> {
+		self.rows(&ListOptions::default())
+			.into_iter()
+			.map(|(name, package)| (name.as_str(), package.r#type.as_str()))
+			.collect()
+	}
+
+	/// Names of the packages selected by `options`, in the requested order.
+	pub fn names(&self, options: &ListOptions) -> Vec<&str> {
+		self.rows(options)
+			.into_iter()
+			.map(|(name, _)| name.as_str())
+			.collect()
+	}
+
+	/// List packages selected by `options` as JSON, with untruncated values.
+	pub fn list_json(&self, options: &ListOptions) -> Result<String> {
+		let packages: Vec<_> = self
+			.rows(options)
+			.into_iter()
+			.map(|(name, package)| {
+				let path = self.path.join(name);
+				json!({
+					"name": name,
+					"type": package.r#type,
+					"args": package.args,
+					"tag": package.tag,
+					"cwd": package.cwd(),
+					"description": package.description,
+					"tags": package.tags,
+					"added_at": package.added_at,
+					"updated_at": package.updated_at,
+					"state": package.state,
+					"error": package.error,
+					"failed_at": package.failed_at,
+					"size_bytes": package.size_bytes,
+					"path": path,
+					"exists": path.exists(),
+				})
+			})
+			.collect();
+		Ok(serde_json::to_string_pretty(&packages)? + "\n")
+	}
+
+	/// List packages selected by `options` as CSV or TSV, `args_delimiter` joining the args.
+	pub fn list_table(
+		&self,
+		options: &ListOptions,
+		format: TableFormat,
+		args_delimiter: &str,
+	) -> String {
+		format.table(
+			&["name", "type", "args", "etag", "cwd", "updated_at"],
+			&self.table_rows(options, args_delimiter),
+		)
+	}
+
+	/// List packages in the columns of `list_table`.
+	pub fn list_porcelain(
+		&self,
+		options: &ListOptions,
+		porcelain: Porcelain,
+		args_delimiter: &str,
+	) -> String {
+		porcelain.render(&self.table_rows(options, args_delimiter))
+	}
+
+	fn table_rows(&self, options: &ListOptions, args_delimiter: &str) -> Vec<Vec<String>> {
+		self.rows(options)
+			.into_iter()
+			.map(|(name, package)| {
+				vec![
+					name.clone(),
+					package.r#type.clone(),
+					package.args.join(args_delimiter),
+					package.tag.clone().unwrap_or_default(),
+					package.cwd().unwrap_or_default(),
+					package.updated_at.clone().unwrap_or_default(),
+				]
+			})
+			.collect()
 	}
 
 	/// Remove Tag for packages.
 	pub fn remove_tag(&mut self) {
+		let before: HashMap<&str, &str> = self
+			.packages
+			.iter()
+			.filter_map(|(name, package)| Some((name.as_str(), package.tag.as_deref()?)))
+			.collect();
+		HistoryEntry::new("remove-tag")
+			.repo(&self.name)
+			.reversible(&self.path.join(REPO_CONFIG), before)
+			.record();
 		for package in self.packages.values_mut() {
 			package.tag = None;
 		}
 	}
 
-	/// Update packages.
-	pub fn update(&mut self, names: Vec<String>) {
-		for name in names {
-			match self.packages.get_mut(&name) {
-				Some(package) => package
-					.add(&name, &self.path, &self.type_config)
-					.unwrap_or_else(|e| {
-						error!("failed to update package '{}' {}", name.bright_yellow(), e)
-					}),
-				None => error!("package '{}' does not exist", name.bright_yellow()),
+	/// Set the tag of a package, or clear it with `None`, without running the script.
+	pub fn set_tag(&mut self, name: &str, tag: Option<String>) -> Result<()> {
+		let Some(package) = self.packages.get(name) else {
+			bail!(not_found("package", name, self.packages.keys()));
+		};
+		HistoryEntry::new("set-etag")
+			.repo(&self.name)
+			.package(name)
+			.args(tag.as_slice())
+			.reversible(&self.path.join(REPO_CONFIG), &package.tag)
+			.record();
+		let package = self.packages.get_mut(name).unwrap();
+		status!(
+			"tag".themed(Role::Success),
+			"{} {} -> {}",
+			name.themed(Role::Name),
+			package.tag.as_deref().unwrap_or("(none)"),
+			tag.as_deref().unwrap_or("(none)").bright_white()
+		);
+		package.tag = tag;
+		Ok(())
+	}
+
+	/// Update packages as set by `options`.
+	pub fn update(&mut self, names: Vec<String>, options: UpdateOptions) -> Summary {
+		self.update_packages(names, options, None, Summary::default())
+	}
+
+	/// Ask before updating `names`, or all packages, showing the count and first names.
+	pub fn confirm_update_all(&self, names: Option<&[String]>) -> Result<bool> {
+		let names: Vec<&str> = match names {
+			Some(names) => names.iter().map(String::as_str).collect(),
+			None => self.packages.keys().map(String::as_str).collect(),
+		};
+		if names.is_empty() {
+			return Ok(true);
+		}
+		prompt(&format!(
+			"Update {} package{} in '{}' ({})?",
+			names.len(),
+			if names.len() == 1 { "" } else { "s" },
+			self.name.themed(Role::Name),
+			first_names(names).themed(Role::Warning)
+		))
+	}
+
+	/// Ask before removing every package, showing the count and first names.
+	pub fn confirm_remove_all(&self, registry: bool) -> Result<bool> {
+		let names: Vec<&str> = self.packages.keys().map(String::as_str).collect();
+		prompt(&format!(
+			"Remove {} package{} from '{}' ({}){}?",
+			names.len(),
+			if names.len() == 1 { "" } else { "s" },
+			self.name.themed(Role::Name),
+			first_names(names).themed(Role::Warning),
+			if registry {
+				""
+			} else {
+				" and delete their files"
 			}
+		))
+	}
+
+	/// Names of packages in `state`, in name order.
+	pub fn with_state(&self, state: State) -> Vec<String> {
+		let mut names: Vec<String> = self
+			.packages
+			.iter()
+			.filter(|(_, p)| p.state == state)
+			.map(|(name, _)| name.clone())
+			.collect();
+		names.sort();
+		names
+	}
+
+	/// Count packages per state, listing the pending ones and the failed ones with their error.
+	pub fn status(&self) -> Result<String> {
+		let failed = self.with_state(State::Failed);
+		let pending = self.with_state(State::Pending);
+		let mut tw = tabwriter::TabWriter::new(vec![]);
+		writeln!(
+			&mut tw,
+			"{} {} ok, {} pending, {} failed",
+			"Status:".themed(Role::Success),
+			self.packages.len() - failed.len() - pending.len(),
+			pending.len(),
+			failed.len()
+		)?;
+		for name in &pending {
+			writeln!(&mut tw, "  {}\tpending", name.themed(Role::Warning))?;
 		}
+		for name in &failed {
+			let package = &self.packages[name];
+			writeln!(
+				&mut tw,
+				"  {}\t{}\t{}",
+				name.themed(Role::Error),
+				package
+					.failed_at
+					.as_deref()
+					.and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+					.map(relative_time)
+					.unwrap_or_default()
+					.bright_black(),
+				package.error.as_deref().unwrap_or_default()
+			)?;
+		}
+		tw.flush()?;
+		Ok(String::from_utf8(tw.into_inner().unwrap())?)
 	}
 
-	/// Update all packages.
-	pub fn update_all(&mut self) {
-		for (name, package) in &mut self.packages {
-			package
-				.add(name, &self.path, &self.type_config)
-				.unwrap_or_else(|e| {
-					error!("failed to update package '{}' {}", name.bright_yellow(), e)
-				});
+	/// Every package in name order with its state, when it last failed and the first line of the
+	/// error.
+	pub fn status_porcelain(&self, porcelain: Porcelain) -> String {
+		let mut names: Vec<&String> = self.packages.keys().collect();
+		names.sort();
+		let rows: Vec<_> = names
+			.into_iter()
+			.map(|name| {
+				let package = &self.packages[name];
+				vec![
+					name.clone(),
+					package.state.to_string(),
+					package.failed_at.clone().unwrap_or_default(),
+					package
+						.error
+						.as_deref()
+						.and_then(|e| e.lines().next())
+						.unwrap_or_default()
+						.to_string(),
+				]
+			})
+			.collect();
+		porcelain.render(&rows)
+	}
+
+	/// Packages among `names`, or all if `None`, of type `r#type`, in name order.
+	///
+	/// Fails when the type is not in types.toml.
+	pub fn filter_type(&self, names: Option<Vec<String>>, r#type: &str) -> Result<Vec<String>> {
+		if !self.type_config.has_type(r#type) {
+			bail!(not_found("type", r#type, self.type_config.names()));
+		}
+		let r#type = self.type_config.registered(r#type);
+		let mut names = names.unwrap_or_else(|| self.packages.keys().cloned().collect::<Vec<_>>());
+		names.sort();
+		let total = names.len();
+		// Unknown names are kept for `update` to report.
+		names.retain(|name| {
+			self.packages
+				.get(name)
+				.is_none_or(|p| self.type_config.registered(&p.r#type) == r#type)
+		});
+		note!(
+			"{} of {} package{} of type '{}'",
+			names.len(),
+			total,
+			if total == 1 { "" } else { "s" },
+			r#type.themed(Role::Type)
+		);
+		Ok(names)
+	}
+
+	/// Packages whose type is not in types.toml, grouped by the stored type name.
+	pub fn missing_types(&self) -> BTreeMap<&str, Vec<&str>> {
+		let mut missing: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+		for (name, package) in &self.packages {
+			if !self.type_config.has_type(&package.r#type) {
+				missing
+					.entry(package.r#type.as_str())
+					.or_default()
+					.push(name.as_str());
+			}
+		}
+		for names in missing.values_mut() {
+			names.sort();
+		}
+		missing
+	}
+
+	/// Report the packages whose type is not in types.toml, returning whether there are any.
+	pub fn check_types(&self) -> Result<(String, bool)> {
+		let missing = self.missing_types();
+		if missing.is_empty() {
+			return Ok((
+				format!(
+					"{} {} package{} with a registered type\n",
+					"Types:".themed(Role::Success),
+					self.packages.len(),
+					if self.packages.len() == 1 { "" } else { "s" }
+				),
+				false,
+			));
+		}
+		let mut tw = tabwriter::TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", "Missing types:".themed(Role::Error))?;
+		for (r#type, names) in &missing {
+			writeln!(
+				&mut tw,
+				"  {}\t{}",
+				r#type.themed(Role::Warning),
+				names.join(", ")
+			)?;
+		}
+		tw.flush()?;
+		Ok((String::from_utf8(tw.into_inner().unwrap())?, true))
+	}
+
+	/// Change the type of every package of type `old` to `new`.
+	///
+	/// Fails without changing anything when `new` is not in types.toml, no package has type
+	/// `old` or the args of a package don't fit the parameters of `new`.
+	pub fn reassign_type(&mut self, old: &str, new: &str) -> Result<()> {
+		if !self.type_config.has_type(new) {
+			bail!(not_found("type", new, self.type_config.names()));
+		}
+		let new = self.type_config.registered(new);
+		let mut names: Vec<String> = self
+			.packages
+			.iter()
+			.filter(|(_, package)| package.r#type == old)
+			.map(|(name, _)| name.clone())
+			.collect();
+		if names.is_empty() {
+			bail!(
+				"{} no package of repository '{}' has type '{}'",
+				Code::PackageNotFound,
+				self.name.themed(Role::Warning),
+				old.themed(Role::Warning)
+			);
+		}
+		names.sort();
+		for name in &names {
+			self.type_config
+				.check_args(&new, &self.packages[name].args)
+				.with_context(|| {
+					format!("can't reassign package '{}'", name.themed(Role::Warning))
+				})?;
+		}
+		for name in &names {
+			change!(
+				"{}\t{} -> {}",
+				name.themed(Role::Name),
+				old.bright_black(),
+				new.themed(Role::Type)
+			);
+			self.packages.get_mut(name).unwrap().r#type = new.clone();
+		}
+		HistoryEntry::new("reassign-type")
+			.repo(&self.name)
+			.args(&[old, new.as_str()])
+			.record();
+		Ok(())
+	}
+
+	/// Packages among `names`, or all if `None`, not updated within `age`, in name order.
+	///
+	/// Packages without any timestamp count as stale.
+	pub fn filter_stale(&self, names: Option<Vec<String>>, age: Duration) -> Vec<String> {
+		let mut names = names.unwrap_or_else(|| self.packages.keys().cloned().collect::<Vec<_>>());
+		names.sort();
+		let total = names.len();
+		let cutoff = Utc::now() - age;
+		// Unknown names are kept for `update` to report.
+		names.retain(|name| {
+			self.packages
+				.get(name)
+				.and_then(Package::changed_at)
+				.is_none_or(|time| time < cutoff)
+		});
+		note!(
+			"{} of {} package{} not updated in {}",
+			names.len(),
+			total,
+			if total == 1 { "" } else { "s" },
+			humantime::format_duration(age)
+		);
+		names
+	}
+
+	/// Packages among `names`, or all if `None`, without those in `except`, in name order, with
+	/// the names left out.
+	///
+	/// Names in `except` that aren't packages are reported and ignored.
+	pub fn filter_except(
+		&self,
+		names: Option<Vec<String>>,
+		except: &[String],
+	) -> (Vec<String>, Vec<String>) {
+		for name in except {
+			if !self.packages.contains_key(name) {
+				note!(
+					"'{}' isn't a package of '{}', nothing to exclude{}",
+					name.themed(Role::Warning),
+					self.name.themed(Role::Name),
+					did_you_mean(name, self.packages.keys())
+				);
+			}
+		}
+		let mut names = names.unwrap_or_else(|| self.packages.keys().cloned().collect::<Vec<_>>());
+		names.sort();
+		names.into_iter().partition(|name| {
+			!except
+				.iter()
+				.any(|e| self.packages.key(e) == Some(name) || e == name)
+		})
+	}
+
+	/// Update all packages in name order, so runs are reproducible, with an overall progress bar.
+	pub fn update_all(&mut self, options: UpdateOptions) -> Summary {
+		let mut names: Vec<String> = self.packages.keys().cloned().collect();
+		names.sort();
+		self.update_excluding(names, vec![], options)
+	}
+
+	/// Update `names` with an overall progress bar, reporting `excluded` as left out on purpose.
+	pub fn update_excluding(
+		&mut self,
+		names: Vec<String>,
+		excluded: Vec<String>,
+		options: UpdateOptions,
+	) -> Summary {
+		let mut summary = Summary::default();
+		for name in &excluded {
+			summary.exclude(name);
 		}
+		let progress = progress_bar(names.len());
+		self.update_packages(names, options, progress, summary)
 	}
 
-	/// Clone packages to the current directory.
-	pub fn copy(&self, names: Vec<String>) {
+	fn update_packages(
+		&mut self,
+		names: Vec<String>,
+		options: UpdateOptions,
+		progress: Option<ProgressBar>,
+		mut summary: Summary,
+	) -> Summary {
+		let mut durations = vec![];
+		let total = names.len();
+		let mut names = names.into_iter().enumerate();
+		for (i, name) in names.by_ref() {
+			match &progress {
+				Some(bar) => {
+					bar.set_message(format!("package {}/{}: {}", i + 1, total, name));
+					// Scripts stream their own output, which the bar would mangle.
+					bar.suspend(|| self.update_one(name, options, &mut summary, &mut durations));
+					bar.inc(1);
+				}
+				None => self.update_one(name, options, &mut summary, &mut durations),
+			}
+			if options.fail_fast && summary.has_failures() {
+				break;
+			}
+		}
+		if let Some(bar) = progress {
+			bar.finish_and_clear();
+		}
+		summary.skip_rest(names.map(|(_, name)| name));
+		print_timings(durations, options.timings);
+		summary.print();
+		summary
+	}
+
+	fn update_one(
+		&mut self,
+		name: String,
+		options: UpdateOptions,
+		summary: &mut Summary,
+		durations: &mut Vec<(String, Duration)>,
+	) {
+		let Some(package) = self.packages.get_mut(&name) else {
+			error!(not_found("package", &name, self.packages.keys()));
+			summary.fail(&name, "does not exist");
+			return;
+		};
+		// A pending package was never installed, running its script is its first install.
+		let (verb, label) = match package.state {
+			_ if options.reinstall => ("reinstall", "reinstalled"),
+			State::Pending => ("install", "installed"),
+			_ => ("update", "updated"),
+		};
+		let start = Instant::now();
+		let old_tag = package.tag.clone();
+		let target = Target {
+			name: &name,
+			repo: &self.name,
+			repo_path: &self.path,
+		};
+		let result = if options.reinstall {
+			package.reinstall(target, &self.type_config)
+		} else {
+			package.add(target, &self.type_config)
+		};
+		HistoryEntry::new(verb)
+			.repo(&self.name)
+			.package(&name)
+			.r#type(&package.r#type)
+			.args(&package.args)
+			.etag(old_tag.as_deref(), package.tag.as_deref())
+			.result(&result)
+			.record();
+		match result {
+			Ok(()) => {
+				let elapsed = start.elapsed();
+				if let Err(e) = ShimConfig::load()
+					.and_then(|shim_cfg| shim_cfg.refresh(&self.name, &name, &self.path))
+				{
+					error!(
+						"failed to refresh shims of '{}' {}",
+						name.themed(Role::Warning),
+						e
+					);
+				}
+				let timing = if options.timings || elapsed >= SLOW_UPDATE {
+					format!(" ({})", format_duration(elapsed))
+				} else {
+					String::new()
+				};
+				let new_tag = package.tag.as_deref();
+				// Scripts printing no tag give nothing to compare, nor does a first install.
+				if verb == "install" || (old_tag.is_none() && new_tag.is_none()) {
+					status!(
+						label.themed(Role::Success),
+						"{}{}",
+						name.themed(Role::Name),
+						timing
+					);
+					summary.succeed();
+				} else if old_tag.as_deref() == new_tag {
+					status!(
+						"unchanged".bright_black(),
+						"{}{}",
+						name.themed(Role::Name),
+						timing
+					);
+					summary.succeed_changed(false);
+				} else {
+					change!(
+						"{}\t{} -> {}{}",
+						name.themed(Role::Name),
+						old_tag.as_deref().unwrap_or("none").bright_black(),
+						new_tag.unwrap_or("none").themed(Role::Success),
+						timing
+					);
+					summary.succeed_changed(true);
+				}
+				durations.push((name, elapsed));
+			}
+			// A script that needs the network, it's tried again once online.
+			Err(e) if e.is::<OfflineError>() => {
+				note!(
+					"skipped '{}' in offline mode, {}",
+					name.themed(Role::Warning),
+					e
+				);
+				summary.skip(&name);
+			}
+			Err(e) => {
+				error!(
+					"failed to {} package '{}' {}",
+					verb,
+					name.themed(Role::Warning),
+					e
+				);
+				summary.fail(&name, e);
+			}
+		}
+	}
+
+	/// Check installed files against the recorded checksums, `names` or all packages if empty.
+	///
+	/// Packages without a checksum, including those marked `no_verify`, are skipped.
+	pub fn verify(&self, names: Vec<String>) -> Summary {
+		let names = if names.is_empty() {
+			let mut names: Vec<String> = self.packages.keys().cloned().collect();
+			names.sort();
+			names
+		} else {
+			names
+		};
+		let mut summary = Summary::default();
 		for name in names {
+			let Some(package) = self.packages.get(&name) else {
+				error!(not_found("package", &name, self.packages.keys()));
+				summary.fail(&name, "does not exist");
+				continue;
+			};
+			let Some(expected) = package.checksum.as_deref() else {
+				note!("no checksum recorded for '{}'", name.themed(Role::Warning));
+				summary.skip(&name);
+				continue;
+			};
+			let Some(path) = installed_path(&self.path, &name) else {
+				error!("package '{}' is not installed", name.themed(Role::Warning));
+				summary.fail(&name, "not installed");
+				continue;
+			};
+			let files = match checksum::files(&path) {
+				Ok(files) => files,
+				Err(e) => {
+					error!(
+						"failed to verify package '{}' {}",
+						name.themed(Role::Warning),
+						e
+					);
+					summary.fail(&name, e);
+					continue;
+				}
+			};
+			if checksum::digest(&files) == expected {
+				status!(
+					"verified".themed(Role::Success),
+					"{}",
+					name.themed(Role::Name)
+				);
+				summary.succeed();
+				continue;
+			}
+			error!(
+				"package '{}' changed since it was installed",
+				name.themed(Role::Warning)
+			);
+			match checksum::load_manifest(&self.name, &name) {
+				Some(recorded) => {
+					let changes = Changes::between(&recorded, &files);
+					for (label, files) in [
+						("modified:", &changes.modified),
+						("added:", &changes.added),
+						("removed:", &changes.removed),
+					] {
+						for file in files {
+							eprintln!("  {} {}", label.themed(Role::Warning), file);
+						}
+					}
+					summary.fail(
+						&name,
+						format!(
+							"{} modified, {} added, {} removed",
+							changes.modified.len(),
+							changes.added.len(),
+							changes.removed.len()
+						),
+					);
+				}
+				None => summary.fail(&name, "contents changed"),
+			}
+		}
+		summary.print();
+		summary
+	}
+
+	/// Clone packages to the current directory, `fail_fast` stops at the first failure.
+	pub fn copy(&self, names: Vec<String>, fail_fast: bool, copier: &Copier) -> Summary {
+		let mut summary = Summary::default();
+		let mut names = names.into_iter();
+		for name in names.by_ref() {
 			match self.packages.get(&name) {
-				Some(package) => match package.copy(&self.path, &name) {
-					Ok(_) => clone!("{}", name.bright_yellow()),
-					Err(e) => error!("failed to copy package '{}' {}", name.bright_yellow(), e),
+				Some(package) => match package.copy(&self.path, &name, copier) {
+					Ok(_) => {
+						self.history("clone", &name).record();
+						clone!("{}", name.themed(Role::Warning));
+						summary.succeed();
+					}
+					Err(e) => {
+						error!(
+							"failed to copy package '{}' {}",
+							name.themed(Role::Warning),
+							e
+						);
+						self.history("clone", &name).failed(&e).record();
+						summary.fail(&name, e);
+					}
 				},
-				None => error!("package '{}' does not exist", name.bright_yellow()),
+				None => {
+					error!(not_found("package", &name, self.packages.keys()));
+					summary.fail(&name, "does not exist");
+				}
+			}
+			if fail_fast && summary.has_failures() {
+				break;
 			}
 		}
+		summary.skip_rest(names);
+		summary.print();
+		summary
 	}
 }
 
-impl From<(TomlRepoConfig, &Path)> for RepoConfig {
-	fn from((config, path): (TomlRepoConfig, &Path)) -> Self {
-		Self {
-			packages: config
-				.packages
-				.into_iter()
-				.map(|(name, package)| (name, package.into()))
-				.collect(),
-			type_config: TypeConfig::load().expect("failed to load type config"),
-			path: path.into(),
+/// Path of package `name` under `repo_path`, the entry of that name, else the only file with that
+/// stem, such as `rg.exe` for `rg`.
+fn installed_path(repo_path: &Path, name: &str) -> Option<PathBuf> {
+	let path = repo_path.join(name);
+	if path.exists() {
+		return Some(path);
+	}
+	let candidates: Vec<PathBuf> = fs::read_dir(repo_path)
+		.ok()?
+		.filter_map(|entry| entry.ok().map(|e| e.path()))
+		.filter(|p| p.is_file() && p.file_stem().is_some_and(|s| s == name))
+		.collect();
+	match <[PathBuf; 1]>::try_from(candidates) {
+		Ok([path]) => Some(path),
+		Err(_) => None,
+	}
+}
+
+/// Error for package `name` having nothing installed under `repo_path`, `NotFound` so a
+/// reinstall can go on.
+fn not_installed(repo_path: &Path, name: &str) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::NotFound,
+		format!(
+			"package '{}' is not installed at '{}'",
+			name.themed(Role::Warning),
+			repo_path.join(name).display()
+		),
+	)
+}
+
+/// Names of the entries at the top level of `dir`, empty when it can't be read.
+fn entries(dir: &Path) -> BTreeSet<OsString> {
+	fs::read_dir(dir)
+		.map(|entries| entries.flatten().map(|e| e.file_name()).collect())
+		.unwrap_or_default()
+}
+
+/// Remove what a failed add of `name` left in `repo_path`, the entries missing from `before`
+/// named `name` or starting with `name.`, so a retry starts clean.
+fn remove_created(repo_path: &Path, name: &str, before: &BTreeSet<OsString>) {
+	let prefix = format!("{}.", name);
+	for entry in entries(repo_path).difference(before) {
+		let entry_name = entry.to_string_lossy();
+		if entry_name != name && !entry_name.starts_with(&prefix) {
+			continue;
+		}
+		let path = repo_path.join(entry);
+		let result = if path.is_dir() {
+			fs::remove_dir_all(&path)
+		} else {
+			fs::remove_file(&path)
+		};
+		match result {
+			Ok(()) => remove!("{} (left by the failed add)", path.display()),
+			Err(e) => error!(
+				"failed to clean up '{}' {}",
+				path.display().to_string().themed(Role::Warning),
+				e
+			),
 		}
 	}
 }
 
+/// Upper case identifier joining `parts` with `_`, other characters than ASCII letters and digits
+/// also become `_`.
+fn var_name(parts: &[&str]) -> String {
+	parts
+		.iter()
+		.map(|part| {
+			part.chars()
+				.map(|c| match c {
+					c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+					_ => '_',
+				})
+				.collect::<String>()
+		})
+		.collect::<Vec<_>>()
+		.join("_")
+}
+
+/// Double quote a dotenv value when it isn't a plain word.
+fn dotenv_quote(value: &str) -> String {
+	if value
+		.chars()
+		.all(|c| c.is_ascii_alphanumeric() || r"-_./:\".contains(c))
+	{
+		value.to_string()
+	} else {
+		format!("\"{}\"", value.replace('\\', r"\\").replace('"', r#"\""#))
+	}
+}
+
+/// Progress bar over `len` packages on stderr, `None` when it's not a terminal or output is quiet.
+fn progress_bar(len: usize) -> Option<ProgressBar> {
+	styled_progress_bar(len as u64, "{msg} [{bar:30}] {elapsed}")
+}
+
+/// Progress bar over `len` units on stderr drawn with `template`, `None` when it's not a terminal
+/// or output is quiet.
+pub(crate) fn styled_progress_bar(len: u64, template: &str) -> Option<ProgressBar> {
+	if !io::stderr().is_terminal() || verbosity() == Verbosity::Quiet || plain() {
+		return None;
+	}
+	let style = ProgressStyle::with_template(template)
+		.unwrap()
+		.progress_chars("=> ");
+	Some(ProgressBar::new(len).with_style(style))
+}
+
+fn format_duration(duration: Duration) -> String {
+	format!("{:.1}s", duration.as_secs_f64())
+}
+
+/// Print update durations from slowest to fastest, all of them when `full`,
+/// otherwise only the slowest packages above the threshold.
+fn print_timings(mut durations: Vec<(String, Duration)>, full: bool) {
+	durations.sort_by_key(|(_, d)| Reverse(*d));
+	if !full {
+		durations.retain(|(_, d)| *d >= SLOW_UPDATE);
+		durations.truncate(SLOWEST_SHOWN);
+		// A single slow package was already annotated on its own line.
+		if durations.len() < 2 {
+			return;
+		}
+	}
+	if durations.is_empty() {
+		return;
+	}
+	let mut tw = tabwriter::TabWriter::new(vec![]);
+	let header = if full { "Timings:" } else { "Slowest:" };
+	writeln!(&mut tw, "{}", header.themed(Role::Success)).unwrap();
+	for (name, duration) in &durations {
+		writeln!(
+			&mut tw,
+			"  {}\t{}",
+			name.themed(Role::Name),
+			format_duration(*duration)
+		)
+		.unwrap();
+	}
+	tw.flush().unwrap();
+	print!("{}", String::from_utf8(tw.into_inner().unwrap()).unwrap());
+}
+
+/// Width left for the args column of `list` after the name and type columns, `None` unless stdout
+/// is a terminal so piped output stays complete.
+fn args_width(rows: &[(&String, &Package)]) -> Option<usize> {
+	if !io::stdout().is_terminal() {
+		return None;
+	}
+	let (terminal_size::Width(width), _) = terminal_size::terminal_size()?;
+	let name = rows
+		.iter()
+		.map(|(name, package)| name.chars().count() + package.state.marker().chars().count())
+		.max()?;
+	let r#type = rows
+		.iter()
+		.map(|(_, package)| package.r#type.chars().count())
+		.max()?;
+	let used = 2 + name + COLUMN_PADDING + r#type + COLUMN_PADDING;
+	Some((width as usize).saturating_sub(used).max(MIN_ARGS_WIDTH))
+}
+
 impl fmt::Display for RepoConfig {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		let mut tw = tabwriter::TabWriter::new(vec![]);
-		writeln!(&mut tw, "{}", "Packages:".bright_green()).unwrap();
-		let btree_map: BTreeMap<_, _> = self.packages.iter().collect();
-		for (name, package) in &btree_map {
-			writeln!(
-				&mut tw,
-				"  {}\t{}\t{}\t{}",
-				name.bright_cyan(),
-				package.r#type.bright_purple(),
-				package.args.join(", "),
-				package.cwd.as_deref().unwrap_or_default().bright_white()
-			)
-			.unwrap();
+		write!(f, "{}", self.list(&ListOptions::default()))
+	}
+}
+
+/// Order of packages in `list`.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum SortKey {
+	#[default]
+	Name,
+	Type,
+	/// Most recently updated first, by `updated_at`, `added_at` or the directory time
+	Updated,
+}
+
+/// Syntax of the variable definitions printed by `env`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum EnvFormat {
+	/// POSIX shell
+	Sh,
+	/// PowerShell
+	Ps1,
+	/// `.env` file
+	Dotenv,
+}
+
+impl EnvFormat {
+	/// Syntax of the shell `gpm` runs in on this platform.
+	pub fn native() -> Self {
+		if cfg!(windows) {
+			EnvFormat::Ps1
+		} else {
+			EnvFormat::Sh
 		}
-		tw.flush().unwrap();
-		write!(
-			f,
-			"{}",
-			String::from_utf8(tw.into_inner().unwrap()).unwrap()
-		)
 	}
 }
 
-#[derive(Debug)]
+/// Flags of `update`, `install` and `retry-failed`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UpdateOptions {
+	/// Print the duration of every package, not only slow ones
+	pub timings: bool,
+	/// Stop at the first failure
+	pub fail_fast: bool,
+	/// Delete the package and run the script from scratch
+	pub reinstall: bool,
+}
+
+/// Flags of `add`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AddOptions {
+	/// Allow spaces and unicode in the name
+	pub allow_weird_names: bool,
+	/// Register the package as pending without running the script
+	pub register_only: bool,
+	/// Don't record a checksum of the installed files
+	pub no_verify: bool,
+}
+
+/// Selection and order of packages in `list`.
+#[derive(Debug, Default)]
+pub struct ListOptions<'a> {
+	/// Only packages labeled with this tag
+	pub tag: Option<&'a str>,
+	/// Only packages of this type
+	pub r#type: Option<&'a str>,
+	pub sort: SortKey,
+	/// Add tag, install state and last change columns
+	pub long: bool,
+	/// Print args in full instead of truncating them to the terminal width
+	pub full: bool,
+}
+
+#[derive(Debug, Clone)]
 struct Package {
 	r#type: String,
 	args: Box<[String]>,
 	/// Tag of the package
 	tag: Option<String>,
 	cwd: Option<String>,
+	description: Option<String>,
+	/// Labels used to select packages
+	tags: Vec<String>,
+	/// RFC 3339 timestamps
+	added_at: Option<String>,
+	updated_at: Option<String>,
+	size_bytes: Option<u64>,
+	checksum: Option<String>,
+	no_verify: bool,
+	state: State,
+	error: Option<String>,
+	failed_at: Option<String>,
 }
 
 impl Package {
-	fn new(r#type: String, args: Box<[String]>, cwd: bool) -> Self {
-		Self {
+	/// Create a package, `cwd` is the directory passed to the script, which must be valid UTF-8.
+	///
+	/// A `cwd` inside the home directory is stored as `~/...`.
+	fn new(
+		r#type: String,
+		args: Box<[String]>,
+		cwd: Option<PathBuf>,
+		description: Option<String>,
+	) -> Result<Self> {
+		let cwd = match cwd.as_deref().map(contract_tilde) {
+			Some(dir) => match dir.to_str() {
+				Some(dir) => Some(dir.to_string()),
+				None => bail!(
+					"directory '{}' is not valid UTF-8",
+					dir.display().to_string().themed(Role::Warning)
+				),
+			},
+			None => None,
+		};
+		Ok(Self {
 			r#type,
 			args,
 			tag: None,
-			cwd: if cwd {
-				Some(env::current_dir().unwrap().to_str().unwrap().into())
-			} else {
-				None
-			},
-		}
+			cwd,
+			description: description.filter(|d| !d.is_empty()),
+			tags: vec![],
+			added_at: Some(timestamp()),
+			updated_at: None,
+			size_bytes: None,
+			checksum: None,
+			no_verify: false,
+			state: State::Ok,
+			error: None,
+			failed_at: None,
+		})
 	}
 
-	/// Add package, execute the script.
-	fn add(&mut self, name: &str, repo_path: &Path, type_config: &TypeConfig) -> Result<()> {
-		let tag = type_config.execute(
-			&self.r#type,
-			name,
-			repo_path,
-			self.tag.as_deref(),
-			self.cwd.as_deref(),
-			&self.args,
-		)?;
+	fn has_tag(&self, tag: &str) -> bool {
+		self.tags.iter().any(|t| t == tag)
+	}
+
+	/// When the package last changed, the update or else the addition time.
+	fn changed_at(&self) -> Option<DateTime<FixedOffset>> {
+		let time = self.updated_at.as_ref().or(self.added_at.as_ref())?;
+		DateTime::parse_from_rfc3339(time).ok()
+	}
+
+	/// Record a failed run of the script.
+	fn failed(&mut self, error: &anyhow::Error) {
+		self.state = State::Failed;
+		self.error = error.to_string().lines().next().map(String::from);
+		self.failed_at = Some(timestamp());
+	}
+
+	/// Add package, execute the script, recording whether it failed.
+	///
+	/// `updated_at` is only set when the script reports a different tag.
+	fn add(&mut self, target: Target, type_config: &TypeConfig) -> Result<()> {
+		let tag = type_config
+			.execute(
+				&self.r#type,
+				target,
+				self.tag.as_deref(),
+				self.cwd().as_deref(),
+				&self.args,
+			)
+			.inspect_err(|e| {
+				if !e.is::<OfflineError>() {
+					self.failed(e)
+				}
+			})?;
+		let installed = installed_path(target.repo_path, target.name);
+		if installed.is_none() && !type_config.no_artifact(&self.r#type) {
+			let e = anyhow!(
+				"script completed but produced no files, expected '{}' in '{}'",
+				target.name.themed(Role::Warning),
+				target.repo_path.display().to_string().themed(Role::Path)
+			);
+			self.failed(&e);
+			return Err(e);
+		}
+		self.state = State::Ok;
+		self.error = None;
+		self.failed_at = None;
+		self.size_bytes = installed.as_deref().map(du::size);
+		self.checksum = None;
+		if let Some(path) = installed.filter(|_| !self.no_verify) {
+			if let Err(e) = self.record_checksum(target, &path) {
+				error!(
+					"failed to compute the checksum of '{}' {}",
+					target.name.themed(Role::Warning),
+					e
+				);
+			}
+		}
 		if !tag.is_empty() {
+			if self.tag.as_deref() != Some(&tag) {
+				self.updated_at = Some(timestamp());
+			}
 			self.tag = Some(tag);
 		}
 
 		Ok(())
 	}
 
-	fn remove(&self, name: &str, repo_path: &Path) -> Result<()> {
-		let path = repo_path.join(name);
-		match fs::metadata(&path) {
-			io::Result::Ok(meta) => {
-				if meta.is_dir() {
-					fs::remove_dir_all(path)?;
-				} else {
-					fs::remove_file(path)?;
-				}
-			}
-			Err(e) => return Err(e.into()),
-		}
+	/// Hash the installed files at `path`, keeping the digest and saving the per-file hashes.
+	fn record_checksum(&mut self, target: Target, path: &Path) -> Result<()> {
+		let files = checksum::files(path)?;
+		checksum::save_manifest(target.repo, target.name, &files)?;
+		self.checksum = Some(checksum::digest(&files));
 		Ok(())
 	}
 
-	fn copy(&self, repo_path: &Path, name: &str) -> Result<()> {
-		let from = repo_path.join(name);
-		let to = env::current_dir()?.join(name);
-		if fs::metadata(&from)?.is_dir() {
-			copy_dir_all(from, to)?;
+	fn remove(&self, name: &str, repo_path: &Path) -> Result<()> {
+		let Some(path) = installed_path(repo_path, name) else {
+			return Err(not_installed(repo_path, name).into());
+		};
+		if fs::metadata(&path)?.is_dir() {
+			fs::remove_dir_all(path)?;
 		} else {
-			fs::copy(from, to)?;
+			fs::remove_file(path)?;
 		}
 		Ok(())
 	}
+
+	/// Delete the package directory and clear the tag, then add it again.
+	///
+	/// The package stays registered, marked failed, when either step fails.
+	fn reinstall(&mut self, target: Target, type_config: &TypeConfig) -> Result<()> {
+		match self.remove(target.name, target.repo_path) {
+			Err(e)
+				if e.downcast_ref::<io::Error>()
+					.is_none_or(|e| e.kind() != io::ErrorKind::NotFound) =>
+			{
+				self.failed(&e);
+				return Err(e);
+			}
+			_ => {}
+		}
+		self.tag = None;
+		self.add(target, type_config)
+	}
+
+	/// Directory passed to the script, with a leading `~` expanded to the current home directory.
+	fn cwd(&self) -> Option<String> {
+		let cwd = self.cwd.as_deref()?;
+		Some(match expand_tilde(Path::new(cwd)) {
+			Ok(path) => path.to_string_lossy().into_owned(),
+			Err(_) => cwd.to_string(),
+		})
+	}
+
+	/// Rewrite a `cwd` inside the home directory as `~/...`, returning whether it changed.
+	fn contract_cwd(&mut self) -> bool {
+		let Some(cwd) = &self.cwd else {
+			return false;
+		};
+		let contracted = contract_tilde(Path::new(cwd));
+		match contracted.to_str() {
+			Some(contracted) if contracted != cwd => {
+				self.cwd = Some(contracted.to_string());
+				true
+			}
+			_ => false,
+		}
+	}
+
+	fn copy(&self, repo_path: &Path, name: &str, copier: &Copier) -> Result<()> {
+		let Some(path) = installed_path(repo_path, name) else {
+			return Err(not_installed(repo_path, name).into());
+		};
+		let file_name = path.file_name().unwrap_or(name.as_ref());
+		copier.copy(&path, &env::current_dir()?.join(file_name))
+	}
 }
 
 impl From<TomlPackage> for Package {
@@ -309,20 +2370,16 @@ impl From<TomlPackage> for Package {
 			args: package.args,
 			tag: package.tag,
 			cwd: package.cwd,
+			description: package.description,
+			tags: package.tags,
+			added_at: package.added_at,
+			updated_at: package.updated_at,
+			size_bytes: package.size_bytes,
+			checksum: package.checksum,
+			no_verify: package.no_verify,
+			state: package.state,
+			error: package.error,
+			failed_at: package.failed_at,
 		}
 	}
 }
-
-fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
-	fs::create_dir_all(&dst)?;
-	for entry in fs::read_dir(src)? {
-		let entry = entry?;
-		let ty = entry.file_type()?;
-		if ty.is_dir() {
-			copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
-		} else {
-			fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
-		}
-	}
-	Ok(())
-}
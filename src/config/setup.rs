@@ -0,0 +1,299 @@
+//! Standalone scripts reproducing the packages of a repository on machines without gpm, written
+//! by `repo export-script`.
+
+use super::r#type::{invocation, program, resolve_args, Target, TypeConfig};
+use super::repository::RepoConfig;
+use super::util::{expand_tilde, powershell_quote, powershell_string, shell_quote, timestamp};
+use crate::note;
+use crate::redact;
+use crate::theme::{Role, Themed};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+/// Directory the generated script writes the type scripts to, relative to where it installs.
+const SCRIPTS: &str = ".gpm-setup";
+
+/// Language of the generated script.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SetupShell {
+	Bash,
+	Pwsh,
+}
+
+impl Default for SetupShell {
+	fn default() -> Self {
+		if cfg!(windows) {
+			SetupShell::Pwsh
+		} else {
+			SetupShell::Bash
+		}
+	}
+}
+
+impl SetupShell {
+	fn quote(self, word: &str) -> String {
+		match self {
+			SetupShell::Bash => shell_quote(word),
+			SetupShell::Pwsh => powershell_quote(word),
+		}
+	}
+
+	/// Command line running `command`, the program first.
+	fn command(self, command: &[String]) -> String {
+		let words: Vec<String> = command.iter().map(|word| self.quote(word)).collect();
+		match self {
+			SetupShell::Bash => words.join(" "),
+			SetupShell::Pwsh => format!("& {}", words.join(" ")),
+		}
+	}
+}
+
+/// A type script written by the generated script.
+struct Script<'a> {
+	name: &'a str,
+	/// Path relative to the install directory
+	path: String,
+	content: String,
+}
+
+/// Lines writing `script` to its path.
+fn write_script(shell: SetupShell, script: &Script) -> Result<String> {
+	let mut content = script.content.clone();
+	if !content.ends_with('\n') {
+		content.push('\n');
+	}
+	Ok(match shell {
+		SetupShell::Bash => {
+			// A delimiter no line of the script equals.
+			let mut delimiter = "GPM_SCRIPT".to_string();
+			while content.lines().any(|line| line == delimiter) {
+				delimiter.push('_');
+			}
+			format!(
+				"cat > {path} <<'{delimiter}'\n{content}{delimiter}\nchmod +x {path}\n",
+				path = shell_quote(&script.path),
+			)
+		}
+		SetupShell::Pwsh => {
+			if content.lines().any(|line| line.starts_with("'@")) {
+				bail!(
+					"the script of type '{}' has a line starting with \"'@\", which can't be embedded in PowerShell",
+					script.name.themed(Role::Warning)
+				);
+			}
+			format!(
+				"Set-Content -LiteralPath {} -Value @'\n{}'@\n",
+				powershell_string(&script.path),
+				content
+			)
+		}
+	})
+}
+
+/// Lines setting the environment variables of a type before running its script, checking that
+/// masked secrets are set instead of writing them out.
+fn env_lines(shell: SetupShell, r#type: &str, env: &[(&String, &String)]) -> Vec<String> {
+	env.iter()
+		.map(|(key, value)| {
+			let shown = redact::env_value(key, value);
+			match (shell, shown == **value) {
+				(SetupShell::Bash, true) => format!("export {}={}", key, shell_quote(value)),
+				(SetupShell::Bash, false) => format!(
+					": \"${{{}:?type {} needs it, set it before running}}\"",
+					key, r#type
+				),
+				(SetupShell::Pwsh, true) => format!("$env:{} = {}", key, powershell_string(value)),
+				(SetupShell::Pwsh, false) => format!(
+					"if (-not $env:{}) {{ throw {} }}",
+					key,
+					powershell_string(&format!("{} is not set, type {} needs it", key, r#type))
+				),
+			}
+		})
+		.collect()
+}
+
+/// Script installing the packages of `repo_cfg` into the directory given as its first argument,
+/// the current one by default, in name order.
+///
+/// Each type script is embedded and run with its shell like gpm does. Packages whose directory
+/// exists are skipped, so running it again only installs what's missing. Secrets in the
+/// environment of a type are required from the environment unless `--show-secrets` is given.
+pub fn generate(
+	repo: &str,
+	repo_cfg: &RepoConfig,
+	type_cfg: &TypeConfig,
+	shell: SetupShell,
+) -> Result<String> {
+	let (shells, types) = type_cfg.export();
+	let (packages, _) = repo_cfg.export();
+	let mut scripts = vec![];
+	let mut steps = vec![];
+	for (name, package) in &packages {
+		let Some(r#type) = types.get(&package.r#type) else {
+			note!(
+				"skipped '{}', type '{}' is not in types.toml",
+				name,
+				package.r#type.themed(Role::Warning)
+			);
+			steps.push(format!(
+				"# {}: skipped, type '{}' is missing\n",
+				name, package.r#type
+			));
+			continue;
+		};
+		let (Some(content), Some(shell_args)) = (&r#type.script, shells.get(&r#type.shell)) else {
+			note!(
+				"skipped '{}', the script or shell of type '{}' is missing",
+				name,
+				package.r#type.themed(Role::Warning)
+			);
+			steps.push(format!(
+				"# {}: skipped, the script or shell of type '{}' is missing\n",
+				name, package.r#type
+			));
+			continue;
+		};
+		let path = format!("./{}/{}.{}", SCRIPTS, package.r#type, r#type.ext);
+		if !scripts.iter().any(|s: &Script| s.name == package.r#type) {
+			scripts.push(Script {
+				name: &package.r#type,
+				path: path.clone(),
+				content: content.clone(),
+			});
+		}
+
+		let target = Target {
+			name,
+			repo,
+			repo_path: Path::new("."),
+		};
+		let args = resolve_args(&package.args, &target, &package.r#type);
+		let cwd = package
+			.cwd
+			.as_deref()
+			.map(|cwd| expand_tilde(Path::new(cwd)).map(|cwd| cwd.to_string_lossy().into_owned()))
+			.transpose()?;
+		let mut flags = vec![("-n", name.as_str())];
+		flags.extend(cwd.as_deref().map(|cwd| ("-d", cwd)));
+		let command: Vec<String> = std::iter::once(program(&r#type.shell).to_string())
+			.chain(
+				invocation(&r#type.shell, shell_args, Path::new(&path), &flags, &args)
+					.into_iter()
+					.map(|word| word.to_string_lossy().into_owned()),
+			)
+			.collect();
+		let env: Vec<_> = r#type.env.iter().collect();
+		let mut run = env_lines(shell, &package.r#type, &env);
+		run.push(shell.command(&command));
+		let step = match (shell, r#type.no_artifact) {
+			(SetupShell::Bash, true) => format!("({})\n", run.join("; ")),
+			(SetupShell::Bash, false) => format!(
+				"if [ -e {name} ]; then\n\techo {skipped}\nelse\n\t({run})\nfi\n",
+				name = shell_quote(name),
+				skipped = shell_quote(&format!("{} is installed, skipped", name)),
+				run = run.join("; ")
+			),
+			(SetupShell::Pwsh, no_artifact) => {
+				// Variables set for the script are process wide, removed once it ran.
+				run.extend(
+					env.iter()
+						.map(|(key, _)| format!("Remove-Item -ErrorAction Ignore Env:{}", key)),
+				);
+				if no_artifact {
+					run.join("\n") + "\n"
+				} else {
+					format!(
+						"if (Test-Path -LiteralPath {}) {{\n\tWrite-Output {}\n}} else {{\n\t{}\n}}\n",
+						powershell_string(name),
+						powershell_string(&format!("{} is installed, skipped", name)),
+						run.join("\n\t")
+					)
+				}
+			}
+		};
+		steps.push(format!("# {}, type {}\n{}", name, package.r#type, step));
+	}
+
+	let header = format!(
+		"Generated by gpm {} from repository '{}' on {}, run 'gpm repo {} export-script' again instead of editing.\n\
+		 Installs the packages into the directory given as the first argument, the current one by default.\n\
+		 Packages whose directory exists are skipped, so it can run again.",
+		env!("CARGO_PKG_VERSION"),
+		repo,
+		timestamp(),
+		repo
+	);
+	let header: Vec<String> = header
+		.lines()
+		.map(|line| format!("# {}", line.trim()))
+		.collect();
+	let mut out = String::new();
+	match shell {
+		SetupShell::Bash => {
+			out.push_str("#!/usr/bin/env bash\n");
+			out.push_str(&header.join("\n"));
+			out.push_str("\nset -euo pipefail\n");
+			out.push_str("dest=\"${1:-.}\"\nmkdir -p \"$dest\"\ncd \"$dest\"\n");
+			out.push_str(&format!(
+				"mkdir -p {scripts}\ntrap 'rm -rf {scripts}' EXIT\n",
+				scripts = SCRIPTS
+			));
+		}
+		SetupShell::Pwsh => {
+			out.push_str(&header.join("\n"));
+			out.push_str("\nparam([string]$Dest = '.')\n$ErrorActionPreference = 'Stop'\n");
+			out.push_str(
+				"New-Item -ItemType Directory -Force -Path $Dest | Out-Null\nSet-Location $Dest\n",
+			);
+			out.push_str(&format!(
+				"New-Item -ItemType Directory -Force -Path {} | Out-Null\ntry {{\n",
+				SCRIPTS
+			));
+		}
+	}
+	for script in &scripts {
+		out.push('\n');
+		out.push_str(&write_script(shell, script)?);
+	}
+	for step in &steps {
+		out.push('\n');
+		out.push_str(step);
+	}
+	if shell == SetupShell::Pwsh {
+		out.push_str(&format!(
+			"}} finally {{\n\tRemove-Item -Recurse -Force {}\n}}\n",
+			SCRIPTS
+		));
+	}
+	Ok(out)
+}
+
+/// Generate the script of repository `repo` and write it to `output`, executable, or print it.
+pub fn export(
+	repo: &str,
+	repo_cfg: &RepoConfig,
+	shell: SetupShell,
+	output: Option<&Path>,
+) -> Result<()> {
+	let script = generate(repo, repo_cfg, &TypeConfig::load()?, shell)?;
+	let Some(output) = output else {
+		print!("{}", script);
+		return Ok(());
+	};
+	fs::write(output, script)?;
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		fs::set_permissions(output, fs::Permissions::from_mode(0o755))?;
+	}
+	note!(
+		"exported to '{}'",
+		output.display().to_string().themed(Role::Path)
+	);
+	Ok(())
+}
@@ -0,0 +1,257 @@
+//! Shims in GPM_HOME/bin pointing at executables of packages, recorded in shims.toml.
+
+use super::main::Config;
+use super::repository::RepoConfig;
+use super::util::{load_toml, not_found, save_toml, validate_name, HistoryEntry};
+use crate::theme::{Role, Themed};
+use crate::{add, error, note, remove, BIN_PATH, REPO_CONFIG, SHIMS_CONFIG};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tabwriter::TabWriter;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShimConfig {
+	/// Key: shim name, Value: what it points at
+	#[serde(default)]
+	shims: BTreeMap<String, Shim>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Shim {
+	repo: String,
+	package: String,
+	/// Executable relative to the repository directory
+	path: String,
+}
+
+/// File of shim `name` in the bin directory.
+fn shim_file(name: &str) -> PathBuf {
+	if cfg!(windows) {
+		BIN_PATH.join(format!("{}.cmd", name))
+	} else {
+		BIN_PATH.join(name)
+	}
+}
+
+/// Create or replace the shim file of `name` pointing at `target`.
+fn write_shim(name: &str, target: &Path) -> Result<()> {
+	fs::create_dir_all(&*BIN_PATH)?;
+	let file = shim_file(name);
+	if file.symlink_metadata().is_ok() {
+		fs::remove_file(&file)?;
+	}
+	#[cfg(unix)]
+	std::os::unix::fs::symlink(target, file)?;
+	#[cfg(windows)]
+	fs::write(file, format!("@\"{}\" %*\r\n", target.display()))?;
+	Ok(())
+}
+
+/// Executable of a package, `exe` relative to its directory, else the package itself when it's a
+/// file, else a file named after the package inside it.
+fn find_executable(package: &str, package_path: &Path, exe: Option<&str>) -> Result<PathBuf> {
+	if let Some(exe) = exe {
+		let path = package_path.join(exe);
+		if !path.is_file() {
+			bail!(
+				"'{}' does not exist",
+				path.display().to_string().themed(Role::Warning)
+			);
+		}
+		return Ok(path);
+	}
+	if package_path.is_file() {
+		return Ok(package_path.to_path_buf());
+	}
+	let mut candidates = vec![package.to_string()];
+	if cfg!(windows) {
+		candidates.insert(0, format!("{}.exe", package));
+	}
+	match candidates
+		.iter()
+		.map(|c| package_path.join(c))
+		.find(|p| p.is_file())
+	{
+		Some(path) => Ok(path),
+		None => bail!(
+			"no executable named '{}' in '{}', pass --exe",
+			package.themed(Role::Warning),
+			package_path.display()
+		),
+	}
+}
+
+impl ShimConfig {
+	/// Load shims.toml, empty if it doesn't exist yet.
+	pub fn load() -> Result<Self> {
+		if SHIMS_CONFIG.exists() {
+			load_toml(&SHIMS_CONFIG)
+		} else {
+			Ok(Self::default())
+		}
+	}
+
+	/// Save to shims.toml.
+	pub fn save(&self) -> Result<()> {
+		save_toml(&SHIMS_CONFIG, self)
+	}
+
+	/// Link an executable of `package`, named `name` or after the executable.
+	pub fn link(
+		&mut self,
+		repo: &str,
+		package: &str,
+		name: Option<String>,
+		exe: Option<&str>,
+	) -> Result<()> {
+		let gpm_cfg = Config::load()?;
+		let repo = &*gpm_cfg.registered(repo);
+		let repo_path = gpm_cfg.get_repo_path(repo)?;
+		let repo_cfg = RepoConfig::load(repo, &repo_path.join(REPO_CONFIG))?;
+		let package = &*repo_cfg.registered(package);
+		let package_path = repo_cfg.package_path(package)?;
+		let repo_path = dunce::canonicalize(repo_path)?;
+		let target = find_executable(package, &package_path, exe)?;
+		let name = match name {
+			Some(name) => name,
+			None => target
+				.file_stem()
+				.unwrap_or_default()
+				.to_string_lossy()
+				.into_owned(),
+		};
+		validate_name(&name, false)?;
+		if let Some(shim) = self.shims.get(&name) {
+			if shim.repo != repo || shim.package != package {
+				bail!(
+					"shim '{}' already links '{}/{}'",
+					name.themed(Role::Warning),
+					shim.repo,
+					shim.package
+				);
+			}
+		}
+		write_shim(&name, &target)?;
+		HistoryEntry::new("bin link")
+			.repo(repo)
+			.package(package)
+			.args(&[&name])
+			.record();
+		add!(
+			"{}\t{}",
+			name.themed(Role::Name),
+			target.display().to_string().themed(Role::Path)
+		);
+		let path = target
+			.strip_prefix(&repo_path)
+			.unwrap_or(&target)
+			.to_string_lossy()
+			.into_owned();
+		self.shims.insert(
+			name,
+			Shim {
+				repo: repo.to_string(),
+				package: package.to_string(),
+				path,
+			},
+		);
+		Ok(())
+	}
+
+	/// Delete shims and forget them.
+	pub fn unlink(&mut self, names: &[String]) {
+		for name in names {
+			let Some(shim) = self.shims.remove(name) else {
+				error!(not_found("shim", name, self.shims.keys()));
+				continue;
+			};
+			match fs::remove_file(shim_file(name)) {
+				Ok(()) => {}
+				Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+				Err(e) => {
+					error!(
+						"failed to remove shim '{}' {}",
+						name.themed(Role::Warning),
+						e
+					);
+					self.shims.insert(name.clone(), shim);
+					continue;
+				}
+			}
+			HistoryEntry::new("bin unlink")
+				.repo(&shim.repo)
+				.package(&shim.package)
+				.args(&[name])
+				.record();
+			remove!("{}", name.themed(Role::Name));
+		}
+	}
+
+	/// Names of the shims of a package.
+	pub fn of_package(&self, repo: &str, package: &str) -> Vec<String> {
+		self.shims
+			.iter()
+			.filter(|(_, s)| s.repo == repo && s.package == package)
+			.map(|(name, _)| name.clone())
+			.collect()
+	}
+
+	/// Recreate the shims of a package after its script ran, noting those whose target is gone.
+	///
+	/// Symlinks follow a replaced executable by themselves, but the `.cmd` trampolines written on
+	/// Windows embed the path.
+	pub fn refresh(&self, repo: &str, package: &str, repo_path: &Path) -> Result<()> {
+		for name in self.of_package(repo, package) {
+			let target = repo_path.join(&self.shims[&name].path);
+			if target.exists() {
+				write_shim(&name, &target)?;
+			} else {
+				note!(
+					"shim '{}' points to missing '{}'",
+					name.themed(Role::Warning),
+					target.display()
+				);
+			}
+		}
+		Ok(())
+	}
+
+	/// Render shims with their package and target, marking broken ones.
+	pub fn list(&self) -> Result<String> {
+		let gpm_cfg = Config::load()?;
+		let mut tw = TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", "Shims:".themed(Role::Success))?;
+		for (name, shim) in &self.shims {
+			let target = gpm_cfg
+				.get_repo_path(&shim.repo)
+				.map(|path| path.join(&shim.path));
+			let (target, broken) = match &target {
+				Ok(target) => (target.display().to_string(), !target.exists()),
+				Err(_) => (String::new(), true),
+			};
+			writeln!(
+				&mut tw,
+				"  {}\t{}/{}\t{}{}",
+				name.themed(Role::Name),
+				shim.repo,
+				shim.package,
+				target.themed(Role::Path),
+				if broken {
+					" (broken)".themed(Role::Error)
+				} else {
+					"".normal()
+				}
+			)?;
+		}
+		tw.flush()?;
+		Ok(String::from_utf8(tw.into_inner().unwrap())?)
+	}
+}
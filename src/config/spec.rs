@@ -0,0 +1,404 @@
+//! Packages to add in one go, from `add --spec` and `add --pkg` strings or lists read by
+//! `add --from-file` and `add --from-stdin`.
+
+use crate::theme::{Role, Themed};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// A package to add, with where it was found for error messages.
+#[derive(Debug, PartialEq)]
+pub struct Spec {
+	/// `line N` or `package N`, counting from 1
+	pub location: String,
+	pub name: String,
+	pub r#type: String,
+	pub args: Vec<String>,
+	/// Directory passed to the script
+	pub cwd: Option<PathBuf>,
+	pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+	#[serde(default)]
+	package: Vec<Package>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Package {
+	name: String,
+	r#type: String,
+	#[serde(default)]
+	args: Vec<String>,
+	#[serde(default)]
+	cwd: bool,
+	description: Option<String>,
+}
+
+/// Read specs from `path`, or from stdin when `None`.
+pub fn read(path: Option<&Path>) -> Result<Vec<Spec>> {
+	let content = match path {
+		Some(path) => fs::read_to_string(path).map_err(|e| {
+			anyhow!(
+				"failed to read '{}' {}",
+				path.display().to_string().themed(Role::Warning),
+				e
+			)
+		})?,
+		None => {
+			let mut content = String::new();
+			io::stdin().read_to_string(&mut content)?;
+			content
+		}
+	};
+	parse(&content)
+}
+
+/// Parse `name<TAB>type<TAB>args` lines, or `[[package]]` tables when the first line that isn't
+/// blank or a `#` comment starts with `[`.
+///
+/// Args on a line are split on whitespace, the TOML form takes args containing spaces and
+/// `cwd = true` for the current directory.
+pub fn parse(content: &str) -> Result<Vec<Spec>> {
+	let first = content
+		.lines()
+		.map(str::trim)
+		.find(|line| !line.is_empty() && !line.starts_with('#'));
+	if first.is_some_and(|line| line.starts_with('[')) {
+		let document: Document = toml::from_str(content)?;
+		return document
+			.package
+			.into_iter()
+			.enumerate()
+			.map(|(i, p)| {
+				Ok(Spec {
+					location: format!("package {}", i + 1),
+					name: p.name,
+					r#type: p.r#type,
+					args: p.args,
+					cwd: p.cwd.then(env::current_dir).transpose()?,
+					description: p.description,
+				})
+			})
+			.collect();
+	}
+	let mut specs = vec![];
+	for (i, line) in content.lines().enumerate() {
+		if line.trim().is_empty() || line.trim_start().starts_with('#') {
+			continue;
+		}
+		let mut fields = line.splitn(3, '\t').map(str::trim);
+		let (Some(name), Some(r#type)) = (fields.next(), fields.next()) else {
+			return Err(anyhow!(
+				"line {} '{}' isn't 'name<TAB>type<TAB>args'",
+				i + 1,
+				line.themed(Role::Warning)
+			));
+		};
+		specs.push(Spec {
+			location: format!("line {}", i + 1),
+			name: name.to_string(),
+			r#type: r#type.to_string(),
+			args: fields
+				.next()
+				.unwrap_or_default()
+				.split_whitespace()
+				.map(String::from)
+				.collect(),
+			cwd: None,
+			description: None,
+		});
+	}
+	Ok(specs)
+}
+
+/// Parse a `name=type:args` spec, args split like a shell does, with quotes and backslashes.
+///
+/// `index` numbers the spec among the others passed, from 1.
+pub fn parse_compact(spec: &str, index: usize) -> Result<Spec> {
+	let Some(eq) = spec.find('=') else {
+		return Err(pointing(
+			spec,
+			spec.len(),
+			"expected '=' after the package name",
+		));
+	};
+	if eq == 0 {
+		return Err(pointing(spec, 0, "missing package name before '='"));
+	}
+	let rest = &spec[eq + 1..];
+	let (r#type, args) = match rest.find(':') {
+		Some(colon) => (&rest[..colon], Some(eq + 1 + colon + 1)),
+		None => (rest, None),
+	};
+	if r#type.is_empty() {
+		return Err(pointing(spec, eq + 1, "missing package type after '='"));
+	}
+	if let Some(space) = r#type.find(char::is_whitespace) {
+		return Err(pointing(
+			spec,
+			eq + 1 + space,
+			"whitespace in the package type, args go after ':'",
+		));
+	}
+	Ok(Spec {
+		location: format!("spec {}", index),
+		name: spec[..eq].to_string(),
+		r#type: r#type.to_string(),
+		args: match args {
+			Some(start) => split_words(spec, start)?,
+			None => vec![],
+		},
+		cwd: None,
+		description: None,
+	})
+}
+
+/// Parse a `name=args` package of `add --pkg`, of type `r#type`, args split like
+/// `parse_compact` does. A package without args may leave out the `=`.
+pub fn parse_pkg(pkg: &str, r#type: &str, cwd: Option<&Path>, index: usize) -> Result<Spec> {
+	let (name, args) = match pkg.find('=') {
+		Some(eq) => (&pkg[..eq], split_words(pkg, eq + 1)?),
+		None => (pkg, vec![]),
+	};
+	if name.is_empty() {
+		return Err(pointing(pkg, 0, "missing package name before '='"));
+	}
+	Ok(Spec {
+		location: format!("package {}", index),
+		name: name.to_string(),
+		r#type: r#type.to_string(),
+		args,
+		cwd: cwd.map(Path::to_path_buf),
+		description: None,
+	})
+}
+
+/// Split `spec` from byte `start` into words on whitespace, `'` quoting literally and `"`
+/// allowing `\"` and `\\` inside.
+fn split_words(spec: &str, start: usize) -> Result<Vec<String>> {
+	let mut words = vec![];
+	let mut word: Option<String> = None;
+	let mut chars = spec[start..].char_indices().map(|(i, c)| (start + i, c));
+	while let Some((i, c)) = chars.next() {
+		match c {
+			c if c.is_whitespace() => words.extend(word.take()),
+			'\'' => {
+				let word = word.get_or_insert_with(String::new);
+				loop {
+					match chars.next() {
+						Some((_, '\'')) => break,
+						Some((_, c)) => word.push(c),
+						None => return Err(pointing(spec, i, "unterminated quote")),
+					}
+				}
+			}
+			'"' => {
+				let word = word.get_or_insert_with(String::new);
+				loop {
+					match chars.next() {
+						Some((_, '"')) => break,
+						Some((_, '\\')) => match chars.next() {
+							Some((_, c @ ('"' | '\\'))) => word.push(c),
+							Some((_, c)) => {
+								word.push('\\');
+								word.push(c);
+							}
+							None => return Err(pointing(spec, i, "unterminated quote")),
+						},
+						Some((_, c)) => word.push(c),
+						None => return Err(pointing(spec, i, "unterminated quote")),
+					}
+				}
+			}
+			'\\' => match chars.next() {
+				Some((_, c)) => word.get_or_insert_with(String::new).push(c),
+				None => return Err(pointing(spec, i, "nothing to escape after '\\'")),
+			},
+			c => word.get_or_insert_with(String::new).push(c),
+		}
+	}
+	words.extend(word);
+	Ok(words)
+}
+
+/// Extensions of downloads dropped from inferred names, tried repeatedly so `.tar.gz` goes too.
+const ARCHIVE_EXTS: [&str; 14] = [
+	".zip", ".tar", ".gz", ".tgz", ".xz", ".txz", ".bz2", ".zst", ".7z", ".exe", ".msi", ".deb",
+	".rpm", ".git",
+];
+
+/// Path segments that name a download location rather than what is downloaded, like the `main`
+/// of `.../archive/refs/heads/main.zip`.
+const GENERIC_SEGMENTS: [&str; 14] = [
+	"archive", "bin", "blob", "dl", "download", "file", "get", "index", "latest", "main", "master",
+	"raw", "release", "tarball",
+];
+
+/// Package name for `add --name-from-arg`, the last path segment of the URL or path in `arg`
+/// without archive extensions, a trailing `.git` and anything from a version on.
+///
+/// `ripgrep-14.1.0-x86_64.zip` gives `ripgrep`, fails when nothing usable is left or the name is
+/// ambiguous, a generic segment such as `download` or `main`.
+pub fn infer_name(arg: &str) -> Result<String> {
+	let path = arg.split_once("://").map_or(arg, |(_, rest)| rest);
+	let path = path.split(['?', '#']).next().unwrap_or_default();
+	let mut name = path
+		.trim_end_matches('/')
+		.rsplit(['/', '\\', ':'])
+		.next()
+		.unwrap_or_default();
+	while let Some(ext) = ARCHIVE_EXTS
+		.iter()
+		.find(|ext| name.to_lowercase().ends_with(*ext) && name.len() > ext.len())
+	{
+		name = &name[..name.len() - ext.len()];
+	}
+	// Cut before the first `-` or `_` separated part that looks like a version.
+	let mut end = name.len();
+	let mut start = 0;
+	for part in name.split(['-', '_']) {
+		if start > 0 && is_version(part) {
+			end = start - 1;
+			break;
+		}
+		start += part.len() + 1;
+	}
+	let name = &name[..end];
+	if name.is_empty() || is_version(name) || name.contains(['{', '}', '*', '?']) {
+		return Err(anyhow!(
+			"can't infer a package name from '{}', pass the name before the type instead",
+			arg.themed(Role::Warning)
+		));
+	}
+	if GENERIC_SEGMENTS
+		.iter()
+		.any(|generic| generic.eq_ignore_ascii_case(name))
+	{
+		return Err(anyhow!(
+			"'{}' from '{}' is ambiguous as a package name, pass the name before the type instead",
+			name.themed(Role::Warning),
+			arg
+		));
+	}
+	Ok(name.to_string())
+}
+
+/// `14.1.0`, `v1.2` or `20240101`, starting with a digit after an optional `v`.
+fn is_version(part: &str) -> bool {
+	let part = part.strip_prefix(['v', 'V']).unwrap_or(part);
+	part.starts_with(|c: char| c.is_ascii_digit())
+		&& part
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '+')
+		&& (part.contains('.') || part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Error showing `spec` with a caret under the character at byte `at`.
+fn pointing(spec: &str, at: usize, message: &str) -> anyhow::Error {
+	anyhow!(
+		"invalid spec, {}\n  {}\n  {}{}",
+		message,
+		spec,
+		" ".repeat(spec[..at].chars().count()),
+		"^".themed(Role::Warning)
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lines() {
+		let specs =
+			parse("# tools\nrg\tghrelease\tBurntSushi/ripgrep  rg-*.zip\n\nfd\tscoop\n").unwrap();
+		assert_eq!(specs.len(), 2);
+		assert_eq!(specs[0].location, "line 2");
+		assert_eq!(specs[0].args, ["BurntSushi/ripgrep", "rg-*.zip"]);
+		assert_eq!(specs[1].name, "fd");
+		assert!(specs[1].args.is_empty());
+		assert!(parse("rg ghrelease").is_err());
+	}
+
+	#[test]
+	fn tables() {
+		let specs = parse(
+			"# tools\n[[package]]\nname = \"rg\"\ntype = \"ghrelease\"\nargs = [\"a b\"]\ncwd = true\n",
+		)
+		.unwrap();
+		assert_eq!(specs[0].location, "package 1");
+		assert_eq!(specs[0].args, ["a b"]);
+		assert!(specs[0].cwd.is_some());
+	}
+
+	#[test]
+	fn compact() {
+		let spec = parse_compact(
+			"rg=ghrelease:BurntSushi/ripgrep '*linux* x'.tar.gz \"a\\\"b\"",
+			1,
+		)
+		.unwrap();
+		assert_eq!(spec.name, "rg");
+		assert_eq!(spec.r#type, "ghrelease");
+		assert_eq!(
+			spec.args,
+			["BurntSushi/ripgrep", "*linux* x.tar.gz", "a\"b"]
+		);
+		assert!(parse_compact("rg=ghrelease", 1).unwrap().args.is_empty());
+		assert!(parse_compact("rg=gh release", 1).is_err());
+		assert!(parse_compact("rg:ghrelease", 1).is_err());
+		assert!(parse_compact("=ghrelease", 1).is_err());
+		let error = parse_compact("rg=t:'a", 1).unwrap_err().to_string();
+		assert!(error.starts_with("invalid spec, unterminated quote\n  rg=t:'a\n"));
+		assert!(error.lines().last().unwrap().starts_with("       "));
+	}
+
+	#[test]
+	fn packages_of_a_type() {
+		let cwd = Path::new("/src");
+		let spec = parse_pkg(
+			"fd=https://github.com/sharkdp/fd 'a b'",
+			"git",
+			Some(cwd),
+			2,
+		)
+		.unwrap();
+		assert_eq!(spec.location, "package 2");
+		assert_eq!(spec.name, "fd");
+		assert_eq!(spec.args, ["https://github.com/sharkdp/fd", "a b"]);
+		assert_eq!(spec.cwd.as_deref(), Some(cwd));
+		assert!(parse_pkg("fd", "git", None, 1).unwrap().args.is_empty());
+		assert!(parse_pkg("=x", "git", None, 1).is_err());
+	}
+
+	#[test]
+	fn inferred_names() {
+		for (arg, name) in [
+			(
+				"https://example.com/dl/ripgrep-14.1.0-x86_64.zip?raw=1",
+				"ripgrep",
+			),
+			("https://github.com/sharkdp/fd.git", "fd"),
+			("git@github.com:junegunn/fzf.git", "fzf"),
+			("BurntSushi/ripgrep", "ripgrep"),
+			("https://example.com/tools/", "tools"),
+			("node_v20.1.0.tar.gz", "node"),
+			("7zip-23.01.exe", "7zip"),
+			("C:\\tools\\jq.exe", "jq"),
+		] {
+			assert_eq!(infer_name(arg).unwrap(), name, "{}", arg);
+		}
+		assert!(infer_name("https://example.com/v1.2.zip").is_err());
+		assert!(infer_name("{name}-*.zip").is_err());
+		assert!(infer_name("").is_err());
+		assert!(infer_name("https://github.com/o/r/archive/refs/heads/main.zip").is_err());
+		assert!(infer_name("https://example.com/latest/download").is_err());
+	}
+}
@@ -0,0 +1,130 @@
+//! Syncing GPM_HOME with a git repository.
+
+use super::main::Config;
+use super::r#type::TypeConfig;
+use crate::theme::{Role, Themed};
+use crate::{add, ensure_online, error, GPM_HOME};
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Paths under GPM_HOME that are never synced.
+const GITIGNORE: &str = "# generated by gpm
+repositories/
+backups/
+";
+
+/// Run git in GPM_HOME, failing on non-zero exit status.
+fn git(args: &[&str]) -> Result<()> {
+	let status = Command::new("git")
+		.current_dir(&*GPM_HOME)
+		.args(args)
+		.status()
+		.context("failed to run git, is it installed?")?;
+	tracing::info!(args = ?args, %status, "git");
+	if !status.success() {
+		bail!("'git {}' failed with {}", args.join(" "), status);
+	}
+	Ok(())
+}
+
+/// Run git in GPM_HOME, returning trimmed stdout.
+fn git_output(args: &[&str]) -> Result<String> {
+	let output = Command::new("git")
+		.current_dir(&*GPM_HOME)
+		.args(args)
+		.stderr(Stdio::inherit())
+		.output()
+		.context("failed to run git, is it installed?")?;
+	tracing::debug!(args = ?args, status = %output.status, "git");
+	if !output.status.success() {
+		bail!("'git {}' failed with {}", args.join(" "), output.status);
+	}
+	Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Initialize the git repository at GPM_HOME with `url` as the remote.
+pub fn init(url: &str) -> Result<()> {
+	if !GPM_HOME.exists() {
+		bail!(
+			"'{}' does not exist, run 'gpm init' first",
+			GPM_HOME.display()
+		);
+	}
+	fs::write(GPM_HOME.join(".gitignore"), GITIGNORE)?;
+	if !GPM_HOME.join(".git").exists() {
+		git(&["init", "--quiet"])?;
+	}
+	if git_output(&["remote"])?.lines().any(|r| r == "origin") {
+		git(&["remote", "set-url", "origin", url])?;
+	} else {
+		git(&["remote", "add", "origin", url])?;
+	}
+	add!("{}\t{}", "origin".themed(Role::Name), url);
+	commit()
+}
+
+/// Commit all changes, doing nothing if the tree is clean.
+fn commit() -> Result<()> {
+	git(&["add", "--all"])?;
+	let changes = git_output(&["status", "--porcelain"])?;
+	if changes.is_empty() {
+		return Ok(());
+	}
+	let message = format!(
+		"Sync gpm configuration\n\n{}",
+		changes
+			.lines()
+			.map(|l| format!("- {}", l.trim()))
+			.collect::<Vec<_>>()
+			.join("\n")
+	);
+	git(&["commit", "--quiet", "-m", &message])
+}
+
+/// Commit changes and push them to the remote.
+pub fn push() -> Result<()> {
+	ensure_online("config-sync push")?;
+	ensure_initialized()?;
+	commit()?;
+	git(&["push", "--set-upstream", "origin", "HEAD"])
+}
+
+/// Commit local changes, pull and merge from the remote, then validate the configs.
+pub fn pull() -> Result<()> {
+	ensure_online("config-sync pull")?;
+	ensure_initialized()?;
+	commit()?;
+	git(&["pull", "--no-rebase", "--no-edit", "origin", "HEAD"])?;
+	validate();
+	Ok(())
+}
+
+fn ensure_initialized() -> Result<()> {
+	if !GPM_HOME.join(".git").exists() {
+		bail!("config sync is not initialized, run 'gpm config-sync init <URL>' first");
+	}
+	Ok(())
+}
+
+/// Load the synced configs, reporting errors and repositories missing on this machine.
+fn validate() {
+	if let Err(e) = TypeConfig::load() {
+		error!("failed to load types config {}", e);
+	}
+	match Config::load() {
+		Ok(gpm_cfg) => {
+			for (name, path) in gpm_cfg.missing_repositories() {
+				error!(
+					"repository '{}' does not exist at '{}'",
+					name.themed(Role::Warning),
+					path.display()
+				);
+			}
+		}
+		Err(e) => error!("failed to load config {}", e),
+	}
+}
@@ -1,26 +1,41 @@
 //! Handling package type configuration file at TYPES_CONFIG.
 
-use super::util::{prompt, sort_keys};
-use crate::{add, error, remove, SCRIPT_ROOT, TYPES_CONFIG};
+use super::code::Code;
+use super::export::{self, Conflict};
+use super::names::NameMap;
+use super::util::{
+	command_line, create_dir_all, edit_toml, env_assignment, interrupted, load_toml, not_found,
+	powershell_string, prompt, prompt_batch, read_input, save_toml, shell_quote, sort_keys,
+	validate_name, which, HistoryEntry, OfflineError, Porcelain, TableFormat, ASSUME,
+};
+use crate::theme::{Role, Themed};
+use crate::{
+	add, environment, error, note, offline, redact, remove, status, verbose, SCRIPT_ROOT,
+	TYPES_CONFIG,
+};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::str::FromStr;
+use std::time::Instant;
 use std::{fmt, fs};
 use tabwriter::TabWriter;
 
 // Separate from the Config struct to allow more flexibility in the future.
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct TomlTypeConfig {
 	#[serde(serialize_with = "sort_keys")]
 	shell: HashMap<String, Box<[String]>>,
 	/// Key: type name, Value: type properties
-	#[serde(serialize_with = "sort_keys")]
+	#[serde(default, serialize_with = "sort_keys")]
 	types: HashMap<String, TomlTypeProp>,
 }
 
@@ -38,9 +53,19 @@ impl From<TypeConfig> for TomlTypeConfig {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct TomlTypeProp {
 	ext: String,
 	shell: String,
+	/// Arguments the script expects, in order
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	params: Vec<Param>,
+	/// Environment variables of the script, over those gpm sets
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	env: BTreeMap<String, String>,
+	/// The script installs outside the repository, so it isn't expected to create files there
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	no_artifact: bool,
 }
 
 impl From<TypeProp> for TomlTypeProp {
@@ -48,7 +73,392 @@ impl From<TypeProp> for TomlTypeProp {
 		Self {
 			ext: prop.ext,
 			shell: prop.shell,
+			params: prop.params,
+			env: prop.env,
+			no_artifact: prop.no_artifact,
+		}
+	}
+}
+
+/// Argument a script expects, declared in `params` of a type.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Param {
+	name: String,
+	#[serde(default = "required_by_default")]
+	required: bool,
+}
+
+fn required_by_default() -> bool {
+	true
+}
+
+impl Param {
+	/// `<name>` if required, `[name]` otherwise.
+	pub fn usage(&self) -> String {
+		if self.required {
+			format!("<{}>", self.name)
+		} else {
+			format!("[{}]", self.name)
+		}
+	}
+}
+
+impl FromStr for Param {
+	type Err = String;
+
+	/// Parse `name`, or `name?` for an optional parameter.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, required) = match s.strip_suffix('?') {
+			Some(name) => (name, false),
+			None => (s, true),
+		};
+		if name.is_empty() || name.contains(char::is_whitespace) {
+			return Err(format!("invalid parameter name '{}'", name));
+		}
+		Ok(Self {
+			name: name.to_string(),
+			required,
+		})
+	}
+}
+
+/// Changes of `type edit`, `None` and empty fields leave the type as it is.
+#[derive(Debug, Default)]
+pub struct TypeEdit {
+	/// Script file extension, the script is renamed
+	pub ext: Option<String>,
+	pub shell: Option<String>,
+	/// Parameters replacing the declared ones, empty to clear them
+	pub params: Option<Vec<Param>>,
+	/// Environment variables to set
+	pub env: Vec<(String, String)>,
+	/// Environment variables to remove
+	pub unset_env: Vec<String>,
+	pub no_artifact: Option<bool>,
+}
+
+/// Parse `KEY=VALUE` of `type edit --env`.
+pub fn parse_env(s: &str) -> Result<(String, String), String> {
+	match s.split_once('=') {
+		Some((key, value)) if !key.is_empty() && !key.contains(char::is_whitespace) => {
+			Ok((key.to_string(), value.to_string()))
+		}
+		_ => Err(format!("expected 'KEY=VALUE', got '{}'", s)),
+	}
+}
+
+/// Package a script is executed for.
+#[derive(Clone, Copy)]
+pub struct Target<'a> {
+	pub name: &'a str,
+	pub repo: &'a str,
+	pub repo_path: &'a Path,
+}
+
+/// Package args with `{name}`, `{dest}`, `{repo}` and `{type}` replaced by their values.
+///
+/// `{{` and `}}` stand for literal braces, other braces are kept as they are.
+pub fn resolve_args(args: &[String], target: &Target, type_name: &str) -> Vec<String> {
+	let dest = target.repo_path.join(target.name);
+	let dest = dest.to_string_lossy();
+	let values = [
+		("name", target.name),
+		("dest", &dest),
+		("repo", target.repo),
+		("type", type_name),
+	];
+	args.iter().map(|arg| substitute(arg, &values)).collect()
+}
+
+fn substitute(arg: &str, values: &[(&str, &str)]) -> String {
+	let mut out = String::with_capacity(arg.len());
+	let mut rest = arg;
+	while let Some(i) = rest.find(['{', '}']) {
+		out.push_str(&rest[..i]);
+		rest = &rest[i..];
+		if rest.starts_with("{{") || rest.starts_with("}}") {
+			out.push_str(&rest[..1]);
+			rest = &rest[2..];
+			continue;
+		}
+		let placeholder = values
+			.iter()
+			.find(|(key, _)| rest[1..].starts_with(key) && rest[1 + key.len()..].starts_with('}'));
+		match placeholder {
+			Some((key, value)) if rest.starts_with('{') => {
+				out.push_str(value);
+				rest = &rest[key.len() + 2..];
+			}
+			_ => {
+				out.push_str(&rest[..1]);
+				rest = &rest[1..];
+			}
+		}
+	}
+	out.push_str(rest);
+	out
+}
+
+/// Usage of a type's parameters, e.g. `<url> [asset_pattern]`.
+pub fn usage(params: &[Param]) -> String {
+	params
+		.iter()
+		.map(Param::usage)
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Exit code of a script that needs the network in offline mode, its package is then skipped.
+const EXIT_OFFLINE: i32 = 75;
+
+/// Arguments of PowerShell in new configurations, skipping the user profile and execution
+/// policy which slow down or block scripts.
+pub const POWERSHELL_ARGS: &[&str] = &["-NoProfile", "-ExecutionPolicy", "Bypass", "-Command"];
+
+/// Shell registered in a new configuration, with its arguments.
+#[cfg(target_os = "windows")]
+pub const DEFAULT_SHELL: (&str, &[&str]) = ("powershell", POWERSHELL_ARGS);
+#[cfg(not(target_os = "windows"))]
+pub const DEFAULT_SHELL: (&str, &[&str]) = ("bash", &["-c"]);
+
+/// Shells `type shell detect` looks for, with the arguments they are registered with.
+const KNOWN_SHELLS: [(&str, &[&str]); 6] = [
+	("pwsh", POWERSHELL_ARGS),
+	("powershell", POWERSHELL_ARGS),
+	("bash", &[]),
+	("zsh", &[]),
+	("fish", &[]),
+	("nu", &[]),
+];
+
+/// Types `init --migrate` registers for packages of the releases with namespaces, with their
+/// POSIX shell and PowerShell scripts. `git` clones its first argument or pulls it, printing the
+/// commit as the tag, `local` copies it.
+const BUILTIN_TYPES: [(&str, &str, &str); 2] = [
+	(
+		"git",
+		r#"name=
+while [ $# -gt 0 ]; do
+	case "$1" in
+		-n) name=$2; shift 2 ;;
+		-t | -d) shift 2 ;;
+		*) break ;;
+	esac
+done
+if [ -d "$name/.git" ]; then
+	git -C "$name" pull --quiet >&2 || exit
+else
+	git clone --quiet "$1" "$name" >&2 || exit
+fi
+git -C "$name" rev-parse --short HEAD
+"#,
+		r#"param([string]$n, [string]$t, [string]$d)
+if (Test-Path (Join-Path $n '.git')) {
+	git -C $n pull --quiet | Out-Host
+} else {
+	git clone --quiet $args[0] $n | Out-Host
+}
+if ($LASTEXITCODE) { exit $LASTEXITCODE }
+git -C $n rev-parse --short HEAD
+"#,
+	),
+	(
+		"local",
+		r#"name=
+while [ $# -gt 0 ]; do
+	case "$1" in
+		-n) name=$2; shift 2 ;;
+		-t | -d) shift 2 ;;
+		*) break ;;
+	esac
+done
+rm -rf "$name" && cp -R "$1" "$name"
+"#,
+		r#"param([string]$n, [string]$t, [string]$d)
+$ErrorActionPreference = 'Stop'
+if (Test-Path $n) { Remove-Item -Recurse -Force $n }
+Copy-Item -Recurse $args[0] $n
+"#,
+	),
+];
+
+/// `DEFAULT_SHELL`, or PowerShell 7 on Windows when `pwsh` is installed.
+pub fn default_shell() -> (&'static str, &'static [&'static str]) {
+	if cfg!(windows) && which("pwsh").is_some() {
+		("pwsh", POWERSHELL_ARGS)
+	} else {
+		DEFAULT_SHELL
+	}
+}
+
+/// Program of a shell entry, without the `:variant` suffix naming entries that share a program
+/// but differ in arguments, e.g. `powershell:profile`.
+///
+/// A suffix containing a path separator is part of the path, as in `C:\pwsh\pwsh.exe`.
+pub fn program(shell: &str) -> &str {
+	match shell.rsplit_once(':') {
+		Some((program, variant))
+			if !program.is_empty() && !variant.is_empty() && !variant.contains(['/', '\\']) =>
+		{
+			program
+		}
+		_ => shell,
+	}
+}
+
+/// Whether `shell` is Windows PowerShell or PowerShell 7, by its program name.
+fn is_powershell(shell: &str) -> bool {
+	let program = Path::new(program(shell))
+		.file_stem()
+		.map(|s| s.to_string_lossy().to_lowercase());
+	matches!(program.as_deref(), Some("powershell" | "pwsh"))
+}
+
+/// Whether PowerShell runs what follows its arguments as a command, rather than as a file.
+fn powershell_command(shell: &str, args: &[String]) -> bool {
+	is_powershell(shell)
+		&& args
+			.last()
+			.is_some_and(|a| a.eq_ignore_ascii_case("-c") || a.eq_ignore_ascii_case("-command"))
+}
+
+/// Quoting of the words of the command run by a POSIX shell or fish registered with `-c`.
+fn command_quote(shell: &str, args: &[String]) -> Option<fn(&str) -> String> {
+	if args.last().is_none_or(|a| a != "-c") {
+		return None;
+	}
+	let program = Path::new(program(shell))
+		.file_stem()
+		.map(|s| s.to_string_lossy().to_lowercase());
+	match program.as_deref()? {
+		"sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" => Some(shell_quote),
+		"fish" => Some(fish_quote),
+		_ => None,
+	}
+}
+
+/// Quote an argument for fish, where backslashes also escape inside single quotes.
+fn fish_quote(arg: &str) -> String {
+	format!("'{}'", arg.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Whether PowerShell is registered with only `-c`, as gpm did before `POWERSHELL_ARGS`.
+fn legacy_powershell(shell: &str, args: &[String]) -> bool {
+	is_powershell(shell) && matches!(args, [arg] if arg.eq_ignore_ascii_case("-c"))
+}
+
+/// Arguments of the shell running `script` with `flags` and `args`, after the program.
+pub(super) fn invocation(
+	shell: &str,
+	shell_args: &[String],
+	script: &Path,
+	flags: &[(&str, &str)],
+	args: &[String],
+) -> Vec<OsString> {
+	let mut invocation: Vec<OsString> = shell_args.iter().map(OsString::from).collect();
+	if powershell_command(shell, shell_args) {
+		// The text after `-Command` is parsed as code, so every value is passed as a literal.
+		let words: Vec<String> = ["&".to_string(), powershell_word(&script.to_string_lossy())]
+			.into_iter()
+			.chain(
+				flags
+					.iter()
+					.flat_map(|(flag, value)| [flag.to_string(), powershell_word(value)]),
+			)
+			.chain(args.iter().map(|a| powershell_word(a)))
+			.collect();
+		invocation.push(words.join(" ").into());
+	} else if let Some(quote) = command_quote(shell, shell_args) {
+		// As for PowerShell, otherwise the script runs without flags and args, which would become
+		// `$0`, `$1` and so on of the command.
+		let words: Vec<String> = std::iter::once(script.to_string_lossy().into_owned())
+			.chain(
+				flags
+					.iter()
+					.flat_map(|(flag, value)| [flag.to_string(), value.to_string()]),
+			)
+			.chain(args.iter().cloned())
+			.map(|word| quote(&word))
+			.collect();
+		invocation.push(words.join(" ").into());
+	} else {
+		invocation.push(script.into());
+		for (flag, value) in flags {
+			invocation.push(flag.into());
+			invocation.push(value.into());
 		}
+		invocation.extend(args.iter().map(OsString::from));
+	}
+	invocation
+}
+
+/// Reject extensions that would put the script outside the scripts directory or double the dot.
+fn validate_ext(ext: &str) -> Result<()> {
+	let reason = if ext.is_empty() {
+		"it's empty"
+	} else if ext.starts_with('.') {
+		"it starts with '.'"
+	} else if ext.contains(['/', '\\']) {
+		"it contains a path separator"
+	} else {
+		return Ok(());
+	};
+	bail!(
+		"invalid extension '{}': {}, pass it like 'ps1'",
+		ext.themed(Role::Warning),
+		reason
+	)
+}
+
+/// Shebang line of new scripts run by `shell`, through `env` unless it's an absolute path.
+fn shebang(shell: &str) -> String {
+	if Path::new(shell).is_absolute() {
+		format!("#!{}", shell)
+	} else if shell == "sh" {
+		"#!/bin/sh".to_string()
+	} else {
+		format!("#!/usr/bin/env {}", shell)
+	}
+}
+
+/// Let the owner, group and others execute a script, nothing on Windows.
+fn make_executable(path: &Path) -> io::Result<()> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+	}
+	#[cfg(not(unix))]
+	let _ = path;
+	Ok(())
+}
+
+/// Whether a script can be executed directly, always on Windows.
+fn is_executable(metadata: &fs::Metadata) -> bool {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		metadata.permissions().mode() & 0o111 != 0
+	}
+	#[cfg(not(unix))]
+	{
+		let _ = metadata;
+		true
+	}
+}
+
+/// A word of a PowerShell command, parameter names such as `-Force` as they are and anything else
+/// as a string literal.
+fn powershell_word(value: &str) -> String {
+	let parameter = value.strip_prefix('-').is_some_and(|name| {
+		name.starts_with(|c: char| c.is_ascii_alphabetic())
+			&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+	});
+	if parameter {
+		value.to_string()
+	} else {
+		powershell_string(value)
 	}
 }
 
@@ -57,24 +467,22 @@ impl From<TypeProp> for TomlTypeProp {
 pub struct TypeConfig {
 	/// Key: type name, Value: type properties
 	shell: HashMap<String, Box<[String]>>,
-	types: HashMap<String, TypeProp>,
+	types: NameMap<TypeProp>,
 }
 
 impl TypeConfig {
+	/// A configuration with the default shell, and `DEFAULT_SHELL` as a fallback when that is
+	/// PowerShell 7.
 	pub fn new() -> Self {
-		#[cfg(target_os = "windows")]
-		{
-			Self {
-				shell: HashMap::from([("powershell".into(), Box::from(["-c".into()]))]),
-				types: HashMap::new(),
-			}
-		}
-		#[cfg(not(target_os = "windows"))]
-		{
-			Self {
-				shell: HashMap::from([("bash".into(), Box::from(["-c".into()]))]),
-				types: HashMap::new(),
-			}
+		let to_args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect();
+		let (shell, args) = default_shell();
+		let (fallback, fallback_args) = DEFAULT_SHELL;
+		Self {
+			shell: HashMap::from([
+				(shell.into(), to_args(args)),
+				(fallback.into(), to_args(fallback_args)),
+			]),
+			types: NameMap::default(),
 		}
 	}
 
@@ -83,106 +491,554 @@ impl TypeConfig {
 		if !TYPES_CONFIG.exists() {
 			Ok(Self::new())
 		} else {
-			toml::from_str::<TomlTypeConfig>(&fs::read_to_string(&*TYPES_CONFIG)?)
-				.map(|c| c.into())
-				.map_err(Into::into)
+			load_toml::<TomlTypeConfig>(&TYPES_CONFIG).map(|c| c.into())
+		}
+	}
+
+	/// Open the configuration in the editor, creating it first if it doesn't exist.
+	pub fn edit() -> Result<()> {
+		if !TYPES_CONFIG.exists() {
+			Self::new().save()?;
 		}
+		edit_toml::<TomlTypeConfig>(&TYPES_CONFIG)
 	}
 
 	/// Save the configuration.
 	pub fn save(self) -> Result<()> {
-		fs::write(
-			&*TYPES_CONFIG,
-			toml::to_string(&TomlTypeConfig::from(self))?,
-		)
-		.map_err(Into::into)
+		save_toml(&TYPES_CONFIG, &TomlTypeConfig::from(self))
 	}
 
 	/// Add a new type.
-	pub fn add(&mut self, name: String, ext: String, shell: String) -> Result<()> {
+	pub fn add(
+		&mut self,
+		name: String,
+		ext: String,
+		shell: String,
+		allow_weird_names: bool,
+	) -> Result<()> {
+		validate_name(&name, allow_weird_names)?;
+		validate_ext(&ext)?;
+		if !self.types.contains_key(&name) && !self.shell.contains_key(&shell) {
+			self.offer_shell(&shell)?;
+		}
 		if let Entry::Vacant(e) = self.types.entry(name.clone()) {
 			let path = SCRIPT_ROOT.join(format!("{}.{}", name, ext));
 			if !path.exists() {
-				File::create(path)?;
+				let mut file = File::create(&path)?;
+				if cfg!(unix) {
+					writeln!(file, "{}", shebang(program(&shell)))?;
+				}
+				make_executable(&path)?;
 			}
-			add!("{}\t{}\t{}", name.bright_cyan(), ext.bright_purple(), shell);
+			HistoryEntry::new("type add")
+				.r#type(&name)
+				.args(&[&ext, &shell])
+				.reversible(&TYPES_CONFIG, ())
+				.record();
+			add!(
+				"{}\t{}\t{}",
+				name.themed(Role::Name),
+				ext.themed(Role::Type),
+				shell
+			);
 			e.insert(TypeProp::new(ext, shell));
 			Ok(())
 		} else {
-			bail!("type '{}' already exists", name.bright_yellow())
+			bail!(
+				"{} type '{}' already exists",
+				Code::TypeExists,
+				self.types.registered(&name).themed(Role::Warning)
+			)
+		}
+	}
+
+	/// Register the built-in type `name` with the default shell, writing its script unless one
+	/// exists. Returns false when no such type is built in.
+	pub fn add_builtin(&mut self, name: &str) -> Result<bool> {
+		let Some((_, posix, powershell)) = BUILTIN_TYPES.iter().find(|(n, ..)| *n == name) else {
+			return Ok(false);
+		};
+		let (shell, _) = default_shell();
+		let (ext, content) = if is_powershell(shell) {
+			("ps1", *powershell)
+		} else {
+			("sh", *posix)
+		};
+		let path = SCRIPT_ROOT.join(format!("{}.{}", name, ext));
+		if !path.exists() {
+			let mut file = File::create(&path)?;
+			if cfg!(unix) {
+				writeln!(file, "{}", shebang(program(shell)))?;
+			}
+			file.write_all(content.as_bytes())?;
+			make_executable(&path)?;
 		}
+		self.add(name.to_string(), ext.to_string(), shell.to_string(), false)?;
+		Ok(true)
+	}
+
+	/// Sorted type names.
+	pub fn names(&self) -> Vec<&str> {
+		let mut names: Vec<_> = self.types.keys().map(String::as_str).collect();
+		names.sort();
+		names
+	}
+
+	/// Registered spelling of type `name`.
+	pub fn registered(&self, name: &str) -> String {
+		self.types.registered(name)
+	}
+
+	/// Path of the script of a type, `None` if the type doesn't exist.
+	pub fn script_path(&self, name: &str) -> Option<PathBuf> {
+		let (name, prop) = self.types.get_key_value(self.types.key(name)?)?;
+		Some(prop.script(name))
+	}
+
+	/// How the script of a type runs: its path, shell, extension and the command line of a
+	/// sample package, as text or JSON. Also returns whether the script and shell exist.
+	pub fn which(&self, name: &str, json: bool) -> Result<(String, bool)> {
+		let Some((name, prop)) = self
+			.types
+			.key(name)
+			.and_then(|key| self.types.get_key_value(key))
+		else {
+			bail!(not_found("type", name, self.types.keys()));
+		};
+		let script = prop.script(name);
+		let exists = script.is_file();
+		let shell_args = self.shell.get(&prop.shell);
+		let resolved = which(program(&prop.shell));
+		// A package named `<name>` with the declared parameters as args.
+		let args: Vec<String> = if prop.params.is_empty() {
+			vec!["<args>...".to_string()]
+		} else {
+			prop.params.iter().map(Param::usage).collect()
+		};
+		let command: Option<Vec<String>> = shell_args.map(|shell_args| {
+			std::iter::once(OsString::from(program(&prop.shell)))
+				.chain(invocation(
+					&prop.shell,
+					shell_args,
+					&script,
+					&[("-n", "<name>")],
+					&args,
+				))
+				.map(|a| a.to_string_lossy().into_owned())
+				.collect()
+		});
+		let command_line = command
+			.as_ref()
+			.map(|command| command_line(Path::new("<repo>"), command));
+		let env: BTreeMap<&String, String> = prop
+			.env
+			.iter()
+			.map(|(key, value)| (key, redact::env_value(key, value)))
+			.collect();
+		if json {
+			let value = serde_json::json!({
+				"type": name,
+				"script": script,
+				"exists": exists,
+				"ext": prop.ext,
+				"shell": prop.shell,
+				"shell_args": shell_args,
+				"program": resolved,
+				"env": env,
+				"command": command,
+				"command_line": command_line,
+			});
+			return Ok((
+				serde_json::to_string_pretty(&value)? + "\n",
+				exists && command.is_some(),
+			));
+		}
+		let mut tw = TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", name.themed(Role::Type))?;
+		writeln!(
+			&mut tw,
+			"  script:\t{} {}",
+			script.display().to_string().themed(Role::Path),
+			if exists {
+				"(exists)".bright_black()
+			} else {
+				"(missing)".themed(Role::Error)
+			}
+		)?;
+		writeln!(&mut tw, "  ext:\t{}", prop.ext)?;
+		match shell_args {
+			Some(shell_args) => writeln!(
+				&mut tw,
+				"  shell:\t{} {}",
+				prop.shell.themed(Role::Name),
+				shell_args.join(" ")
+			)?,
+			None => writeln!(
+				&mut tw,
+				"  shell:\t{} {}",
+				prop.shell.themed(Role::Warning),
+				"(not registered)".themed(Role::Error)
+			)?,
+		}
+		writeln!(
+			&mut tw,
+			"  program:\t{}",
+			match &resolved {
+				Some(path) => path.display().to_string().themed(Role::Path),
+				None => format!("{} (not found on PATH)", program(&prop.shell)).themed(Role::Error),
+			}
+		)?;
+		for (key, value) in &env {
+			writeln!(&mut tw, "  env:\t{}={}", key, value)?;
+		}
+		if let Some(command_line) = &command_line {
+			writeln!(&mut tw, "  command:\t{}", command_line)?;
+		}
+		tw.flush()?;
+		Ok((
+			String::from_utf8(tw.into_inner().unwrap())?,
+			exists && command.is_some(),
+		))
+	}
+
+	/// Whether a type is registered.
+	pub fn has_type(&self, name: &str) -> bool {
+		self.types.contains_key(name)
+	}
+
+	/// Parameters declared by a type, empty if it declares none or doesn't exist.
+	pub fn params(&self, name: &str) -> &[Param] {
+		self.types.get(name).map_or(&[], |prop| &prop.params)
+	}
+
+	/// Whether scripts of a type install outside the repository, so nothing is expected there.
+	pub fn no_artifact(&self, name: &str) -> bool {
+		self.types.get(name).is_some_and(|prop| prop.no_artifact)
+	}
+
+	/// Check the number of `args` against the parameters declared by a type.
+	///
+	/// Types without declared parameters accept any arguments.
+	pub fn check_args(&self, name: &str, args: &[String]) -> Result<()> {
+		let params = self.params(name);
+		if params.is_empty() {
+			return Ok(());
+		}
+		let required = params.iter().filter(|p| p.required).count();
+		if args.len() < required {
+			bail!(
+				"{} type '{}' requires {}",
+				Code::ArgsInvalid,
+				name.themed(Role::Warning),
+				usage(&params[args.len()..required])
+			);
+		}
+		if args.len() > params.len() {
+			bail!(
+				"{} type '{}' takes at most {} argument{}, usage: {}",
+				Code::ArgsInvalid,
+				name.themed(Role::Warning),
+				params.len(),
+				if params.len() == 1 { "" } else { "s" },
+				usage(params)
+			);
+		}
+		Ok(())
+	}
+
+	/// Change the extension, shell or parameters of a type, renaming its script for a new
+	/// extension.
+	pub fn modify(&mut self, name: &str, edit: TypeEdit) -> Result<()> {
+		let TypeEdit {
+			ext,
+			shell,
+			params,
+			env,
+			unset_env,
+			no_artifact,
+		} = edit;
+		if let Some(ext) = &ext {
+			validate_ext(ext)?;
+		}
+		let name = &self.types.registered(name);
+		let Some(prop) = self.types.get_mut(name) else {
+			bail!(not_found("type", name, self.types.keys()));
+		};
+		let mut changes = vec![];
+		if let Some(params) = params {
+			if let Some(pair) = params.windows(2).find(|p| !p[0].required && p[1].required) {
+				bail!(
+					"required parameter '{}' can't follow optional '{}'",
+					pair[1].name.themed(Role::Warning),
+					pair[0].name
+				);
+			}
+			changes.push(if params.is_empty() {
+				"params cleared".to_string()
+			} else {
+				format!("params={}", usage(&params))
+			});
+			prop.params = params;
+		}
+		if let Some(shell) = shell {
+			if !self.shell.contains_key(&shell) {
+				bail!(
+					"{} shell '{}' does not exist, register it with 'gpm type shell add {} [ARGS]...'",
+					Code::ShellMissing,
+					shell.themed(Role::Warning),
+					shell
+				);
+			}
+			changes.push(format!("shell={}", shell));
+			prop.shell = shell;
+		}
+		if let Some(ext) = ext {
+			let old = prop.script(name);
+			prop.ext = ext;
+			let new = prop.script(name);
+			if old.exists() && old != new {
+				fs::rename(&old, &new)?;
+				verbose!("renamed '{}' to '{}'", old.display(), new.display());
+			}
+			changes.push(format!("ext={}", prop.ext));
+		}
+		// Only keys are recorded, values may be secrets.
+		for key in unset_env {
+			if prop.env.remove(&key).is_none() {
+				bail!(
+					"type '{}' has no environment variable '{}'",
+					name,
+					key.themed(Role::Warning)
+				);
+			}
+			changes.push(format!("env-{}", key));
+		}
+		for (key, value) in env {
+			changes.push(format!("env+{}", key));
+			prop.env.insert(key, value);
+		}
+		if let Some(no_artifact) = no_artifact {
+			changes.push(format!("no_artifact={}", no_artifact));
+			prop.no_artifact = no_artifact;
+		}
+		HistoryEntry::new("type edit")
+			.r#type(name)
+			.args(&changes)
+			.record();
+		status!(
+			"edited:".themed(Role::Success),
+			"{} {}",
+			name.themed(Role::Name),
+			changes.join(", ")
+		);
+		Ok(())
 	}
 
 	/// Remove types and delete the script files.
 	pub fn remove(&mut self, names: Vec<String>) {
 		for name in names {
+			let name = self.types.registered(&name);
 			match self.types.remove(&name) {
 				Some(r#type) => {
 					match fs::remove_file(SCRIPT_ROOT.join(&name).with_extension(&r#type.ext)) {
-						Ok(_) => remove!("{}", name.bright_cyan()),
+						Ok(_) => {
+							HistoryEntry::new("type remove").r#type(&name).record();
+							remove!("{}", name.themed(Role::Name));
+						}
 						Err(e) => {
 							error!(e);
-							match prompt("Remove from registry?") {
-								Ok(true) => remove!("{}", name.bright_cyan()),
-								Ok(false) => {}
-								Err(e) => error!(e),
+							HistoryEntry::new("type remove")
+								.r#type(&name)
+								.failed(&e)
+								.record();
+							match prompt_batch("Remove from registry?") {
+								Ok(true) => {
+									HistoryEntry::new("type remove-registry")
+										.r#type(&name)
+										.reversible(&TYPES_CONFIG, TomlTypeProp::from(r#type))
+										.record();
+									remove!("{}", name.themed(Role::Name));
+								}
+								Ok(false) => {
+									HistoryEntry::new("type remove-registry")
+										.r#type(&name)
+										.skipped()
+										.record();
+									self.types.insert(name, r#type);
+								}
+								Err(e) => {
+									error!(e);
+									self.types.insert(name, r#type);
+								}
 							}
 						}
 					}
 				}
-				None => error!("type '{}' does not exist", name.bright_yellow()),
+				None => error!(not_found("type", &name, self.types.keys())),
+			}
+			if interrupted() {
+				break;
+			}
+		}
+	}
+
+	/// Reverse a recorded `type add` or `type remove-registry`, `before` is the type before it.
+	///
+	/// The script of an added type is only deleted after a prompt.
+	pub fn undo(&mut self, entry: &HistoryEntry, before: &serde_json::Value) -> Result<()> {
+		let name = entry.r#type.as_deref().unwrap_or_default();
+		match entry.operation.as_str() {
+			"type add" => {
+				let Some(prop) = self.types.remove(name) else {
+					bail!(not_found("type", name, self.types.keys()));
+				};
+				remove!("{}", name.themed(Role::Name));
+				let script = prop.script(name);
+				if script.exists() && prompt(&format!("Delete '{}'?", script.display()))? {
+					fs::remove_file(script)?;
+				}
+			}
+			"type remove-registry" => {
+				if self.types.contains_key(name) {
+					bail!(
+						"{} type '{}' already exists",
+						Code::TypeExists,
+						self.types.registered(name).themed(Role::Warning)
+					);
+				}
+				let prop = TypeProp::from(serde_json::from_value::<TomlTypeProp>(before.clone())?);
+				add!(
+					"{}\t{}\t{}",
+					name.themed(Role::Name),
+					prop.ext.themed(Role::Type),
+					prop.shell
+				);
+				self.types.insert(name.to_string(), prop);
 			}
+			op => bail!("'{}' can't be undone", op),
 		}
+		Ok(())
 	}
 
 	/// Remove types without deleting the script files.
 	pub fn remove_registry(&mut self, names: Vec<String>) {
 		for name in names {
+			let name = self.types.registered(&name);
 			match self.types.remove(&name) {
-				Some(_) => remove!("{}", name.bright_cyan()),
-				None => error!("type '{}' does not exist", name.bright_yellow()),
+				Some(prop) => {
+					HistoryEntry::new("type remove-registry")
+						.r#type(&name)
+						.reversible(&TYPES_CONFIG, TomlTypeProp::from(prop))
+						.record();
+					remove!("{}", name.themed(Role::Name));
+				}
+				None => error!(not_found("type", &name, self.types.keys())),
 			}
 		}
 	}
 
 	/// Execute script with arguments, returning stdout.
+	///
+	/// Placeholders in `args` are substituted first, see `resolve_args`.
 	pub fn execute(
 		&self,
 		type_name: &str,
-		name: &str,
-		repo_path: &Path,
+		target: Target,
 		tag: Option<&str>,
 		cwd: Option<&str>,
 		args: &[String],
 	) -> Result<String> {
+		let Target {
+			name, repo_path, ..
+		} = target;
 		let prop = match self.types.get(type_name) {
 			Some(prop) => prop,
-			None => bail!("type '{}' does not exist", type_name.bright_yellow()),
+			None => bail!(not_found("type", type_name, self.types.keys())),
 		};
 
 		let (shell, shell_args) = match self.shell.get_key_value(&prop.shell) {
 			Some(s) => s,
-			None => bail!("shell '{}' does not exist", prop.shell.bright_yellow()),
+			None => bail!(
+				"{} shell '{}' does not exist",
+				Code::ShellMissing,
+				prop.shell.themed(Role::Warning)
+			),
 		};
-		let mut cmd = std::process::Command::new(shell);
-		cmd.current_dir(repo_path).args(shell_args.iter());
-		cmd.arg(SCRIPT_ROOT.join(type_name).with_extension(&prop.ext))
-			.arg("-n")
-			.arg(name);
-		if let Some(cwd) = cwd {
-			cmd.arg("-d").arg(cwd);
+		let script = prop.script(type_name);
+		verbose!("script: {}", script.display());
+		verbose!("shell: {} {:?}", shell, shell_args);
+		let args = resolve_args(args, &target, type_name);
+		verbose!("args: {:?}", redact::args(&args));
+		let mut flags = vec![("-n", name)];
+		flags.extend(cwd.map(|cwd| ("-d", cwd)));
+		flags.extend(tag.map(|tag| ("-t", tag)));
+		let mut cmd = std::process::Command::new(program(shell));
+		cmd.current_dir(repo_path)
+			.args(invocation(shell, shell_args, &script, &flags, &args));
+		let shown: Vec<String> = std::iter::once(cmd.get_program())
+			.chain(cmd.get_args())
+			.map(|a| a.to_string_lossy().into_owned())
+			.collect();
+		let vars = environment::vars();
+		verbose!("cwd: {}", repo_path.display());
+		for (name, value) in &vars {
+			verbose!("env: {}", env_assignment(name, value));
 		}
-		if let Some(tag) = tag {
-			cmd.arg("-t").arg(tag);
+		for (name, value) in &prop.env {
+			verbose!(
+				"env: {}",
+				env_assignment(name, &redact::env_value(name, value))
+			);
 		}
-		cmd.args(args);
-		println!("{} {:?}", "executing:".bright_blue(), cmd);
+		status!(
+			"executing:".bright_blue(),
+			"{}",
+			command_line(repo_path, &redact::args(&shown))
+		);
 
+		let start = Instant::now();
 		let output = cmd
+			.envs(vars)
+			.envs(&prop.env)
 			.stdin(Stdio::inherit())
 			.stdout(Stdio::piped())
 			.stderr(Stdio::inherit())
-			.output()?;
+			.output()
+			.map_err(|e| {
+				anyhow!(
+					"{} failed to run '{}' with '{}' {}{}",
+					Code::ScriptFailed,
+					script.display().to_string().themed(Role::Warning),
+					shell,
+					e,
+					if e.kind() == io::ErrorKind::NotFound {
+						", run 'gpm type shell detect' to register the installed shells"
+					} else {
+						""
+					}
+				)
+			})?;
+		let elapsed = start.elapsed();
+		tracing::info!(
+			package = name,
+			r#type = type_name,
+			script = %script.display(),
+			status = %output.status,
+			duration = ?elapsed,
+			"script executed"
+		);
+		verbose!("{} exited with {} in {:.1?}", name, output.status, elapsed);
+		if offline() && output.status.code() == Some(EXIT_OFFLINE) {
+			return Err(OfflineError(format!("the script of type '{}'", type_name)).into());
+		}
+		if !output.status.success() {
+			bail!(
+				"{} the script of type '{}' exited with {}",
+				Code::ScriptFailed,
+				type_name.themed(Role::Warning),
+				output.status
+			);
+		}
 		if output.stdout.is_empty() {
 			Ok("".to_string())
 		} else {
@@ -204,50 +1060,416 @@ impl From<TomlTypeConfig> for TypeConfig {
 	}
 }
 
-impl fmt::Display for TypeConfig {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl TypeConfig {
+	/// Render shells and types, `long` adds the usage of the parameters of each type.
+	/// Shells grouped by program, each variant with its arguments and the types using it.
+	pub fn shell_list(&self) -> String {
+		let mut programs: BTreeMap<&str, Vec<(&String, &[String])>> = BTreeMap::new();
+		for (name, args) in &self.shell {
+			programs
+				.entry(program(name))
+				.or_default()
+				.push((name, args));
+		}
+		let mut tw = TabWriter::new(vec![]);
+		for (program, mut variants) in programs {
+			variants.sort();
+			writeln!(&mut tw, "{}", program.themed(Role::Success)).unwrap();
+			for (name, args) in variants {
+				let mut types: Vec<&str> = self
+					.types
+					.iter()
+					.filter(|(_, prop)| prop.shell == *name)
+					.map(|(t, _)| t.as_str())
+					.collect();
+				types.sort_unstable();
+				writeln!(
+					&mut tw,
+					"  {}\t{}\t{}",
+					name.themed(Role::Name),
+					args.join(" ").themed(Role::Type),
+					types.join(", ").bright_black()
+				)
+				.unwrap();
+			}
+		}
+		tw.flush().unwrap();
+		String::from_utf8(tw.into_inner().unwrap()).unwrap()
+	}
+
+	pub fn list(&self, long: bool) -> String {
 		let mut tw = TabWriter::new(vec![]);
-		writeln!(&mut tw, "{}", "Shell:".bright_green()).unwrap();
+		writeln!(&mut tw, "{}", "Shell:".themed(Role::Success)).unwrap();
 		let btree_map: BTreeMap<_, _> = self.shell.iter().collect();
 		for (name, args) in &btree_map {
 			writeln!(
 				&mut tw,
-				"  {}\t{}",
-				name.bright_cyan(),
-				args.join(" ").bright_purple()
+				"  {}\t{}{}",
+				name.themed(Role::Name),
+				args.join(" ").themed(Role::Type),
+				if legacy_powershell(name, args) {
+					"\t(loads the profile, 'gpm init' upgrades it)".bright_black()
+				} else {
+					"".normal()
+				}
 			)
 			.unwrap();
 		}
 
-		writeln!(&mut tw, "{}", "Types:".bright_green()).unwrap();
-		for (name, prop) in &self.types {
-			writeln!(
+		writeln!(&mut tw, "{}", "Types:".themed(Role::Success)).unwrap();
+		let btree_map: BTreeMap<_, _> = self.types.iter().collect();
+		for (name, prop) in &btree_map {
+			write!(
 				&mut tw,
 				"  {}\t{}\t{}",
-				name.bright_cyan(),
-				prop.ext.bright_purple(),
+				name.themed(Role::Name),
+				prop.ext.themed(Role::Type),
 				prop.shell,
 			)
 			.unwrap();
+			if long {
+				let env: Vec<String> = prop
+					.env
+					.iter()
+					.map(|(key, value)| format!("{}={}", key, redact::env_value(key, value)))
+					.collect();
+				write!(
+					&mut tw,
+					"\t{}\t{}",
+					usage(&prop.params),
+					env.join(" ").bright_black()
+				)
+				.unwrap();
+			}
+			writeln!(&mut tw).unwrap();
 		}
 		tw.flush().unwrap();
-		write!(
-			f,
-			"{}",
-			String::from_utf8(tw.into_inner().unwrap()).unwrap()
+		String::from_utf8(tw.into_inner().unwrap()).unwrap()
+	}
+}
+
+impl TypeConfig {
+	/// Shells, and types with the content of their scripts, for `export`.
+	pub(super) fn export(
+		&self,
+	) -> (
+		BTreeMap<String, Vec<String>>,
+		BTreeMap<String, export::Type>,
+	) {
+		let shell = self
+			.shell
+			.iter()
+			.map(|(name, args)| (name.clone(), args.to_vec()))
+			.collect();
+		let types = self
+			.types
+			.iter()
+			.map(|(name, prop)| {
+				let r#type = export::Type {
+					ext: prop.ext.clone(),
+					shell: prop.shell.clone(),
+					params: prop.params.clone(),
+					env: prop.env.clone(),
+					no_artifact: prop.no_artifact,
+					script: fs::read_to_string(prop.script(name)).ok(),
+				};
+				(name.clone(), r#type)
+			})
+			.collect();
+		(shell, types)
+	}
+
+	/// Offer to replace the bare `-c` of PowerShell shells with `POWERSHELL_ARGS`, returning
+	/// whether any was replaced.
+	pub fn upgrade_powershell(&mut self) -> Result<bool> {
+		let mut legacy: Vec<&String> = self
+			.shell
+			.iter()
+			.filter(|(name, args)| legacy_powershell(name, args))
+			.map(|(name, _)| name)
+			.collect();
+		if legacy.is_empty() {
+			return Ok(false);
+		}
+		legacy.sort_unstable();
+		let names: Vec<String> = legacy.into_iter().cloned().collect();
+		// `init` runs from scripts too, which shouldn't fail on this question.
+		if !io::stdin().is_terminal() && ASSUME.get().copied().flatten().is_none() {
+			note!(
+				"shell {} loads the PowerShell profile, run 'gpm init --yes' to upgrade",
+				names.join(", ").themed(Role::Warning)
+			);
+			return Ok(false);
+		}
+		let message = format!(
+			"Run {} with '{}' instead of '-c', skipping the profile and the execution policy?",
+			names.join(", "),
+			POWERSHELL_ARGS.join(" ")
+		);
+		if !prompt(&message)? {
+			return Ok(false);
+		}
+		for name in names {
+			self.shell.insert(
+				name.clone(),
+				POWERSHELL_ARGS.iter().map(|a| a.to_string()).collect(),
+			);
+			note!("shell '{}' upgraded", name.themed(Role::Name));
+		}
+		Ok(true)
+	}
+
+	/// Register shell `name` invoked with `args`.
+	pub fn add_shell(&mut self, name: String, args: Vec<String>) -> Result<()> {
+		if self.shell.contains_key(&name) {
+			bail!("shell '{}' already exists", name.themed(Role::Warning));
+		}
+		add!(
+			"{}\t{}",
+			name.themed(Role::Name),
+			args.join(" ").themed(Role::Type)
+		);
+		self.shell.insert(name, args.into_boxed_slice());
+		Ok(())
+	}
+
+	/// Ask for the arguments of an unregistered shell and register it, failing when not prompting
+	/// or declined.
+	fn offer_shell(&mut self, shell: &str) -> Result<()> {
+		let missing = || {
+			anyhow!(
+				"{} shell '{}' does not exist, register it with 'gpm type shell add {} [ARGS]...'",
+				Code::ShellMissing,
+				shell.themed(Role::Warning),
+				shell
+			)
+		};
+		if !io::stdin().is_terminal() {
+			return Err(missing());
+		}
+		if !prompt(&format!(
+			"Shell '{}' isn't registered, register it?",
+			shell.themed(Role::Warning)
+		))? {
+			return Err(missing());
+		}
+		let args = read_input(&format!(
+			"Arguments of '{}' before the script, space separated, e.g. '-c'",
+			shell
+		))?;
+		self.add_shell(
+			shell.to_string(),
+			args.split_whitespace().map(String::from).collect(),
 		)
 	}
+
+	/// Check the scripts of the types, returning a description of each problem.
+	pub fn check(&self) -> Vec<String> {
+		let mut types: Vec<_> = self.types.iter().collect();
+		types.sort_by_key(|(name, _)| *name);
+		types
+			.into_iter()
+			.filter_map(|(name, prop)| {
+				let script = prop.script(name);
+				let problem = match fs::metadata(&script) {
+					Err(_) => "is missing",
+					Ok(metadata) if !is_executable(&metadata) => {
+						"is not executable, run 'chmod +x' on it"
+					}
+					Ok(_) => return None,
+				};
+				Some(format!(
+					"script '{}' of type '{}' {}",
+					script.display().to_string().themed(Role::Path),
+					name.themed(Role::Warning),
+					problem
+				))
+			})
+			.collect()
+	}
+
+	/// Look for the known shells in `PATH` and offer to register the ones found but missing,
+	/// returning whether any was registered.
+	pub fn detect(&mut self) -> Result<bool> {
+		let mut tw = TabWriter::new(vec![]);
+		writeln!(&mut tw, "{}", "Shells:".themed(Role::Success))?;
+		let mut missing = vec![];
+		for (name, args) in KNOWN_SHELLS {
+			let path = which(name);
+			let status = match (&path, self.shell.contains_key(name)) {
+				(Some(_), true) => "registered".themed(Role::Success),
+				(Some(_), false) => {
+					missing.push((name, args));
+					"not registered".themed(Role::Warning)
+				}
+				(None, true) => "registered, not found".themed(Role::Error),
+				(None, false) => "not found".bright_black(),
+			};
+			writeln!(
+				&mut tw,
+				"  {}\t{}\t{}",
+				name.themed(Role::Name),
+				status,
+				path.map(|p| p.display().to_string())
+					.unwrap_or_default()
+					.themed(Role::Path)
+			)?;
+		}
+		tw.flush()?;
+		print!("{}", String::from_utf8(tw.into_inner().unwrap())?);
+		if missing.is_empty() {
+			return Ok(false);
+		}
+		let names: Vec<&str> = missing.iter().map(|(name, _)| *name).collect();
+		if !prompt(&format!("Register {}?", names.join(", ")))? {
+			return Ok(false);
+		}
+		for (name, args) in missing {
+			self.shell
+				.insert(name.into(), args.iter().map(|a| a.to_string()).collect());
+			add!(
+				"{}\t{}",
+				name.themed(Role::Name),
+				args.join(" ").themed(Role::Type)
+			);
+		}
+		Ok(true)
+	}
+
+	/// Whether shell `name` is registered with `args`.
+	pub(super) fn has_shell(&self, name: &str, args: &[String]) -> bool {
+		self.shell.get(name).is_some_and(|a| **a == *args)
+	}
+
+	/// Register an exported shell, `Ok(false)` when skipped as existing.
+	pub(super) fn import_shell(
+		&mut self,
+		name: &str,
+		args: Vec<String>,
+		conflict: Conflict,
+	) -> Result<bool> {
+		let args: Box<[String]> = args.into();
+		if self.shell.contains_key(name) && !conflict.replace("shell", name)? {
+			return Ok(false);
+		}
+		add!("{}\t{}", name.themed(Role::Name), args.join(" "));
+		self.shell.insert(name.to_string(), args);
+		Ok(true)
+	}
+
+	/// Register an exported type and write its script, `Ok(false)` when skipped as existing.
+	///
+	/// A type exported without its script asks for the path of one.
+	pub(super) fn import(
+		&mut self,
+		name: &str,
+		r#type: export::Type,
+		conflict: Conflict,
+	) -> Result<bool> {
+		validate_name(name, true)?;
+		let existing = self.types.key(name).cloned();
+		if let Some(existing) = &existing {
+			if !conflict.replace("type", existing)? {
+				return Ok(false);
+			}
+		}
+		let script = match r#type.script {
+			Some(script) => script,
+			None => {
+				let path = read_input(&format!(
+					"Script of type '{}' is not in the export, path to one",
+					name.themed(Role::Name)
+				))?;
+				fs::read_to_string(path.trim()).map_err(|e| {
+					anyhow!(
+						"failed to read '{}' {}",
+						path.trim().themed(Role::Warning),
+						e
+					)
+				})?
+			}
+		};
+		if let Some(existing) = existing {
+			let old = self.types.remove(&existing).unwrap();
+			if old.ext != r#type.ext {
+				let _ = fs::remove_file(old.script(&existing));
+			}
+		}
+		let prop = TypeProp {
+			ext: r#type.ext,
+			shell: r#type.shell,
+			params: r#type.params,
+			env: r#type.env,
+			no_artifact: r#type.no_artifact,
+		};
+		create_dir_all(&SCRIPT_ROOT)?;
+		let path = prop.script(name);
+		fs::write(&path, script)?;
+		make_executable(&path)?;
+		add!(
+			"{}\t{}\t{}",
+			name.themed(Role::Name),
+			prop.ext.themed(Role::Type),
+			prop.shell
+		);
+		self.types.insert(name.to_string(), prop);
+		Ok(true)
+	}
+
+	/// List types as CSV or TSV, with their parameters.
+	pub fn table(&self, format: TableFormat) -> String {
+		format.table(&["name", "ext", "shell", "params"], &self.table_rows())
+	}
+
+	/// List types in the columns of `table`.
+	pub fn porcelain(&self, porcelain: Porcelain) -> String {
+		porcelain.render(&self.table_rows())
+	}
+
+	fn table_rows(&self) -> Vec<Vec<String>> {
+		let btree_map: BTreeMap<_, _> = self.types.iter().collect();
+		btree_map
+			.into_iter()
+			.map(|(name, prop)| {
+				vec![
+					name.clone(),
+					prop.ext.clone(),
+					prop.shell.clone(),
+					usage(&prop.params),
+				]
+			})
+			.collect()
+	}
+}
+
+impl fmt::Display for TypeConfig {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.list(false))
+	}
 }
 
 #[derive(Debug)]
 pub struct TypeProp {
 	ext: String,
 	shell: String,
+	params: Vec<Param>,
+	env: BTreeMap<String, String>,
+	no_artifact: bool,
 }
 
 impl TypeProp {
 	pub fn new(ext: String, shell: String) -> Self {
-		Self { ext, shell }
+		Self {
+			ext,
+			shell,
+			params: vec![],
+			env: BTreeMap::new(),
+			no_artifact: false,
+		}
+	}
+
+	fn script(&self, name: &str) -> PathBuf {
+		SCRIPT_ROOT.join(name).with_extension(&self.ext)
 	}
 }
 
@@ -256,6 +1478,82 @@ impl From<TomlTypeProp> for TypeProp {
 		Self {
 			ext: prop.ext,
 			shell: prop.shell,
+			params: prop.params,
+			env: prop.env,
+			no_artifact: prop.no_artifact,
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn placeholders_are_substituted() {
+		let values = [("name", "rg"), ("repo", "tools")];
+		assert_eq!(substitute("{name}-{repo}.zip", &values), "rg-tools.zip");
+		assert_eq!(substitute("{{name}} {name}", &values), "{name} rg");
+		assert_eq!(
+			substitute(r"\d{3} {unknown} {", &values),
+			r"\d{3} {unknown} {"
+		);
+		assert_eq!(substitute("}}{name", &values), "}{name");
+	}
+
+	#[test]
+	fn powershell_invocation() {
+		let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+		assert!(powershell_command("powershell", &args(POWERSHELL_ARGS)));
+		assert!(powershell_command("/opt/bin/PWSH.exe", &args(&["-c"])));
+		assert!(!powershell_command("pwsh", &args(&["-File"])));
+		assert!(!powershell_command("bash", &args(&["-c"])));
+		assert!(legacy_powershell("powershell", &args(&["-c"])));
+		assert!(!legacy_powershell("powershell", &args(POWERSHELL_ARGS)));
+		assert!(powershell_command(
+			"powershell:profile",
+			&args(&["-Command"])
+		));
+		assert_eq!(program("powershell:profile"), "powershell");
+		assert_eq!(program(r"C:\pwsh\pwsh.exe"), r"C:\pwsh\pwsh.exe");
+		assert_eq!(program(r"C:\pwsh\pwsh.exe:profile"), r"C:\pwsh\pwsh.exe");
+		assert_eq!(program("bash"), "bash");
+		assert_eq!(powershell_word("-Force"), "-Force");
+		assert_eq!(powershell_word("a b's"), "'a b''s'");
+		assert_eq!(powershell_word("-"), "'-'");
+		assert_eq!(powershell_word("$env:x"), "'$env:x'");
+	}
+
+	#[test]
+	fn command_invocation() {
+		let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+		let script = Path::new("/gpm/scripts/a b.sh");
+		let flags = [("-n", "rg")];
+		let words = args(&["it's", "$HOME", "a&b", "x\\y"]);
+		assert_eq!(
+			invocation("bash", &args(&["-c"]), script, &flags, &words),
+			[
+				"-c",
+				r"'/gpm/scripts/a b.sh' -n rg 'it'\''s' '$HOME' 'a&b' 'x\y'"
+			]
+		);
+		assert_eq!(
+			invocation("fish", &args(&["-c"]), script, &flags, &words)[1],
+			r"'/gpm/scripts/a b.sh' '-n' 'rg' 'it\'s' '$HOME' 'a&b' 'x\\y'"
+		);
+		assert_eq!(
+			invocation("sh", &[], script, &flags, &words),
+			[
+				"/gpm/scripts/a b.sh",
+				"-n",
+				"rg",
+				"it's",
+				"$HOME",
+				"a&b",
+				"x\\y"
+			]
+		);
+		assert!(command_quote("bash", &args(&["-l"])).is_none());
+		assert!(command_quote("nu", &args(&["-c"])).is_none());
+	}
+}
@@ -1,14 +1,36 @@
 //! Shared utilities for configuration handling.
 
-use anyhow::Result;
-use serde::{Serialize, Serializer};
+use super::backup;
+use super::code::Code;
+use crate::checksum;
+use crate::theme::{Role, Themed};
+use crate::{error, note, plain, redact, GPM_CONFIG, HISTORY_FILE, LENIENT, OLD_HISTORY_FILE};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use clap::ValueEnum;
+use colored::Colorize;
+use once_cell::sync::OnceCell;
+use path_clean::PathClean;
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
 use std::collections::{BTreeMap, HashMap};
-use std::io::{self, Write};
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::Duration;
+use std::{env, fs, thread};
 
 #[macro_export]
 macro_rules! tabwriter {
     ($fmt:expr, $($arg:tt)*) => {
-        {
+        if $crate::plain() {
+            println!("{}", format!($fmt, $($arg)*).replace('\t', "  "));
+        } else {
             let mut tw = tabwriter::TabWriter::new(vec![]);
             write!(&mut tw, $fmt, $($arg)*).expect("Failed to write to TabWriter");
             tw.flush().expect("Failed to flush TabWriter");
@@ -17,13 +39,18 @@ macro_rules! tabwriter {
     };
 }
 
+/// Print a message prefixed with a colored symbol, already picked for plain mode, unless quiet.
 #[macro_export]
 macro_rules! print_message {
-    ($symbol:expr, $color:ident, $msg:expr) => {
-        $crate::tabwriter!("{} {}", $symbol.$color().bold(), $msg)
+    ($symbol:expr, $msg:expr) => {
+        if $crate::verbosity() >= $crate::Verbosity::Normal {
+            $crate::tabwriter!("{} {}", $symbol.bold(), $msg)
+        }
     };
-    ($symbol:expr, $color:ident, $fmt:expr, $($arg:tt)*) => {
-        $crate::tabwriter!("{} {}", $symbol.$color().bold(), format!($fmt, $($arg)*))
+    ($symbol:expr, $fmt:expr, $($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Normal {
+            $crate::tabwriter!("{} {}", $symbol.bold(), format!($fmt, $($arg)*))
+        }
     };
 }
 
@@ -31,7 +58,7 @@ macro_rules! print_message {
 #[macro_export]
 macro_rules! add {
     ($($arg:tt)*) => {
-        $crate::print_message!("+", bright_green, $($arg)*)
+        $crate::print_message!($crate::theme::Themed::themed($crate::label("+", "[add]"), $crate::theme::Role::Success), $($arg)*)
     };
 }
 
@@ -39,7 +66,15 @@ macro_rules! add {
 #[macro_export]
 macro_rules! clone {
     ($($arg:tt)*) => {
-        $crate::print_message!("=", bright_blue, $($arg)*)
+        $crate::print_message!($crate::label("=", "[clone]").bright_blue(), $($arg)*)
+    };
+}
+
+/// print message for an item that changed, such as a package whose tag moved.
+#[macro_export]
+macro_rules! change {
+    ($($arg:tt)*) => {
+        $crate::print_message!($crate::theme::Themed::themed($crate::label("~", "[change]"), $crate::theme::Role::Warning), $($arg)*)
     };
 }
 
@@ -47,10 +82,648 @@ macro_rules! clone {
 #[macro_export]
 macro_rules! remove {
     ($($arg:tt)*) => {
-        $crate::print_message!("-", bright_red, $($arg)*)
+        $crate::print_message!($crate::theme::Themed::themed($crate::label("-", "[remove]"), $crate::theme::Role::Error), $($arg)*)
     };
 }
 
+/// Device names reserved by Windows, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+	"CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+	"COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Validate a name used as a path component.
+///
+/// Names may only contain ASCII letters, digits, `.`, `-`, `_` and `+`, unless `allow_weird` is set,
+/// which additionally allows other printable characters such as spaces and unicode.
+pub fn validate_name(name: &str, allow_weird: bool) -> Result<()> {
+	let invalid = |reason: &str| {
+		bail!(
+			"{} invalid name '{}': {}, names may only contain ASCII letters, digits, '.', '-', '_' and '+'",
+			Code::NameInvalid,
+			name.themed(Role::Warning),
+			reason
+		)
+	};
+	if name.is_empty() {
+		return invalid("name is empty");
+	}
+	if name == "." || name == ".." || name.contains("..") {
+		return invalid("'..' is not allowed");
+	}
+	if name.trim() != name {
+		return invalid("leading or trailing whitespace");
+	}
+	if name.chars().any(|c| c.is_control()) {
+		return invalid("control characters are not allowed");
+	}
+	if name.chars().any(|c| matches!(c, '/' | '\\')) {
+		return invalid("path separators are not allowed");
+	}
+	if name
+		.chars()
+		.any(|c| matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*'))
+	{
+		return invalid("characters '<>:\"|?*' are not allowed");
+	}
+	let stem = name.split('.').next().unwrap_or_default();
+	if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+		return invalid("reserved device name on Windows");
+	}
+	if name.ends_with('.') {
+		return invalid("trailing '.' is not allowed on Windows");
+	}
+	if !allow_weird
+		&& !name
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'))
+	{
+		return invalid("spaces and unicode require '--allow-weird-names'");
+	}
+	Ok(())
+}
+
+/// Levenshtein edit distance between two strings.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut prev: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut curr = vec![i + 1; b.len() + 1];
+		for (j, cb) in b.iter().enumerate() {
+			curr[j + 1] = (prev[j] + usize::from(ca != *cb))
+				.min(prev[j + 1] + 1)
+				.min(curr[j] + 1);
+		}
+		prev = curr;
+	}
+	prev[b.len()]
+}
+
+/// Lowercase with `-` and `_` unified, so they don't count as typos.
+fn normalize_name(name: &str) -> String {
+	name.to_lowercase().replace('_', "-")
+}
+
+/// Up to three candidates closest to `name`, nearest first, only those reasonably close.
+pub fn closest<S: AsRef<str>>(name: &str, candidates: impl IntoIterator<Item = S>) -> Vec<String> {
+	let normalized = normalize_name(name);
+	let mut matches: Vec<(usize, String)> = candidates
+		.into_iter()
+		.map(|c| c.as_ref().to_string())
+		.filter(|c| c != name)
+		.map(|c| (edit_distance(&normalized, &normalize_name(&c)), c))
+		.filter(|(d, c)| *d <= name.chars().count().max(c.chars().count()) / 2)
+		.collect();
+	matches.sort();
+	matches.into_iter().take(3).map(|(_, c)| c).collect()
+}
+
+/// Suggestion for an unknown name, e.g. `, did you mean 'foo'?`, empty when nothing is close.
+pub fn did_you_mean<S: AsRef<str>>(name: &str, candidates: impl IntoIterator<Item = S>) -> String {
+	let quoted: Vec<String> = closest(name, candidates)
+		.iter()
+		.map(|c| format!("'{}'", c.themed(Role::Name)))
+		.collect();
+	match quoted.as_slice() {
+		[] => String::new(),
+		[only] => format!(", did you mean {}?", only),
+		[init @ .., last] => format!(", did you mean {} or {}?", init.join(", "), last),
+	}
+}
+
+/// Error message for an unknown `kind` of item, with suggestions from `candidates`.
+pub fn not_found<S: AsRef<str>>(
+	kind: &str,
+	name: &str,
+	candidates: impl IntoIterator<Item = S>,
+) -> String {
+	let code = match kind {
+		"repository" => Some(Code::RepoNotFound),
+		"package" => Some(Code::PackageNotFound),
+		"group" => Some(Code::GroupNotFound),
+		"type" => Some(Code::TypeMissing),
+		_ => None,
+	};
+	format!(
+		"{}{} '{}' does not exist{}",
+		code.map(|c| format!("{} ", c)).unwrap_or_default(),
+		kind,
+		name.themed(Role::Warning),
+		did_you_mean(name, candidates)
+	)
+}
+
+/// Expand a leading `~` to the home directory, `~user` is rejected.
+pub fn expand_tilde(path: &Path) -> Result<PathBuf> {
+	let mut components = path.components();
+	let first = match components.next() {
+		Some(Component::Normal(first)) => first.to_string_lossy(),
+		_ => return Ok(path.to_path_buf()),
+	};
+	let Some(user) = first.strip_prefix('~') else {
+		return Ok(path.to_path_buf());
+	};
+	if !user.is_empty() {
+		bail!(
+			"'~{}' is not supported, use the full path to the home directory of '{}'",
+			user,
+			user.themed(Role::Warning)
+		);
+	}
+	let home = dirs::home_dir().context("failed to resolve the home directory")?;
+	Ok(home.join(components.as_path()))
+}
+
+/// `path` as `~/...` when it's inside the home directory, so it stays valid on machines and
+/// accounts with another home.
+pub fn contract_tilde(path: &Path) -> PathBuf {
+	let Some(relative) = dirs::home_dir().and_then(|home| path.strip_prefix(home).ok()) else {
+		return path.to_path_buf();
+	};
+	let rest: Vec<_> = relative
+		.components()
+		.map(|c| c.as_os_str().to_string_lossy())
+		.collect();
+	if rest.is_empty() {
+		PathBuf::from("~")
+	} else {
+		PathBuf::from(format!("~/{}", rest.join("/")))
+	}
+}
+
+/// Directory to pass to a script, `path` resolved like `resolve_path` or the current directory.
+///
+/// Fails unless the directory exists.
+pub fn resolve_cwd(path: Option<&Path>) -> Result<PathBuf> {
+	let Some(path) = path else {
+		return Ok(env::current_dir()?);
+	};
+	let resolved = resolve_path(path)?;
+	if !resolved.is_dir() {
+		bail!(
+			"directory '{}' does not exist",
+			resolved.display().to_string().themed(Role::Warning)
+		);
+	}
+	Ok(resolved)
+}
+
+/// Resolve a user supplied path to an absolute, cleaned path.
+///
+/// Expands `~`, resolves relative paths against the current directory and canonicalizes existing paths.
+/// Fails if the path can't be created because an ancestor is not a directory.
+pub fn resolve_path(path: &Path) -> Result<PathBuf> {
+	let path = env::current_dir()?.join(expand_tilde(path)?).clean();
+	if path.exists() {
+		if !path.is_dir() {
+			bail!(
+				"'{}' is not a directory",
+				path.display().to_string().themed(Role::Warning)
+			);
+		}
+		return Ok(dunce::canonicalize(&path)?);
+	}
+	match path.ancestors().skip(1).find(|a| a.exists()) {
+		Some(ancestor) if !ancestor.is_dir() => bail!(
+			"cannot create '{}', '{}' is not a directory",
+			path.display().to_string().themed(Role::Warning),
+			ancestor.display()
+		),
+		Some(_) => Ok(path),
+		None => bail!(
+			"cannot create '{}', no existing parent directory",
+			path.display().to_string().themed(Role::Warning)
+		),
+	}
+}
+
+/// Create a directory and its missing parents, naming the directory that failed.
+///
+/// Creation starts below the deepest existing ancestor and never touches a root, so the root of a
+/// network share such as `\\server\share` isn't created again, which fails on Windows.
+pub fn create_dir_all(path: &Path) -> Result<()> {
+	let missing: Vec<&Path> = path
+		.ancestors()
+		.take_while(|dir| !dir.as_os_str().is_empty() && !dir.is_dir())
+		.filter(|dir| dir.parent().is_some())
+		.collect();
+	for dir in missing.into_iter().rev() {
+		match fs::create_dir(dir) {
+			Ok(()) => {}
+			Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => {}
+			Err(e) => bail!(
+				"failed to create '{}' {}",
+				dir.display().to_string().themed(Role::Warning),
+				e
+			),
+		}
+	}
+	Ok(())
+}
+
+/// Whether the file system of `dir` ignores case, probed with a temporary file.
+///
+/// Falls back to the usual behavior of the platform when `dir` isn't writable.
+pub fn ignores_case(dir: &Path) -> bool {
+	let probe = dir.join(format!(".gpm-tmp-case-{}", std::process::id()));
+	if fs::write(&probe, "").is_err() {
+		return cfg!(any(windows, target_os = "macos"));
+	}
+	let ignores = dir
+		.join(format!(".GPM-TMP-CASE-{}", std::process::id()))
+		.exists();
+	let _ = fs::remove_file(probe);
+	ignores
+}
+
+/// Recursively copy a directory.
+pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
+	copy_dir_with(src.as_ref(), dst.as_ref(), &|from, to| {
+		fs::copy(from, to).map(drop).map_err(|e| {
+			anyhow!(
+				"failed to copy '{}' {}",
+				to.display().to_string().themed(Role::Warning),
+				e
+			)
+		})
+	})
+}
+
+/// Recursively copy a directory, copying each file with `copy_file`.
+pub fn copy_dir_with(
+	src: &Path,
+	dst: &Path,
+	copy_file: &dyn Fn(&Path, &Path) -> Result<()>,
+) -> Result<()> {
+	create_dir_all(dst)?;
+	let entries = fs::read_dir(src).map_err(|e| {
+		anyhow!(
+			"failed to read '{}' {}",
+			src.display().to_string().themed(Role::Warning),
+			e
+		)
+	})?;
+	for entry in entries {
+		let entry = entry?;
+		let ty = entry.file_type()?;
+		let target = dst.join(entry.file_name());
+		if ty.is_dir() {
+			copy_dir_with(&entry.path(), &target, copy_file)?;
+		} else {
+			copy_file(&entry.path(), &target)?;
+		}
+	}
+	Ok(())
+}
+
+/// Table of config.toml of the selected profile, for settings needed before it's loaded.
+///
+/// `None` if it doesn't parse, in which case loading the configuration reports the error.
+pub fn raw_config() -> Option<toml::Table> {
+	fs::read_to_string(&*GPM_CONFIG).ok()?.parse().ok()
+}
+
+/// Read and parse a TOML file.
+///
+/// Errors tell apart unreadable and unparsable files, the latter including the line, column and
+/// an excerpt of the offending line.
+///
+/// Unknown fields are rejected naming the closest valid field, unless lenient mode is enabled,
+/// in which case they are ignored with a notice.
+pub fn load_toml<T: DeserializeOwned>(path: &Path) -> Result<T> {
+	let mut content = fs::read_to_string(path).map_err(|e| {
+		anyhow!(
+			"failed to read '{}' {}",
+			path.display().to_string().themed(Role::Warning),
+			e
+		)
+	})?;
+	loop {
+		let e = match toml::from_str(&content) {
+			Ok(value) => return Ok(value),
+			Err(e) => e,
+		};
+		let Some((field, expected)) = unknown_field(e.message()) else {
+			return Err(parse_error(path, &content, &e).into());
+		};
+		if LENIENT.get().copied().unwrap_or_default() {
+			if let Some(stripped) = e.span().and_then(|s| remove_key_at(&content, s.start)) {
+				note!(
+					"ignoring unknown field '{}' in '{}'",
+					field.themed(Role::Warning),
+					path.display()
+				);
+				content = stripped;
+				continue;
+			}
+		}
+		let ConfigError(mut msg) = parse_error(path, &content, &e);
+		if let Some(suggestion) = did_you_mean(&field, &expected).strip_prefix(", ") {
+			msg.push_str(&format!("\n  {}", suggestion));
+		}
+		msg.push_str("\n  pass '--lenient' to ignore unknown fields");
+		return Err(ConfigError(msg).into());
+	}
+}
+
+/// Open `path` in the user's editor, then re-parse it, offering to re-open it while it doesn't parse.
+pub fn edit_toml<T: DeserializeOwned>(path: &Path) -> Result<()> {
+	let editor = env::var("VISUAL")
+		.or_else(|_| env::var("EDITOR"))
+		.unwrap_or_else(|_| {
+			if cfg!(windows) {
+				"notepad".into()
+			} else {
+				"vi".into()
+			}
+		});
+	let mut parts = editor.split_whitespace();
+	let Some(program) = parts.next() else {
+		bail!("editor is empty, set the EDITOR environment variable");
+	};
+	let args: Vec<_> = parts.collect();
+	loop {
+		let status = Command::new(program)
+			.args(&args)
+			.arg(path)
+			.status()
+			.with_context(|| format!("failed to launch editor '{}'", editor))?;
+		if !status.success() {
+			bail!("editor '{}' exited with {}", editor, status);
+		}
+		match load_toml::<T>(path) {
+			Ok(_) => return Ok(()),
+			Err(e) => {
+				error!(e);
+				if !prompt("Re-open the editor?")? {
+					bail!("'{}' does not parse", path.display());
+				}
+			}
+		}
+	}
+}
+
+/// Open a directory or file in the platform file manager.
+pub fn open_in_file_manager(path: &Path) -> Result<()> {
+	let program = if cfg!(windows) {
+		"explorer"
+	} else if cfg!(target_os = "macos") {
+		"open"
+	} else {
+		"xdg-open"
+	};
+	let status = Command::new(program)
+		.arg(path)
+		.status()
+		.with_context(|| format!("failed to launch '{}', pass '--print' instead", program))?;
+	// explorer exits with 1 even when it succeeds.
+	if !status.success() && !cfg!(windows) {
+		bail!("'{}' exited with {}", program, status);
+	}
+	Ok(())
+}
+
+/// Serialize and write a TOML file.
+///
+/// If the file already exists, only changed, added and removed keys are applied to it so comments,
+/// key order and formatting are preserved.
+pub fn save_toml<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+	let generated = toml::to_string(value)?;
+	let content = match fs::read_to_string(path)
+		.ok()
+		.and_then(|c| c.parse::<toml_edit::DocumentMut>().ok())
+	{
+		Some(mut doc) => {
+			let new = generated.parse::<toml_edit::DocumentMut>()?;
+			merge_table(doc.as_table_mut(), new.as_table());
+			doc.to_string()
+		}
+		None => generated,
+	};
+	backup::backup(path, &content)?;
+	fs::write(path, content).map_err(|e| {
+		anyhow!(
+			"failed to write '{}' {}",
+			path.display().to_string().themed(Role::Warning),
+			e
+		)
+	})?;
+	tracing::info!(path = %path.display(), "config saved");
+	record_saved(path);
+	Ok(())
+}
+
+/// Apply the differences between `old` and `new` to `old`, keeping formatting of unchanged items.
+fn merge_table(old: &mut dyn toml_edit::TableLike, new: &dyn toml_edit::TableLike) {
+	let removed: Vec<String> = old
+		.iter()
+		.map(|(key, _)| key.to_string())
+		.filter(|key| !new.contains_key(key))
+		.collect();
+	for key in removed {
+		old.remove(&key);
+	}
+	for (key, new_item) in new.iter() {
+		match old.get_mut(key) {
+			Some(old_item) => {
+				if let (Some(old_table), Some(new_table)) =
+					(old_item.as_table_like_mut(), new_item.as_table_like())
+				{
+					merge_table(old_table, new_table);
+				} else if !same_value(old_item, new_item) {
+					let decor = old_item.as_value().map(|v| v.decor().clone());
+					*old_item = new_item.clone();
+					if let (Some(value), Some(decor)) = (old_item.as_value_mut(), decor) {
+						*value.decor_mut() = decor;
+					}
+				}
+			}
+			None => {
+				old.insert(key, new_item.clone());
+			}
+		}
+	}
+}
+
+/// Compare two items by value, ignoring formatting.
+fn same_value(a: &toml_edit::Item, b: &toml_edit::Item) -> bool {
+	fn parse(value: &toml_edit::Value) -> Option<toml::Value> {
+		toml::from_str::<toml::Table>(&format!("v = {}", value))
+			.ok()?
+			.remove("v")
+	}
+	match (a.as_value(), b.as_value()) {
+		(Some(a), Some(b)) => parse(a).is_some_and(|a| Some(a) == parse(b)),
+		_ => false,
+	}
+}
+
+/// Extract the field name and the expected field names from a serde unknown field message.
+fn unknown_field(message: &str) -> Option<(String, Vec<String>)> {
+	let rest = message.strip_prefix("unknown field `")?;
+	let (field, rest) = rest.split_once('`')?;
+	let expected = rest
+		.split('`')
+		.skip(1)
+		.step_by(2)
+		.map(str::to_string)
+		.collect();
+	Some((field.to_string(), expected))
+}
+
+/// Remove the key-value pair whose key starts at `offset`, returning the new document.
+fn remove_key_at(content: &str, offset: usize) -> Option<String> {
+	fn find_path(table: &toml_edit::Table, offset: usize) -> Option<Vec<String>> {
+		for (name, _) in table.iter() {
+			let (key, item) = table.get_key_value(name)?;
+			if key.span().is_some_and(|s| s.contains(&offset)) {
+				return Some(vec![name.to_string()]);
+			}
+			if let Some(child) = item.as_table() {
+				if let Some(mut path) = find_path(child, offset) {
+					path.insert(0, name.to_string());
+					return Some(path);
+				}
+			}
+		}
+		None
+	}
+
+	let doc = toml_edit::ImDocument::parse(content).ok()?;
+	let path = find_path(doc.as_table(), offset)?;
+	let mut doc = content.parse::<toml_edit::DocumentMut>().ok()?;
+	let (last, parents) = path.split_last()?;
+	let mut table = doc.as_table_mut();
+	for name in parents {
+		table = table.get_mut(name)?.as_table_mut()?;
+	}
+	table.remove(last)?;
+	Some(doc.to_string())
+}
+
+/// A configuration file that doesn't parse, told apart from other errors by its exit code.
+#[derive(Debug)]
+pub struct ConfigError(String);
+
+impl Display for ConfigError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{} {}", Code::ConfigInvalid, self.0)
+	}
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Work that needs the network in offline mode, reported as skipped rather than failed.
+#[derive(Debug)]
+pub struct OfflineError(pub String);
+
+impl Display for OfflineError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "offline mode, {} needs the network", self.0)
+	}
+}
+
+impl std::error::Error for OfflineError {}
+
+/// Format a TOML parse error with the file, position and a caret excerpt.
+fn parse_error(path: &Path, content: &str, e: &toml::de::Error) -> ConfigError {
+	let Some(span) = e.span() else {
+		return ConfigError(format!(
+			"failed to parse '{}' {}",
+			path.display().to_string().themed(Role::Warning),
+			e.message()
+		));
+	};
+	let start = span.start.min(content.len());
+	let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+	let line_end = content[start..]
+		.find('\n')
+		.map_or(content.len(), |i| start + i);
+	let line = content[..start].matches('\n').count() + 1;
+	let column = content[line_start..start].chars().count() + 1;
+	let width = content[start..span.end.clamp(start, line_end)]
+		.chars()
+		.count()
+		.max(1);
+	let gutter = line.to_string().len();
+	ConfigError(format!(
+		"failed to parse '{}' at line {}, column {}: {}\n{:gutter$} |\n{} | {}\n{:gutter$} | {}{}",
+		path.display().to_string().themed(Role::Warning),
+		line,
+		column,
+		e.message(),
+		"",
+		line,
+		content[line_start..line_end].trim_end_matches('\r'),
+		"",
+		" ".repeat(column - 1),
+		"^".repeat(width).themed(Role::Error)
+	))
+}
+
+/// Maximum width of descriptions in list output.
+pub const DESCRIPTION_WIDTH: usize = 40;
+
+/// Truncate a string to at most `width` characters, appending an ellipsis when cut, `...` in
+/// plain mode.
+pub fn truncate(s: &str, width: usize) -> String {
+	if s.chars().count() <= width {
+		return s.to_string();
+	}
+	let ellipsis = if plain() { "..." } else { "…" };
+	let mut result: String = s
+		.chars()
+		.take(width.saturating_sub(ellipsis.chars().count()))
+		.collect();
+	result.push_str(ellipsis);
+	result
+}
+
+/// Current local time in RFC 3339 with second precision, as stored in configuration files.
+pub fn timestamp() -> String {
+	Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// How long ago `time` was, e.g. `3 days ago`.
+pub fn relative_time(time: DateTime<FixedOffset>) -> String {
+	let seconds = (Local::now().fixed_offset() - time).num_seconds();
+	if seconds < 60 {
+		return "just now".to_string();
+	}
+	let (count, unit) = [
+		(60 * 60 * 24 * 365, "year"),
+		(60 * 60 * 24 * 30, "month"),
+		(60 * 60 * 24 * 7, "week"),
+		(60 * 60 * 24, "day"),
+		(60 * 60, "hour"),
+		(60, "minute"),
+	]
+	.into_iter()
+	.find_map(|(length, unit)| (seconds >= length).then_some((seconds / length, unit)))
+	.unwrap();
+	format!(
+		"{} {}{} ago",
+		count,
+		unit,
+		if count == 1 { "" } else { "s" }
+	)
+}
+
+/// Format a byte count with binary units, e.g. `1.5 MiB`.
+pub fn human_size(bytes: u64) -> String {
+	const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+	if bytes < 1024 {
+		return format!("{} B", bytes);
+	}
+	let mut size = bytes as f64 / 1024.0;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	format!("{:.1} {}", size, UNITS[unit])
+}
+
 pub fn sort_keys<T, S>(value: &HashMap<String, T>, serializer: S) -> Result<S::Ok, S::Error>
 where
 	T: Serialize,
@@ -62,18 +735,702 @@ where
 		.serialize(serializer)
 }
 
+/// Why a prompt got no answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptError {
+	/// Input ended before an answer was given
+	Eof,
+	/// Ctrl+C was pressed while waiting for the answer
+	Interrupted,
+}
+
+impl Display for PromptError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Self::Eof => write!(f, "no input available; pass --yes or --no"),
+			Self::Interrupted => write!(f, "interrupted"),
+		}
+	}
+}
+
+impl std::error::Error for PromptError {}
+
+/// Set while a prompt waits for input, so Ctrl+C cancels it instead of exiting.
+static PROMPTING: AtomicBool = AtomicBool::new(false);
+/// Set once Ctrl+C cancelled a prompt, later prompts fail without asking.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Answer to every prompt given with `--yes` or `--no`. Set once in `main`.
+pub static ASSUME: OnceCell<Option<bool>> = OnceCell::new();
+
+/// Let Ctrl+C cancel a pending prompt, exiting with code 130 as usual otherwise.
+pub fn handle_interrupts() -> Result<()> {
+	ctrlc::set_handler(|| {
+		if PROMPTING.load(Ordering::SeqCst) {
+			INTERRUPTED.store(true, Ordering::SeqCst);
+		} else {
+			std::process::exit(130);
+		}
+	})?;
+	Ok(())
+}
+
+/// Whether a prompt of this command was cancelled with Ctrl+C.
+pub fn interrupted() -> bool {
+	INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Source of prompt answers, one per line.
+trait Answers {
+	fn next_answer(&mut self) -> Result<String>;
+}
+
+impl<R: BufRead> Answers for R {
+	/// Next line, trimmed, failing instead of answering when the input has ended.
+	fn next_answer(&mut self) -> Result<String> {
+		let mut line = String::new();
+		if self.read_line(&mut line)? == 0 {
+			return Err(PromptError::Eof.into());
+		}
+		Ok(line.trim().to_string())
+	}
+}
+
+/// Stdin read on a separate thread, so waiting for it can be cancelled with Ctrl+C.
+struct Terminal;
+
+impl Answers for Terminal {
+	fn next_answer(&mut self) -> Result<String> {
+		if interrupted() {
+			return Err(PromptError::Interrupted.into());
+		}
+		let (tx, rx) = mpsc::channel();
+		thread::spawn(move || tx.send(io::stdin().lock().next_answer()));
+		PROMPTING.store(true, Ordering::SeqCst);
+		let answer = loop {
+			match rx.recv_timeout(Duration::from_millis(50)) {
+				Ok(answer) => break answer,
+				Err(RecvTimeoutError::Timeout) if !interrupted() => continue,
+				Err(_) => {
+					// Ends the line of the prompt, the reading thread is left blocked.
+					println!();
+					break Err(PromptError::Interrupted.into());
+				}
+			}
+		};
+		PROMPTING.store(false, Ordering::SeqCst);
+		answer
+	}
+}
+
+/// Print `message` and the answer given on the command line, if any.
+fn assumed(message: &str) -> Option<bool> {
+	let answer = ASSUME.get().copied().flatten()?;
+	println!("{message}{}", if answer { "y" } else { "n" });
+	Some(answer)
+}
+
+/// Ask the user to type `word`, for operations too destructive for `--yes` to confirm.
+///
+/// `--no` still declines without asking.
+pub fn prompt_word(message: &str, word: &str) -> Result<bool> {
+	let message = format!("{message} Type '{word}' to confirm");
+	match ASSUME.get().copied().flatten() {
+		Some(false) => {
+			println!("{message}: n");
+			Ok(false)
+		}
+		Some(true) => Ok(read_input(&message)
+			.context(format!("'--yes' doesn't apply, type '{word}' to confirm"))?
+			.trim() == word),
+		None => Ok(read_input(&message)?.trim() == word),
+	}
+}
+
 /// prompt the user for a yes/no response.
 ///
+/// Fails with `PromptError` when stdin is closed or Ctrl+C is pressed, instead of assuming no.
+///
 /// # Arguments
 /// `message` - The prompt to display, appended with " [y/N]: "
 pub fn prompt(message: &str) -> Result<bool> {
-	let mut input = String::new();
-	print!("{message} [y/N]: ");
+	let message = format!("{message} [y/N]: ");
+	match assumed(&message) {
+		Some(answer) => Ok(answer),
+		None => prompt_from(&message, &mut Terminal),
+	}
+}
+
+fn prompt_from(message: &str, input: &mut impl Answers) -> Result<bool> {
+	print!("{}", message.themed(Role::Prompt));
 	io::stdout().flush()?; // Make sure the prompt is immediately displayed
-	io::stdin().read_line(&mut input)?;
-	match input.trim().to_lowercase().as_str() {
-		"y" => Ok(true),
-		"n" => Ok(false),
-		_ => Ok(false),
+	Ok(input.next_answer()?.eq_ignore_ascii_case("y"))
+}
+
+/// Answer given with `a` or `q` to `prompt_batch`, reused for the rest of the command.
+static BATCH_ANSWER: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Prompt the user for a yes/no response that may apply to every remaining item of a batch.
+///
+/// `a` answers yes and `q` answers no to this and all later calls without asking again.
+///
+/// # Arguments
+/// `message` - The prompt to display, appended with the available answers
+pub fn prompt_batch(message: &str) -> Result<bool> {
+	let message = format!("{message} [y/N, a: yes to all, q: no to all]: ");
+	match assumed(&message) {
+		Some(answer) => Ok(answer),
+		None => prompt_batch_from(&message, &mut Terminal, &mut BATCH_ANSWER.lock().unwrap()),
+	}
+}
+
+fn prompt_batch_from(
+	message: &str,
+	input: &mut impl Answers,
+	remembered: &mut Option<bool>,
+) -> Result<bool> {
+	if let Some(answer) = *remembered {
+		return Ok(answer);
+	}
+	print!("{}", message.themed(Role::Prompt));
+	io::stdout().flush()?;
+	let answer = input.next_answer()?.to_lowercase();
+	match answer.as_str() {
+		"a" => *remembered = Some(true),
+		"q" => *remembered = Some(false),
+		_ => {}
+	}
+	Ok(matches!(answer.as_str(), "y" | "a"))
+}
+
+/// Prompt the user for a line of input, returning it trimmed.
+///
+/// # Arguments
+/// `message` - The prompt to display, appended with ": "
+pub fn read_input(message: &str) -> Result<String> {
+	print!("{}", format!("{message}: ").themed(Role::Prompt));
+	io::stdout().flush()?;
+	Terminal.next_answer()
+}
+
+/// Sorted `names` shortened to the first few and how many more there are, for confirmations.
+pub fn first_names(mut names: Vec<&str>) -> String {
+	const SHOWN: usize = 5;
+	names.sort_unstable();
+	let mut shown = names[..names.len().min(SHOWN)].join(", ");
+	if names.len() > SHOWN {
+		shown.push_str(&format!(" and {} more", names.len() - SHOWN));
+	}
+	shown
+}
+
+/// Delimited text formats of listings, for spreadsheets and inventory tools.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TableFormat {
+	/// Comma separated values
+	Csv,
+	/// Tab separated values
+	Tsv,
+}
+
+impl TableFormat {
+	fn separator(self) -> char {
+		match self {
+			TableFormat::Csv => ',',
+			TableFormat::Tsv => '\t',
+		}
+	}
+
+	/// A header line and the rows, uncolored whether or not stdout is a terminal.
+	///
+	/// Fields holding the separator, quotes or line breaks are quoted, with quotes doubled.
+	pub fn table<S: AsRef<str>>(self, header: &[&str], rows: &[Vec<S>]) -> String {
+		let separator = self.separator();
+		let line = |fields: &mut dyn Iterator<Item = &str>| {
+			let fields: Vec<_> = fields
+				.map(|field| {
+					if field.contains([separator, '"', '\n', '\r']) {
+						format!("\"{}\"", field.replace('"', "\"\""))
+					} else {
+						field.to_string()
+					}
+				})
+				.collect();
+			fields.join(&separator.to_string()) + "\n"
+		};
+		let mut table = line(&mut header.iter().copied());
+		for row in rows {
+			table.push_str(&line(&mut row.iter().map(AsRef::as_ref)));
+		}
+		table
+	}
+}
+
+/// Line-oriented output for scripts, whose fields and their order don't change between versions.
+///
+/// One record per line with tab separated fields, no header and no colors. Tabs in fields become
+/// spaces, and so do line breaks unless records end with NUL.
+#[derive(Debug, Clone, Copy)]
+pub struct Porcelain {
+	/// End records with NUL instead of a newline
+	pub nul: bool,
+}
+
+impl Porcelain {
+	pub fn render<S: AsRef<str>>(self, rows: &[Vec<S>]) -> String {
+		let end = if self.nul { '\0' } else { '\n' };
+		let mut output = String::new();
+		for row in rows {
+			let fields: Vec<String> = row
+				.iter()
+				.map(|field| {
+					field
+						.as_ref()
+						.chars()
+						.map(|c| match c {
+							'\t' => ' ',
+							'\n' | '\r' if !self.nul => ' ',
+							c => c,
+						})
+						.collect()
+				})
+				.collect();
+			output.push_str(&fields.join("\t"));
+			output.push(end);
+		}
+		output
+	}
+}
+
+/// Quote an argument for a POSIX shell, leaving plain words as they are.
+pub fn shell_quote(arg: &str) -> String {
+	let plain = !arg.is_empty()
+		&& arg
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+	if plain {
+		arg.to_string()
+	} else {
+		format!("'{}'", arg.replace('\'', r"'\''"))
+	}
+}
+
+/// Quote an argument for PowerShell, leaving plain words as they are.
+pub fn powershell_quote(arg: &str) -> String {
+	let plain = !arg.is_empty()
+		&& arg
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || r"_./:\=+".contains(c));
+	if plain {
+		arg.to_string()
+	} else {
+		powershell_string(arg)
+	}
+}
+
+/// PowerShell string literal of `value`, always quoted as needed where an expression is expected.
+pub fn powershell_string(value: &str) -> String {
+	// Single quotes are the only special character inside single quotes, including the
+	// typographic ones PowerShell treats the same.
+	let escaped = value
+		.replace('\'', "''")
+		.replace('\u{2018}', "\u{2018}\u{2018}")
+		.replace('\u{2019}', "\u{2019}\u{2019}");
+	format!("'{}'", escaped)
+}
+
+/// Path of `program` found in `PATH`, trying the extensions of `PATHEXT` on Windows.
+pub fn which(program: &str) -> Option<PathBuf> {
+	let extensions: Vec<String> = if cfg!(windows) {
+		env::var("PATHEXT")
+			.unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".to_string())
+			.split(';')
+			.map(str::to_string)
+			.collect()
+	} else {
+		vec![String::new()]
+	};
+	env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+		extensions
+			.iter()
+			.map(|ext| dir.join(format!("{}{}", program, ext)))
+			.find(|path| path.is_file())
+	})
+}
+
+/// Command line running `command` in `dir`, quoted for the shell of the platform so it can be
+/// pasted into a terminal: PowerShell on Windows, a POSIX shell otherwise.
+pub fn command_line<S: AsRef<str>>(dir: &Path, command: &[S]) -> String {
+	let dir = dir.to_string_lossy();
+	let command = command.iter().map(AsRef::as_ref);
+	if cfg!(windows) {
+		let command: Vec<String> = command.map(powershell_quote).collect();
+		format!("cd {}; & {}", powershell_quote(&dir), command.join(" "))
+	} else {
+		let command: Vec<String> = command.map(shell_quote).collect();
+		format!("cd {} && {}", shell_quote(&dir), command.join(" "))
+	}
+}
+
+/// Assignment of an environment variable, in the same shell syntax as `command_line`.
+pub fn env_assignment(name: &str, value: &str) -> String {
+	if cfg!(windows) {
+		format!("$env:{} = {}", name, powershell_string(value))
+	} else {
+		format!("export {}={}", name, shell_quote(value))
+	}
+}
+
+/// Let the user pick items to remove from a checkbox list, then confirm the selection.
+///
+/// Returns an empty list when nothing is selected or the removal is declined.
+pub fn select_for_removal(kind: &str, items: &[&str]) -> Result<Vec<String>> {
+	if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+		bail!("'--interactive' requires a terminal, pass the names instead");
+	}
+	if items.is_empty() {
+		note!("no {} to remove", kind);
+		return Ok(vec![]);
+	}
+	let selected = dialoguer::MultiSelect::new()
+		.with_prompt(format!("Select {} to remove (space to toggle)", kind))
+		.items(items)
+		.interact()?;
+	let names: Vec<String> = selected.into_iter().map(|i| items[i].to_string()).collect();
+	if names.is_empty() {
+		note!("nothing selected");
+		return Ok(vec![]);
+	}
+	if prompt(&format!(
+		"Remove {}?",
+		names.join(", ").themed(Role::Warning)
+	))? {
+		Ok(names)
+	} else {
+		Ok(vec![])
+	}
+}
+
+/// The history journal is moved to `OLD_HISTORY_FILE` once it grows past this size.
+const HISTORY_MAX_SIZE: u64 = 1024 * 1024;
+/// Reversible entries waiting for the configuration they changed to be saved.
+static PENDING: Mutex<Vec<HistoryEntry>> = Mutex::new(vec![]);
+
+/// Result of an operation recorded in the history journal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+	#[default]
+	Ok,
+	Failed,
+	Skipped,
+}
+
+/// One operation in the history journal, built up and then written with `record`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HistoryEntry {
+	/// RFC 3339 local time
+	pub time: String,
+	pub operation: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub repo: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub package: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub r#type: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub args: Vec<String>,
+	/// Etag of the package before and after running its script
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub previous_etag: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub etag: Option<String>,
+	pub outcome: Outcome,
+	/// First line of the error of a failed operation
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	/// What `undo` needs to reverse the operation, absent if it can't
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub undo: Option<Undo>,
+}
+
+/// State recorded with a reversible operation.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Undo {
+	/// Configuration file changed by the operation
+	pub config: PathBuf,
+	/// Checksum of the configuration as saved right after the operation
+	pub checksum: String,
+	/// The changed entry before the operation, `null` if it didn't exist
+	#[serde(default)]
+	pub before: serde_json::Value,
+}
+
+impl HistoryEntry {
+	pub fn new(operation: &str) -> Self {
+		Self {
+			time: timestamp(),
+			operation: operation.to_string(),
+			..Default::default()
+		}
+	}
+
+	pub fn repo(mut self, repo: &str) -> Self {
+		self.repo = Some(repo.to_string());
+		self
+	}
+
+	pub fn package(mut self, package: &str) -> Self {
+		self.package = Some(package.to_string());
+		self
+	}
+
+	pub fn r#type(mut self, r#type: &str) -> Self {
+		self.r#type = Some(r#type.to_string());
+		self
+	}
+
+	/// Arguments of the operation, with secrets redacted.
+	pub fn args<S: AsRef<str>>(mut self, args: &[S]) -> Self {
+		self.args = redact::mask(args);
+		self
+	}
+
+	/// Etag of the package before and after the operation.
+	pub fn etag(mut self, previous: Option<&str>, etag: Option<&str>) -> Self {
+		self.previous_etag = previous.map(str::to_string);
+		self.etag = etag.map(str::to_string);
+		self
+	}
+
+	pub fn failed(mut self, error: impl Display) -> Self {
+		self.outcome = Outcome::Failed;
+		self.error = error.to_string().lines().next().map(str::to_string);
+		self
+	}
+
+	pub fn skipped(mut self) -> Self {
+		self.outcome = Outcome::Skipped;
+		self
+	}
+
+	/// Make the operation reversible, `before` is the changed entry of `config` before it.
+	///
+	/// The entry is held back until `config` is saved, and dropped if it never is.
+	pub fn reversible(mut self, config: &Path, before: impl Serialize) -> Self {
+		self.undo = Some(Undo {
+			config: config.to_path_buf(),
+			checksum: String::new(),
+			before: serde_json::to_value(before).unwrap_or_default(),
+		});
+		self
+	}
+
+	/// Set the outcome from the result of the operation.
+	pub fn result<T, E: Display>(self, result: &std::result::Result<T, E>) -> Self {
+		match result {
+			Ok(_) => self,
+			Err(e) => self.failed(e),
+		}
+	}
+
+	/// Append the entry to the journal, a failure to write it only prints a note.
+	pub fn record(self) {
+		if self.undo.is_some() && self.outcome == Outcome::Ok {
+			PENDING.lock().unwrap().push(self);
+		} else if let Err(e) = append_history(&self) {
+			note!("failed to write history: {}", e);
+		}
+	}
+}
+
+/// Checksum of the parsed content of a TOML file, unaffected by formatting and comments.
+pub fn config_checksum(path: &Path) -> Result<String> {
+	let value: toml::Value = toml::from_str(&fs::read_to_string(path)?)?;
+	Ok(checksum::sha256_hex(serde_json::to_string(&value)?))
+}
+
+/// Write the reversible entries held back for `path` now that it's saved.
+fn record_saved(path: &Path) {
+	let entries: Vec<HistoryEntry> = {
+		let mut pending = PENDING.lock().unwrap();
+		let (saved, rest) = pending
+			.drain(..)
+			.partition(|e| e.undo.as_ref().is_some_and(|u| u.config == path));
+		*pending = rest;
+		saved
+	};
+	if entries.is_empty() {
+		return;
+	}
+	let checksum = config_checksum(path).unwrap_or_default();
+	for mut entry in entries {
+		if let Some(undo) = &mut entry.undo {
+			undo.checksum = checksum.clone();
+		}
+		if let Err(e) = append_history(&entry) {
+			note!("failed to write history: {}", e);
+		}
+	}
+}
+
+fn append_history(entry: &HistoryEntry) -> Result<()> {
+	if let Some(parent) = HISTORY_FILE.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	if fs::metadata(&*HISTORY_FILE).is_ok_and(|m| m.len() > HISTORY_MAX_SIZE) {
+		fs::rename(&*HISTORY_FILE, &*OLD_HISTORY_FILE)?;
+	}
+	let mut file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(&*HISTORY_FILE)?;
+	writeln!(file, "{}", serde_json::to_string(entry)?)?;
+	Ok(())
+}
+
+/// Join arguments into a command line that can be pasted into a POSIX shell.
+pub fn quote_args<S: AsRef<str>>(args: &[S]) -> String {
+	args.iter()
+		.map(|arg| shell_quote(arg.as_ref()))
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	#[test]
+	fn home_relative_paths() {
+		let home = dirs::home_dir().unwrap();
+		let inside = home.join("proj").join("a");
+		assert_eq!(contract_tilde(&inside), Path::new("~/proj/a"));
+		assert_eq!(contract_tilde(&home), Path::new("~"));
+		assert_eq!(expand_tilde(&contract_tilde(&inside)).unwrap(), inside);
+		let outside = home.parent().unwrap().join("other");
+		assert_eq!(contract_tilde(&outside), outside);
+	}
+
+	#[test]
+	fn resolved_paths() {
+		let home = dirs::home_dir().unwrap();
+		let cwd = env::current_dir().unwrap();
+		assert_eq!(expand_tilde(Path::new("~")).unwrap(), home);
+		assert_eq!(
+			expand_tilde(Path::new("~/a/b")).unwrap(),
+			home.join("a").join("b")
+		);
+		assert_eq!(expand_tilde(Path::new("./a")).unwrap(), Path::new("./a"));
+		assert_eq!(expand_tilde(Path::new("a~")).unwrap(), Path::new("a~"));
+		assert!(expand_tilde(Path::new("~other/a")).is_err());
+
+		assert_eq!(
+			resolve_path(Path::new("./gpm-missing")).unwrap(),
+			cwd.join("gpm-missing")
+		);
+		assert_eq!(
+			resolve_path(Path::new("gpm-missing/../gpm-other")).unwrap(),
+			cwd.join("gpm-other")
+		);
+		let absolute = env::temp_dir().join("gpm-missing");
+		assert_eq!(resolve_path(&absolute).unwrap(), absolute);
+		assert_eq!(
+			resolve_path(Path::new("~/gpm-missing")).unwrap(),
+			home.join("gpm-missing")
+		);
+		assert!(resolve_path(Path::new("~other")).is_err());
+	}
+
+	#[test]
+	fn porcelain() {
+		let rows = [vec!["a b", "x\ty", "1\n2"], vec!["c", "", ""]];
+		assert_eq!(
+			Porcelain { nul: false }.render(&rows),
+			"a b\tx y\t1 2\nc\t\t\n"
+		);
+		assert_eq!(
+			Porcelain { nul: true }.render(&rows),
+			"a b\tx y\t1\n2\0c\t\t\0"
+		);
+	}
+
+	#[test]
+	fn table_quoting() {
+		let rows = [vec!["a,b", "say \"hi\"", "two\nlines", "plain"]];
+		assert_eq!(
+			TableFormat::Csv.table(&["w", "x", "y", "z"], &rows),
+			"w,x,y,z\n\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",plain\n"
+		);
+		assert_eq!(
+			TableFormat::Tsv.table(&["w"], &[vec!["a,b\tc"]]),
+			"w\n\"a,b\tc\"\n"
+		);
+	}
+
+	#[test]
+	fn creates_missing_parents() {
+		let root = tempfile::tempdir().unwrap();
+		let path = root.path().join("a").join("b");
+		create_dir_all(&path).unwrap();
+		assert!(path.is_dir());
+		create_dir_all(&path).unwrap();
+		fs::write(root.path().join("file"), "").unwrap();
+		let error = create_dir_all(&root.path().join("file").join("c")).unwrap_err();
+		assert!(error.to_string().contains("failed to create"));
+	}
+
+	#[test]
+	fn prompt_reads_answers() {
+		let mut input = Cursor::new("y\n N \nmaybe\n");
+		assert!(prompt_from("?", &mut input).unwrap());
+		assert!(!prompt_from("?", &mut input).unwrap());
+		assert!(!prompt_from("?", &mut input).unwrap());
+	}
+
+	#[test]
+	fn prompt_fails_at_end_of_input() {
+		let mut input = Cursor::new("y\n");
+		prompt_from("?", &mut input).unwrap();
+		let e = prompt_from("?", &mut input).unwrap_err();
+		assert_eq!(e.downcast_ref(), Some(&PromptError::Eof));
+		// An empty line is still an answer.
+		assert!(!prompt_from("?", &mut Cursor::new("\n")).unwrap());
+	}
+
+	#[test]
+	fn prompt_batch_remembers_all_answers() {
+		let mut remembered = None;
+		let mut input = Cursor::new("n\na\n");
+		assert!(!prompt_batch_from("?", &mut input, &mut remembered).unwrap());
+		assert!(prompt_batch_from("?", &mut input, &mut remembered).unwrap());
+		// Input is exhausted, so these come from the remembered answer.
+		assert!(prompt_batch_from("?", &mut input, &mut remembered).unwrap());
+		assert!(prompt_batch_from("?", &mut input, &mut remembered).unwrap());
+
+		let mut remembered = None;
+		let mut input = Cursor::new("Q\n");
+		assert!(!prompt_batch_from("?", &mut input, &mut remembered).unwrap());
+		assert!(!prompt_batch_from("?", &mut input, &mut remembered).unwrap());
+	}
+
+	#[test]
+	fn quoting_for_shells() {
+		assert_eq!(shell_quote("it's"), r"'it'\''s'");
+		assert_eq!(shell_quote("-n"), "-n");
+		assert_eq!(powershell_quote(r"C:\a b\it's"), r"'C:\a b\it''s'");
+		assert_eq!(powershell_quote("$x;y"), "'$x;y'");
+		assert_eq!(powershell_quote("v1.2"), "v1.2");
+	}
+
+	#[test]
+	fn relative_times() {
+		let ago = |seconds| {
+			relative_time(Local::now().fixed_offset() - chrono::Duration::seconds(seconds))
+		};
+		assert_eq!(ago(5), "just now");
+		assert_eq!(ago(60), "1 minute ago");
+		assert_eq!(ago(3 * 24 * 60 * 60 + 5), "3 days ago");
+		assert_eq!(ago(400 * 24 * 60 * 60), "1 year ago");
 	}
 }
@@ -0,0 +1,124 @@
+//! Copies of packages made by `clone`, as reflinks, hard links or plain copies.
+
+use crate::config::util::copy_dir_with;
+use crate::note;
+use crate::theme::{Role, Themed};
+
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use std::cell::Cell;
+use std::path::Path;
+use std::{fmt, fs};
+
+/// When `clone` makes copy-on-write copies of files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum ReflinkMode {
+	/// When the file system supports them, falling back otherwise
+	#[default]
+	Auto,
+	/// Fail when the file system doesn't support them
+	Always,
+	Never,
+}
+
+/// How a file was copied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+	Reflink,
+	HardLink,
+	Copy,
+}
+
+impl fmt::Display for Strategy {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Strategy::Reflink => write!(f, "reflinks"),
+			Strategy::HardLink => write!(f, "hard links, sharing content with the repository"),
+			Strategy::Copy => write!(f, "plain copies"),
+		}
+	}
+}
+
+/// Copies files with the cheapest strategy allowed, telling which once.
+pub struct Copier {
+	reflink: ReflinkMode,
+	/// Hard link files when they can't be reflinked
+	link_files: bool,
+	/// Reflinks failed once in `auto` mode, so they aren't attempted again
+	no_reflink: Cell<bool>,
+	/// Hard links failed once, e.g. across file systems
+	no_link: Cell<bool>,
+	shown: Cell<bool>,
+}
+
+impl Copier {
+	pub fn new(reflink: ReflinkMode, link_files: bool) -> Self {
+		Self {
+			reflink,
+			link_files,
+			no_reflink: Cell::new(reflink == ReflinkMode::Never),
+			no_link: Cell::new(!link_files),
+			shown: Cell::new(false),
+		}
+	}
+
+	/// Copy a file or directory from `from` to `to`, replacing existing files.
+	pub fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+		if fs::metadata(from)?.is_dir() {
+			copy_dir_with(from, to, &|from, to| self.copy_file(from, to))
+		} else {
+			self.copy_file(from, to)
+		}
+	}
+
+	fn copy_file(&self, from: &Path, to: &Path) -> Result<()> {
+		// Reflinks and hard links refuse to replace a file, unlike `fs::copy`.
+		if to.symlink_metadata().is_ok() {
+			fs::remove_file(to)?;
+		}
+		let strategy = if self.reflink(from, to)? {
+			Strategy::Reflink
+		} else if !self.no_link.get() && fs::hard_link(from, to).is_ok() {
+			Strategy::HardLink
+		} else {
+			self.no_link.set(true);
+			fs::copy(from, to).map_err(|e| {
+				anyhow!(
+					"failed to copy '{}' {}",
+					to.display().to_string().themed(Role::Warning),
+					e
+				)
+			})?;
+			Strategy::Copy
+		};
+		if !self.shown.replace(true) {
+			note!("cloning with {}", strategy);
+		}
+		Ok(())
+	}
+
+	/// Reflink `from` to `to`, `false` when not supported and allowed to fall back.
+	fn reflink(&self, from: &Path, to: &Path) -> Result<bool> {
+		if self.no_reflink.get() {
+			return Ok(false);
+		}
+		match reflink_copy::reflink(from, to) {
+			Ok(()) => Ok(true),
+			Err(e) if self.reflink == ReflinkMode::Always => bail!(
+				"failed to reflink '{}' {}, pass '--reflink auto' to fall back to {}",
+				to.display().to_string().themed(Role::Warning),
+				e,
+				if self.link_files {
+					"hard links"
+				} else {
+					"copies"
+				}
+			),
+			Err(_) => {
+				self.no_reflink.set(true);
+				Ok(false)
+			}
+		}
+	}
+}
@@ -0,0 +1,138 @@
+//! Disk usage of repositories and their packages.
+
+use crate::config::main::Config;
+use crate::config::util::human_size;
+use crate::theme::{Role, Themed};
+use crate::REPO_CONFIG;
+
+use anyhow::Result;
+use jwalk::WalkDir;
+use serde_json::json;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use tabwriter::TabWriter;
+
+struct Usage {
+	name: String,
+	path: Box<Path>,
+	/// Sizes of the top level entries, largest first
+	packages: Vec<(String, u64)>,
+	total: u64,
+}
+
+/// Sum file sizes under `root` by top level entry, walking directories in parallel.
+///
+/// Symlinks are not followed and count as zero, hard links are only counted once.
+fn usage(name: &str, root: &Path) -> Usage {
+	let mut sizes: HashMap<String, u64> = HashMap::new();
+	let mut seen = HashSet::new();
+	for entry in WalkDir::new(root).skip_hidden(false).into_iter().flatten() {
+		let Ok(rel) = entry.path().strip_prefix(root).map(Path::to_path_buf) else {
+			continue;
+		};
+		let Some(first) = rel.components().next() else {
+			continue;
+		};
+		let package = first.as_os_str().to_string_lossy().into_owned();
+		if package == REPO_CONFIG {
+			continue;
+		}
+		let size = match entry.metadata() {
+			Ok(meta) if meta.is_file() && first_link(&meta, &mut seen) => meta.len(),
+			_ => 0,
+		};
+		*sizes.entry(package).or_default() += size;
+	}
+	let mut packages: Vec<_> = sizes.into_iter().collect();
+	packages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+	Usage {
+		name: name.to_string(),
+		path: root.into(),
+		total: packages.iter().map(|(_, size)| size).sum(),
+		packages,
+	}
+}
+
+/// Total size of the files under `path`, or of `path` itself when it's a file.
+pub fn size(path: &Path) -> u64 {
+	if path.is_file() {
+		return path.metadata().map_or(0, |meta| meta.len());
+	}
+	let mut seen = HashSet::new();
+	WalkDir::new(path)
+		.skip_hidden(false)
+		.into_iter()
+		.flatten()
+		.filter_map(|entry| entry.metadata().ok())
+		.filter(|meta| meta.is_file() && first_link(meta, &mut seen))
+		.map(|meta| meta.len())
+		.sum()
+}
+
+/// Whether this is the first time a file is seen, false for further hard links to it.
+#[cfg(unix)]
+fn first_link(meta: &std::fs::Metadata, seen: &mut HashSet<(u64, u64)>) -> bool {
+	use std::os::unix::fs::MetadataExt;
+	meta.nlink() <= 1 || seen.insert((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn first_link(_: &std::fs::Metadata, _: &mut HashSet<(u64, u64)>) -> bool {
+	true
+}
+
+/// Report disk usage of one repository, or all of them, largest first.
+pub fn report(gpm_cfg: &Config, repo: Option<&str>, as_json: bool) -> Result<String> {
+	let names = match repo {
+		Some(name) => vec![name],
+		None => gpm_cfg.names(),
+	};
+	let mut usages = vec![];
+	for name in names {
+		usages.push(usage(name, &gpm_cfg.get_repo_path(name)?));
+	}
+	usages.sort_by_key(|u| Reverse(u.total));
+	let total: u64 = usages.iter().map(|u| u.total).sum();
+
+	if as_json {
+		let repositories: Vec<_> = usages
+			.iter()
+			.map(|u| {
+				let packages: Vec<_> = u
+					.packages
+					.iter()
+					.map(|(name, size)| json!({ "name": name, "size": size }))
+					.collect();
+				json!({ "name": u.name, "path": u.path, "size": u.total, "packages": packages })
+			})
+			.collect();
+		return Ok(serde_json::to_string_pretty(&json!({
+			"repositories": repositories,
+			"total": total,
+		}))? + "\n");
+	}
+
+	let mut tw = TabWriter::new(vec![]);
+	writeln!(&mut tw, "{}", "Disk usage:".themed(Role::Success))?;
+	for u in &usages {
+		writeln!(
+			&mut tw,
+			"  {}\t{}",
+			u.name.themed(Role::Name),
+			human_size(u.total)
+		)?;
+		for (package, size) in &u.packages {
+			writeln!(&mut tw, "    {}\t{}", package, human_size(*size))?;
+		}
+	}
+	writeln!(
+		&mut tw,
+		"{}\t{}",
+		"Total:".themed(Role::Success),
+		human_size(total)
+	)?;
+	tw.flush()?;
+	Ok(String::from_utf8(tw.into_inner().unwrap())?)
+}
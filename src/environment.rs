@@ -0,0 +1,99 @@
+//! Resolved paths and build information, also passed to scripts as environment variables.
+
+use crate::config::r#type::default_shell;
+use crate::theme::{Role, Themed};
+use crate::{
+	offline, BIN_PATH, GPM_CONFIG, GPM_HOME, PROFILE, REPO_PATH, SCRIPT_ROOT, TYPES_CONFIG,
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use serde_json::json;
+use std::env::consts::OS;
+use std::io::Write;
+use std::path::Path;
+use tabwriter::TabWriter;
+
+/// Paths used by gpm, keyed by the environment variable name passed to scripts.
+fn paths() -> [(&'static str, &'static Path); 6] {
+	[
+		("GPM_HOME", &GPM_HOME),
+		("GPM_CONFIG", &GPM_CONFIG),
+		("GPM_REPO_PATH", &REPO_PATH),
+		("GPM_SCRIPT_ROOT", &SCRIPT_ROOT),
+		("GPM_TYPES_CONFIG", &TYPES_CONFIG),
+		("GPM_BIN_PATH", &BIN_PATH),
+	]
+}
+
+fn profile() -> Option<&'static str> {
+	PROFILE.get().and_then(Option::as_deref)
+}
+
+/// Environment variables passed to scripts.
+pub fn vars() -> Vec<(&'static str, String)> {
+	let mut vars: Vec<_> = paths()
+		.into_iter()
+		.map(|(name, path)| (name, path.to_string_lossy().into_owned()))
+		.collect();
+	if let Some(profile) = profile() {
+		vars.push(("GPM_PROFILE", profile.into()));
+	}
+	if offline() {
+		vars.push(("GPM_OFFLINE", "1".into()));
+	}
+	vars
+}
+
+/// Render the environment report, either human-readable or as JSON.
+pub fn report(as_json: bool) -> Result<String> {
+	let (shell, shell_args) = default_shell();
+	if as_json {
+		let paths: serde_json::Map<_, _> = paths()
+			.into_iter()
+			.map(|(name, path)| {
+				(
+					name.to_string(),
+					json!({ "path": path, "exists": path.exists() }),
+				)
+			})
+			.collect();
+		return Ok(serde_json::to_string_pretty(&json!({
+			"version": env!("CARGO_PKG_VERSION"),
+			"target": env!("TARGET"),
+			"os": OS,
+			"profile": profile(),
+			"default_shell": { "name": shell, "args": shell_args },
+			"paths": paths,
+		}))? + "\n");
+	}
+
+	let mut tw = TabWriter::new(vec![]);
+	writeln!(&mut tw, "{}", "Build:".themed(Role::Success))?;
+	writeln!(&mut tw, "  version\t{}", env!("CARGO_PKG_VERSION"))?;
+	writeln!(&mut tw, "  target\t{}", env!("TARGET"))?;
+	writeln!(&mut tw, "  os\t{}", OS)?;
+	writeln!(
+		&mut tw,
+		"  default shell\t{} {}",
+		shell.themed(Role::Name),
+		shell_args.join(" ").themed(Role::Type)
+	)?;
+	writeln!(&mut tw, "  profile\t{}", profile().unwrap_or("default"))?;
+	writeln!(&mut tw, "{}", "Paths:".themed(Role::Success))?;
+	for (name, path) in paths() {
+		writeln!(
+			&mut tw,
+			"  {}\t{}\t{}",
+			name.themed(Role::Name),
+			path.display().to_string().themed(Role::Path),
+			if path.exists() {
+				"".normal()
+			} else {
+				"(missing)".themed(Role::Error)
+			}
+		)?;
+	}
+	tw.flush()?;
+	Ok(String::from_utf8(tw.into_inner().unwrap())?)
+}
@@ -0,0 +1,78 @@
+//! Optional log file recording invocations, script executions and config saves.
+
+use crate::GPM_HOME;
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use std::fs::{self, OpenOptions};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+
+static LOG_FILE: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("gpm.log"));
+/// Previous log file, replaced whenever the current one grows past `MAX_SIZE`.
+static OLD_LOG_FILE: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("gpm.log.1"));
+const MAX_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+	Off,
+	Error,
+	Warn,
+	Info,
+	Debug,
+	Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+	fn from(level: LogLevel) -> Self {
+		match level {
+			LogLevel::Off => LevelFilter::OFF,
+			LogLevel::Error => LevelFilter::ERROR,
+			LogLevel::Warn => LevelFilter::WARN,
+			LogLevel::Info => LevelFilter::INFO,
+			LogLevel::Debug => LevelFilter::DEBUG,
+			LogLevel::Trace => LevelFilter::TRACE,
+		}
+	}
+}
+
+/// Start writing log entries at `level` or above to the log file, does nothing when off.
+pub fn init(level: LogLevel) -> Result<()> {
+	if level == LogLevel::Off {
+		return Ok(());
+	}
+	fs::create_dir_all(&*GPM_HOME)?;
+	if fs::metadata(&*LOG_FILE).is_ok_and(|m| m.len() > MAX_SIZE) {
+		fs::rename(&*LOG_FILE, &*OLD_LOG_FILE)?;
+	}
+	let file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(&*LOG_FILE)?;
+	tracing_subscriber::fmt()
+		.with_writer(Mutex::new(file))
+		.with_ansi(false)
+		.with_max_level(LevelFilter::from(level))
+		.init();
+	Ok(())
+}
+
+/// Last `lines` entries of the log, reaching into the rotated file if needed.
+pub fn tail(lines: usize) -> Result<String> {
+	if !LOG_FILE.exists() {
+		bail!(
+			"no log file at '{}', pass '--log-level' to enable logging",
+			LOG_FILE.display()
+		);
+	}
+	let mut content = fs::read_to_string(&*OLD_LOG_FILE).unwrap_or_default();
+	content.push_str(&fs::read_to_string(&*LOG_FILE)?);
+	let all: Vec<&str> = content.lines().collect();
+	let start = all.len().saturating_sub(lines);
+	Ok(all[start..]
+		.iter()
+		.map(|line| format!("{}\n", line))
+		.collect())
+}
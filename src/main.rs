@@ -1,25 +1,158 @@
+mod alias;
+mod checksum;
+mod clean;
+mod completion;
 mod config;
+mod copy;
+mod du;
+mod environment;
+mod logging;
+mod redact;
+#[cfg(feature = "self-update")]
+mod self_update;
+mod theme;
+mod wizard;
 
+use crate::config::code::{Code, ErrorFormat};
+use crate::config::export::Conflict;
+use crate::config::history::HistoryFilter;
+use crate::config::hook::{self, Event};
 use crate::config::main::Config;
-use crate::config::r#type::TypeConfig;
-use crate::config::repository::RepoConfig;
+use crate::config::migrate;
+use crate::config::r#type::{self, Param, TypeConfig, TypeEdit};
+use crate::config::repository::{
+	AddOptions, EnvFormat, ListOptions, RepoConfig, SortKey, State, UpdateOptions,
+};
+use crate::config::setup::{self, SetupShell};
+use crate::config::shim::ShimConfig;
+use crate::config::spec;
+use crate::config::util::{
+	self, open_in_file_manager, resolve_cwd, resolve_path, select_for_removal, ConfigError,
+	OfflineError, Porcelain, PromptError, TableFormat,
+};
+use crate::copy::{Copier, ReflinkMode};
+use crate::logging::LogLevel;
+use crate::theme::{Role, Themed};
 
-use clap::CommandFactory;
-use clap::{builder::styling, Args, Parser, Subcommand};
-use clap_complete::Shell;
+use crate::completion::CompletionShell;
+use anyhow::{bail, Result};
+use clap::{builder::styling, Args, ColorChoice, Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches};
 use colored::Colorize;
-use once_cell::sync::Lazy;
-use path_clean::PathClean;
-use std::path::PathBuf;
-use std::{env, fs, io, process};
+use once_cell::sync::{Lazy, OnceCell};
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use std::{env, fs, io};
 
-static GPM_HOME: Lazy<PathBuf> = Lazy::new(|| dirs::home_dir().unwrap().join(".gpm"));
-static GPM_CONFIG: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("config.toml"));
+/// Root of all gpm data, `~/.gpm` unless overridden by the `GPM_HOME` environment variable.
+///
+/// Empty when neither is available, which `run` reports before any path is used.
+static GPM_HOME: Lazy<PathBuf> = Lazy::new(|| match env::var_os("GPM_HOME") {
+	Some(home) if !home.is_empty() => PathBuf::from(home),
+	_ => dirs::home_dir()
+		.map(|home| home.join(".gpm"))
+		.unwrap_or_default(),
+});
+/// Active profile, `None` for the default profile. Set once in `main` before any path is used.
+static PROFILE: OnceCell<Option<String>> = OnceCell::new();
+static PROFILES_PATH: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("profiles"));
+/// Root of the active profile, the default profile lives directly under GPM_HOME.
+static PROFILE_ROOT: Lazy<PathBuf> =
+	Lazy::new(|| profile_root(PROFILE.get().and_then(Option::as_deref)));
+static GPM_CONFIG: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("config.toml"));
 /// config for each repository
 static REPO_CONFIG: &str = "version.toml";
-static REPO_PATH: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("repositories"));
-static SCRIPT_ROOT: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("scripts"));
-static TYPES_CONFIG: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("types.toml"));
+static REPO_PATH: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("repositories"));
+static SCRIPT_ROOT: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("scripts"));
+static CHECKSUM_PATH: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("checksums"));
+static BIN_PATH: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("bin"));
+static SHIMS_CONFIG: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("shims.toml"));
+static TYPES_CONFIG: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("types.toml"));
+static BACKUP_PATH: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("backups"));
+/// Journal of registry operations, one JSON object per line.
+static HISTORY_FILE: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("history.jsonl"));
+/// Previous history journal, replaced whenever the current one grows too large.
+static OLD_HISTORY_FILE: Lazy<PathBuf> = Lazy::new(|| PROFILE_ROOT.join("history.jsonl.1"));
+/// Ignore unknown fields in configuration files. Set once in `main`.
+static LENIENT: OnceCell<bool> = OnceCell::new();
+/// Fail network operations right away. Set once in `main`.
+static OFFLINE: OnceCell<bool> = OnceCell::new();
+/// Uncolored ASCII output with word prefixes. Set once in `main`.
+static PLAIN: OnceCell<bool> = OnceCell::new();
+/// How errors are printed. Set once in `main`.
+static ERRORS: OnceCell<ErrorFormat> = OnceCell::new();
+/// Output verbosity. Set once in `main`.
+static VERBOSITY: OnceCell<Verbosity> = OnceCell::new();
+
+/// Exit code when a command or some items of a batch failed.
+const EXIT_FAILURE: u8 = 1;
+/// Exit code for invalid arguments or aliases, the same as clap uses.
+const EXIT_USAGE: u8 = 2;
+/// Exit code when Ctrl+C cancelled a prompt, the same as a shell reports for SIGINT.
+const EXIT_INTERRUPTED: u8 = 130;
+/// Exit code when a configuration file doesn't parse.
+const EXIT_CONFIG: u8 = 3;
+/// Exit code for file system errors.
+const EXIT_IO: u8 = 4;
+
+/// How much output the print macros produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+	/// Only errors and final summaries
+	Quiet,
+	Normal,
+	/// Additional details such as resolved paths and timing
+	Verbose,
+}
+
+/// Root of a profile, `None` for the default profile.
+fn profile_root(profile: Option<&str>) -> PathBuf {
+	match profile {
+		Some(name) => PROFILES_PATH.join(name),
+		None => GPM_HOME.clone(),
+	}
+}
+
+/// Current output verbosity.
+pub fn verbosity() -> Verbosity {
+	VERBOSITY.get().copied().unwrap_or(Verbosity::Normal)
+}
+
+/// Whether network access is disabled with `--offline`.
+pub fn offline() -> bool {
+	OFFLINE.get().copied().unwrap_or_default()
+}
+
+/// Whether output is plain ASCII, from `--ascii`, `GPM_PLAIN` or `TERM=dumb`.
+pub fn plain() -> bool {
+	PLAIN.get().copied().unwrap_or_default()
+}
+
+/// Whether errors are printed as JSON, from `--errors json`.
+pub fn json_errors() -> bool {
+	ERRORS.get() == Some(&ErrorFormat::Json)
+}
+
+/// Fail right away in offline mode, before `action` tries to reach the network.
+pub fn ensure_online(action: &str) -> Result<()> {
+	if offline() {
+		return Err(OfflineError(action.to_string()).into());
+	}
+	Ok(())
+}
+
+/// Shown at the end of `gpm --help` and in the man page.
+const EXAMPLES: &str = "\
+Examples:
+  gpm init
+  gpm type add github ps1 powershell
+  gpm add tools
+  gpm repo tools add ripgrep github BurntSushi/ripgrep
+  gpm repo tools update --all";
 
 // region: clap macros
 #[derive(Debug, Parser)]
@@ -29,8 +162,67 @@ static TYPES_CONFIG: Lazy<PathBuf> = Lazy::new(|| GPM_HOME.join("types.toml"));
     about = "A fully customizable general purpose package manager",
     styles = get_styles(),
     arg_required_else_help = true,
+    after_long_help = EXAMPLES,
 )]
 struct App {
+	/// Profile to operate on, omit for the default profile
+	#[clap(long, global = true, env = "GPM_PROFILE")]
+	profile: Option<String>,
+
+	/// Ignore unknown fields in configuration files
+	#[clap(long, global = true, env = "GPM_LENIENT")]
+	lenient: bool,
+
+	/// Fail network operations right away and tell scripts to skip network work
+	#[clap(long, global = true, env = "GPM_OFFLINE", value_parser = clap::builder::BoolishValueParser::new())]
+	offline: bool,
+
+	/// Only print errors and summaries
+	#[clap(short, long, global = true, conflicts_with = "verbose")]
+	quiet: bool,
+
+	/// Print additional details
+	#[clap(short, long, global = true)]
+	verbose: bool,
+
+	/// Answer yes to every question
+	#[clap(short, long, global = true, conflicts_with = "no")]
+	yes: bool,
+
+	/// Answer no to every question
+	#[clap(long, global = true)]
+	no: bool,
+
+	/// Print secrets in executed commands instead of `***`
+	#[clap(long, global = true)]
+	show_secrets: bool,
+
+	/// Skip the hooks configured in config.toml
+	#[clap(long, global = true)]
+	no_hooks: bool,
+
+	/// Print uncolored ASCII with word prefixes such as `[add]`, also set by TERM=dumb
+	#[clap(long, visible_alias = "plain", global = true, env = "GPM_PLAIN", value_parser = clap::builder::BoolishValueParser::new())]
+	ascii: bool,
+
+	/// When to use colors, `auto` honors NO_COLOR and only colors a terminal
+	#[clap(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+	color: ColorChoice,
+
+	/// How to print errors, `json` prints one object per line with a stable code, uncolored
+	#[clap(
+		long,
+		global = true,
+		value_name = "FORMAT",
+		value_enum,
+		default_value_t
+	)]
+	errors: ErrorFormat,
+
+	/// Write log entries at this level or above to gpm.log in the gpm home directory
+	#[clap(long, global = true, value_enum, env = "GPM_LOG_LEVEL", default_value_t = LogLevel::Off)]
+	log_level: LogLevel,
+
 	#[clap(subcommand)]
 	command: TopCommand,
 }
@@ -39,7 +231,11 @@ struct App {
 enum TopCommand {
 	/// Initialize the package manager, creating the necessary directories
 	#[clap(visible_alias = "i")]
-	Init,
+	Init {
+		/// Convert a configuration of the releases with namespaces to repositories
+		#[clap(long)]
+		migrate: bool,
+	},
 
 	/// Add a new repository
 	#[clap(visible_alias = "a")]
@@ -51,6 +247,43 @@ enum TopCommand {
 		/// Repository path
 		#[clap(short, long)]
 		path: Option<PathBuf>,
+
+		/// Repository description
+		#[clap(short, long)]
+		description: Option<String>,
+
+		/// Refuse mutating commands on the repository unless forced
+		#[clap(long)]
+		readonly: bool,
+
+		/// Allow spaces and unicode in the name
+		#[clap(long)]
+		allow_weird_names: bool,
+
+		/// Allow the path to overlap with other repositories
+		#[clap(short, long)]
+		force: bool,
+	},
+
+	/// Mark a repository as read-only
+	#[command(arg_required_else_help = true)]
+	SetReadonly {
+		/// Repository name
+		name: String,
+
+		/// Make the repository writable again
+		#[clap(long)]
+		unset: bool,
+	},
+
+	/// Set or clear the description of a repository
+	#[command(arg_required_else_help = true)]
+	Describe {
+		/// Repository name
+		name: String,
+
+		/// Description, omit to clear
+		description: Option<String>,
 	},
 
 	/// Remove repositories
@@ -58,17 +291,49 @@ enum TopCommand {
 	#[command(arg_required_else_help = true)]
 	Remove {
 		/// Repository name
-		#[clap(num_args = 1..)]
+		#[clap(num_args = 1.., required_unless_present_any = ["interactive", "all"])]
 		name: Vec<String>,
 
 		/// Remove registry only
 		#[clap(short, long)]
 		registry: bool,
+
+		/// Pick the repositories to remove from a list
+		#[clap(short, long, conflicts_with = "name")]
+		interactive: bool,
+
+		/// Remove every repository, after typing 'all' to confirm
+		#[clap(short, long, conflicts_with_all = ["name", "interactive"])]
+		all: bool,
 	},
 
 	/// List all repositories
 	#[clap(visible_alias = "l")]
-	List,
+	List {
+		/// Print only sorted names, one per line
+		#[clap(long)]
+		names_only: bool,
+
+		/// Show the packages of each repository nested beneath it
+		#[clap(long, conflicts_with = "names_only")]
+		tree: bool,
+
+		/// Print CSV or TSV with a header line
+		#[clap(long, conflicts_with_all = ["names_only", "tree"])]
+		format: Option<TableFormat>,
+
+		/// Also mark repositories whose registry doesn't parse
+		#[clap(long, conflicts_with_all = ["names_only", "tree"])]
+		verify: bool,
+
+		/// Print tab separated lines in a format kept stable for scripts, see the docs
+		#[clap(long, conflicts_with_all = ["names_only", "tree", "format"])]
+		porcelain: bool,
+
+		/// End porcelain lines with NUL instead of a newline
+		#[clap(short = 'z', requires = "porcelain")]
+		nul: bool,
+	},
 
 	/// Manage packages in a repository
 	#[command(arg_required_else_help = true)]
@@ -79,17 +344,168 @@ enum TopCommand {
 	#[command(arg_required_else_help = true)]
 	Type(TypeCommand),
 
+	/// Manage profiles
+	#[clap(subcommand, visible_alias = "p")]
+	#[command(arg_required_else_help = true)]
+	Profile(ProfileCommand),
+
+	/// Manage configuration files
+	#[clap(subcommand, visible_alias = "c")]
+	#[command(arg_required_else_help = true)]
+	Config(ConfigCommand),
+
+	/// Sync the configuration with a git repository
+	#[clap(subcommand)]
+	#[command(arg_required_else_help = true)]
+	ConfigSync(ConfigSyncCommand),
+
+	/// Export repositories, packages and types to one file, to move them to another machine
+	Export {
+		/// Write the export to a file instead of stdout
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+	},
+
+	/// Import an export, registering its packages as pending for `install`
+	#[command(arg_required_else_help = true)]
+	Import {
+		/// File written by `export`
+		file: PathBuf,
+
+		/// Replace entries that already exist
+		#[clap(long)]
+		overwrite: bool,
+
+		/// Keep entries that already exist
+		#[clap(long, conflicts_with = "overwrite")]
+		skip_existing: bool,
+	},
+
+	/// Move a repository to a new path
+	#[command(arg_required_else_help = true)]
+	Relocate {
+		/// Repository name
+		name: String,
+
+		/// New repository path
+		path: PathBuf,
+	},
+
+	/// Print the installed path of a package
+	#[command(arg_required_else_help = true)]
+	Which {
+		/// Repository name
+		repo: String,
+
+		/// Package name
+		package: String,
+	},
+
+	/// Show disk usage of repositories and packages
+	Du {
+		/// Repository name, omit for all repositories
+		repo: Option<String>,
+
+		/// Output as JSON
+		#[clap(long)]
+		json: bool,
+	},
+
+	/// Remove temporary files and empty directories left by interrupted operations
+	Clean {
+		/// Only list what would be removed
+		#[clap(long)]
+		dry_run: bool,
+	},
+
+	/// Check the configuration for problems
+	Doctor,
+
+	/// Print resolved paths and environment information
+	Env {
+		/// Output as JSON
+		#[clap(long)]
+		json: bool,
+	},
+
+	/// Show the journal of registry operations
+	History {
+		/// Only operations on this repository
+		#[clap(long)]
+		repo: Option<String>,
+
+		/// Only operations on this package
+		#[clap(long)]
+		package: Option<String>,
+
+		/// Number of most recent entries to show
+		#[clap(short = 'n', long, default_value_t = 20)]
+		limit: usize,
+
+		/// Output as JSON
+		#[clap(long)]
+		json: bool,
+	},
+
+	/// Reverse the most recent registry operation
+	Undo,
+
+	/// Manage command aliases
+	#[clap(subcommand)]
+	#[command(arg_required_else_help = true)]
+	Alias(AliasCommand),
+
+	/// Manage shims of package executables in the bin directory
+	#[clap(subcommand)]
+	#[command(arg_required_else_help = true)]
+	Bin(BinCommand),
+
+	/// Update gpm to the latest release
+	#[cfg(feature = "self-update")]
+	SelfUpdate {
+		/// Only report whether an update is available
+		#[clap(long)]
+		check: bool,
+	},
+
+	/// Inspect the log file
+	#[clap(subcommand)]
+	#[command(arg_required_else_help = true)]
+	Log(LogCommand),
+
 	/// Generate shell completion scripts
 	Generate {
 		/// The shell to generate the completion script for
-		shell: Shell,
+		#[clap(value_enum)]
+		shell: CompletionShell,
+
+		/// Write the script to a file instead of stdout
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+	},
+
+	/// Generate man pages for gpm and each subcommand
+	#[command(arg_required_else_help = true)]
+	GenerateMan {
+		/// Directory to write the pages to
+		#[clap(short, long)]
+		output: PathBuf,
 	},
 }
 
 #[derive(Debug, Args)]
 struct Repository {
 	/// Repository name
-	name: String,
+	#[clap(value_parser = unescape_repo_name, required_unless_present = "path")]
+	name: Option<String>,
+
+	/// Directory holding a version.toml to use without registering it, instead of a name
+	#[clap(long, value_name = "DIR", conflicts_with = "name")]
+	path: Option<PathBuf>,
+
+	/// Allow mutating a read-only repository
+	#[clap(short, long, global = true)]
+	force: bool,
 
 	#[clap(subcommand)]
 	command: RepositoryCommand,
@@ -102,17 +518,97 @@ enum RepositoryCommand {
 	#[command(arg_required_else_help = true)]
 	Add {
 		/// Package name
-		name: String,
+		#[clap(required_unless_present_any = ["spec", "from_file", "from_stdin", "name_from_arg", "pkg"])]
+		name: Option<String>,
 
-		/// Package type
-		r#type: String,
+		/// Package type, omit to be prompted for the details
+		r#type: Option<String>,
 
 		/// Args get passed to the script
 		args: Vec<String>,
 
-		/// Passing cwd to the script
+		/// Pass a directory to the script, the current one when no path is given
+		#[clap(short, long, value_name = "PATH", num_args = 0..=1)]
+		cwd: Option<Option<PathBuf>>,
+
+		/// Package description
 		#[clap(short, long)]
-		cwd: bool,
+		description: Option<String>,
+
+		/// Allow spaces and unicode in the name
+		#[clap(long)]
+		allow_weird_names: bool,
+
+		/// Fail instead of prompting when the type is omitted
+		#[clap(long)]
+		no_interactive: bool,
+
+		/// Only register the package as pending, run the script later with `install`
+		#[clap(long)]
+		register_only: bool,
+
+		/// Don't record a checksum, for packages that modify their own files
+		#[clap(long)]
+		no_verify: bool,
+
+		/// Add a package of TYPE named after the URL or path in the first of its ARGS
+		#[clap(
+			long,
+			num_args = 2..,
+			value_names = ["TYPE", "ARGS"],
+			conflicts_with_all = ["name", "type", "args", "spec", "from_file", "from_stdin"]
+		)]
+		name_from_arg: Vec<String>,
+
+		/// Add a package of `--pkg-type` given as `name=args`, repeat to add several
+		#[clap(
+			long,
+			value_name = "NAME=ARGS",
+			requires = "pkg_type",
+			conflicts_with_all = ["name", "spec", "from_file", "from_stdin", "name_from_arg"]
+		)]
+		pkg: Vec<String>,
+
+		/// Type of the packages added with `--pkg`
+		#[clap(long, value_name = "TYPE", requires = "pkg")]
+		pkg_type: Option<String>,
+
+		/// Add a package given as `name=type:args`, repeat to add several
+		#[clap(long, conflicts_with_all = ["name", "from_file", "from_stdin"])]
+		spec: Vec<String>,
+
+		/// Add the packages listed in a file, one `name<TAB>type<TAB>args` per line
+		#[clap(long, value_name = "PATH", conflicts_with_all = ["name", "from_stdin"])]
+		from_file: Option<PathBuf>,
+
+		/// Add the packages listed on stdin, in the format of `--from-file`
+		#[clap(long, conflicts_with = "name")]
+		from_stdin: bool,
+	},
+
+	/// Manage groups of packages
+	#[command(arg_required_else_help = true)]
+	Group {
+		#[command(subcommand)]
+		command: GroupCommand,
+	},
+
+	/// Set or clear the description of a package
+	#[command(arg_required_else_help = true)]
+	Describe {
+		/// Package name
+		name: String,
+
+		/// Description, omit to clear
+		description: Option<String>,
+	},
+
+	/// Show details of a package
+	#[clap(visible_alias = "s")]
+	#[command(arg_required_else_help = true)]
+	Show {
+		/// Package name
+		name: String,
 	},
 
 	/// Remove packages in the repository
@@ -120,17 +616,66 @@ enum RepositoryCommand {
 	#[command(arg_required_else_help = true)]
 	Remove {
 		/// Package names
-		#[clap(num_args = 1..)]
+		#[clap(num_args = 1.., required_unless_present_any = ["interactive", "all"])]
 		name: Vec<String>,
 
 		/// Remove registry only
 		#[clap(short, long)]
 		registry: bool,
+
+		/// Pick the packages to remove from a list
+		#[clap(short, long, conflicts_with = "name")]
+		interactive: bool,
+
+		/// Remove every package, after confirmation
+		#[clap(short, long, conflicts_with_all = ["name", "interactive"])]
+		all: bool,
+
+		/// Stop at the first failure
+		#[clap(long)]
+		fail_fast: bool,
 	},
 
 	/// Remove tag field for all packages in the repository
 	RemoveTag,
 
+	/// Set the tag of a package without running the script
+	#[command(arg_required_else_help = true)]
+	SetEtag {
+		/// Package name
+		name: String,
+
+		/// New tag
+		#[clap(required_unless_present = "clear")]
+		value: Option<String>,
+
+		/// Clear the tag instead
+		#[clap(long, conflicts_with = "value")]
+		clear: bool,
+	},
+
+	/// Label a package with tags
+	#[command(arg_required_else_help = true)]
+	Tag {
+		/// Package name
+		name: String,
+
+		/// Tags to add
+		#[clap(num_args = 1.., required = true)]
+		tags: Vec<String>,
+	},
+
+	/// Remove tags from a package
+	#[command(arg_required_else_help = true)]
+	Untag {
+		/// Package name
+		name: String,
+
+		/// Tags to remove
+		#[clap(num_args = 1.., required = true)]
+		tags: Vec<String>,
+	},
+
 	/// Update packages in the repository
 	#[clap(visible_alias = "u")]
 	#[command(arg_required_else_help = true)]
@@ -142,197 +687,1750 @@ enum RepositoryCommand {
 		/// Update all
 		#[clap(short, long)]
 		all: bool,
+
+		/// Only update packages with this tag
+		#[clap(long)]
+		tag: Option<String>,
+
+		/// Only update packages of this type, all of them when no package is named
+		#[clap(long)]
+		r#type: Option<String>,
+
+		/// Also update the packages of this group, repeatable
+		#[clap(long)]
+		group: Vec<String>,
+
+		/// Print the duration of every package, not only slow ones
+		#[clap(long)]
+		timings: bool,
+
+		/// Stop at the first failure
+		#[clap(long)]
+		fail_fast: bool,
+
+		/// Only update packages not updated within this duration, e.g. `7d` or `12h`
+		#[clap(long, value_parser = humantime::parse_duration, value_name = "DURATION")]
+		older_than: Option<Duration>,
+
+		/// Delete the packages and run their scripts from scratch
+		#[clap(long)]
+		reinstall: bool,
+
+		/// Leave these packages out of `--all`
+		#[clap(long, num_args = 1.., requires = "all", value_name = "NAME")]
+		except: Vec<String>,
 	},
 
-	/// Clone packages in the repository to the current directory
-	#[clap(visible_alias = "c")]
-	#[command(arg_required_else_help = true)]
-	Clone {
-		/// Package names
-		#[clap(num_args = 1..)]
+	/// Run the scripts of pending packages
+	Install {
+		/// Package names, omit to install all pending packages
 		name: Vec<String>,
+
+		/// Print the duration of every package, not only slow ones
+		#[clap(long)]
+		timings: bool,
+
+		/// Stop at the first failure
+		#[clap(long)]
+		fail_fast: bool,
 	},
 
-	/// List all packages in the repository
-	#[clap(visible_alias = "l")]
-	List,
-}
+	/// Update the packages whose last run failed
+	RetryFailed {
+		/// Print the duration of every package, not only slow ones
+		#[clap(long)]
+		timings: bool,
 
-#[derive(Debug, Subcommand)]
-enum TypeCommand {
-	/// Add a new package type
-	#[clap(visible_alias = "a")]
+		/// Stop at the first failure
+		#[clap(long)]
+		fail_fast: bool,
+	},
+
+	/// Show when a package was added, updated to another etag and removed
 	#[command(arg_required_else_help = true)]
-	Add {
-		/// Package type
+	History {
+		/// Package name, also of a removed package
 		name: String,
 
-		/// Script file extension
-		ext: String,
+		/// Number of most recent entries to show
+		#[clap(short = 'n', long, default_value_t = 20)]
+		limit: usize,
 
-		/// Shell to use
-		shell: String,
+		/// Output as JSON
+		#[clap(long)]
+		json: bool,
 	},
 
-	/// Remove package types
-	#[clap(visible_alias = "r")]
+	/// Count packages by state and list the failed ones
+	Status {
+		/// Print every package as tab separated lines in a format kept stable for scripts
+		#[clap(long)]
+		porcelain: bool,
+
+		/// End porcelain lines with NUL instead of a newline
+		#[clap(short = 'z', requires = "porcelain")]
+		nul: bool,
+	},
+
+	/// Report packages whose type is not in types.toml, failing when there are any
+	CheckTypes {
+		/// Change packages of type OLD to type NEW before checking, repeatable
+		#[clap(long, value_name = "OLD=NEW")]
+		reassign: Vec<String>,
+	},
+
+	/// Write a script installing the packages without gpm, from their types and args
+	ExportScript {
+		/// Language of the script, bash by default, pwsh on Windows
+		#[clap(long, value_enum)]
+		shell: Option<SetupShell>,
+
+		/// Write the script to a file instead of stdout
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+	},
+
+	/// Check installed files against the checksums recorded after the last run
 	#[command(arg_required_else_help = true)]
-	Remove {
-		/// Type names
+	Verify {
+		/// Package names
 		#[clap(num_args = 1..)]
 		name: Vec<String>,
 
-		/// Remove registry only
+		/// Verify all packages
 		#[clap(short, long)]
-		registry: bool,
+		all: bool,
 	},
 
-	/// List all package types
-	#[clap(visible_alias = "l")]
-	List,
-}
-// endregion
+	/// Show the package count and total size of the repository
+	Info {
+		/// Measure the size of every package again, also those installed before sizes were
+		/// recorded
+		#[clap(long)]
+		refresh_sizes: bool,
+	},
 
-fn get_styles() -> clap::builder::Styles {
-	clap::builder::Styles::default()
-		.usage(styling::AnsiColor::BrightGreen.on_default())
-		.header(styling::AnsiColor::BrightGreen.on_default())
-		.literal(styling::AnsiColor::BrightCyan.on_default())
-		.invalid(styling::AnsiColor::BrightYellow.on_default())
-		.error(styling::AnsiColor::BrightRed.on_default().bold())
-		.valid(styling::AnsiColor::BrightGreen.on_default())
-		.placeholder(styling::AnsiColor::Cyan.on_default())
-}
+	/// Clone packages in the repository to the current directory
+	#[clap(visible_alias = "c")]
+	#[command(arg_required_else_help = true)]
+	Clone {
+		/// Package names
+		#[clap(num_args = 1..)]
+		name: Vec<String>,
 
-/// Print an error message to stderr.
-#[macro_export]
-macro_rules! error {
-    ($msg:expr) => {
-        eprintln!("{} {}", "error:".bright_red().bold(), $msg)
-    };
-    ($fmt:expr, $($arg:tt)*) => {
-        eprintln!("{} {}", "error:".bright_red().bold(), format!($fmt, $($arg)*))
-    };
-}
+		/// Only clone packages with this tag
+		#[clap(long)]
+		tag: Option<String>,
 
-fn error_exit0<T>(msg: T)
-where
-	T: std::fmt::Display,
-{
-	error!(msg);
-	process::exit(0);
-}
+		/// Also clone the packages of this group, repeatable
+		#[clap(long)]
+		group: Vec<String>,
 
-fn main() {
-	let args = App::parse();
+		/// Stop at the first failure
+		#[clap(long)]
+		fail_fast: bool,
 
-	match args.command {
-		TopCommand::Init => {
-			if !GPM_HOME.exists() {
-				fs::create_dir(&*GPM_HOME).unwrap_or_else(error_exit0);
-			}
-			if !REPO_PATH.exists() {
-				fs::create_dir(&*REPO_PATH).unwrap_or_else(error_exit0);
-			}
+		/// Make copy-on-write copies of files on file systems supporting them
+		#[clap(long, value_enum, default_value_t = ReflinkMode::Auto)]
+		reflink: ReflinkMode,
+
+		/// Hard link files that can't be reflinked, edits then show in both places
+		#[clap(long)]
+		link_files: bool,
+	},
+
+	/// Open the repository or a package directory in the file manager
+	#[clap(visible_alias = "o")]
+	Open {
+		/// Package name, omit to open the repository
+		name: Option<String>,
+
+		/// Print the path instead of opening it
+		#[clap(short, long)]
+		print: bool,
+	},
+
+	/// Print variables holding the paths of packages
+	Env {
+		/// Package names, omit for every installed package
+		name: Vec<String>,
+
+		/// Syntax of the definitions, defaults to the shell of the platform
+		#[clap(long, value_enum)]
+		format: Option<EnvFormat>,
+
+		/// Define environment variables, e.g. `export` in a POSIX shell
+		#[clap(long)]
+		export: bool,
+
+		/// Write the definitions to a file instead of stdout
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+	},
+
+	/// Open version.toml of the repository in $EDITOR
+	EditFile,
+
+	/// Restore the registry from a backup
+	Restore {
+		/// Backup number, 1 is the newest
+		#[clap(required_unless_present = "list")]
+		index: Option<usize>,
+
+		/// List available backups
+		#[clap(short, long)]
+		list: bool,
+	},
+
+	/// List all packages in the repository
+	#[clap(visible_alias = "l")]
+	List {
+		/// Only list packages with this tag
+		#[clap(long)]
+		tag: Option<String>,
+
+		/// Only list packages of this type
+		#[clap(long)]
+		r#type: Option<String>,
+
+		/// Order of the packages
+		#[clap(long, value_enum, default_value_t = SortKey::Name)]
+		sort: SortKey,
+
+		/// Show the tag and whether the package exists on disk
+		#[clap(short, long)]
+		long: bool,
+
+		/// Print args in full instead of truncating them to the terminal width
+		#[clap(long)]
+		full: bool,
+
+		/// Output as JSON with untruncated values
+		#[clap(long, conflicts_with = "names_only")]
+		json: bool,
+
+		/// Print CSV or TSV with a header line and untruncated values
+		#[clap(long, conflicts_with_all = ["names_only", "json"])]
+		format: Option<TableFormat>,
+
+		/// Separator of the args in CSV, TSV or porcelain output [default: ;]
+		#[clap(long, value_name = "SEP")]
+		args_delimiter: Option<String>,
+
+		/// Print only sorted names, one per line
+		#[clap(long, conflicts_with = "long")]
+		names_only: bool,
+
+		/// Print tab separated lines in a format kept stable for scripts, see the docs
+		#[clap(long, conflicts_with_all = ["names_only", "json", "format", "long"])]
+		porcelain: bool,
+
+		/// End porcelain lines with NUL instead of a newline
+		#[clap(short = 'z', requires = "porcelain")]
+		nul: bool,
+	},
+}
+
+impl RepositoryCommand {
+	/// Whether the command changes the repository.
+	fn is_mutating(&self) -> bool {
+		if let RepositoryCommand::CheckTypes { reassign } = self {
+			return !reassign.is_empty();
+		}
+		!matches!(
+			self,
+			RepositoryCommand::Show { .. }
+				| RepositoryCommand::Clone { .. }
+				| RepositoryCommand::Open { .. }
+				| RepositoryCommand::List { .. }
+				| RepositoryCommand::Status { .. }
+				| RepositoryCommand::History { .. }
+				| RepositoryCommand::Env { .. }
+				| RepositoryCommand::Verify { .. }
+				| RepositoryCommand::ExportScript { .. }
+				| RepositoryCommand::Info {
+					refresh_sizes: false
+				} | RepositoryCommand::Group {
+				command: GroupCommand::List
+			}
+		)
+	}
+
+	/// Package names given to the command, looked up in the registry.
+	fn package_names(&mut self) -> Vec<&mut String> {
+		match self {
+			RepositoryCommand::Describe { name, .. }
+			| RepositoryCommand::Show { name }
+			| RepositoryCommand::History { name, .. }
+			| RepositoryCommand::SetEtag { name, .. }
+			| RepositoryCommand::Tag { name, .. }
+			| RepositoryCommand::Untag { name, .. } => vec![name],
+			RepositoryCommand::Remove { name, .. }
+			| RepositoryCommand::Update { name, .. }
+			| RepositoryCommand::Install { name, .. }
+			| RepositoryCommand::Verify { name, .. }
+			| RepositoryCommand::Clone { name, .. }
+			| RepositoryCommand::Env { name, .. }
+			| RepositoryCommand::Group {
+				command: GroupCommand::Add { name, .. } | GroupCommand::Remove { name, .. },
+			} => name.iter_mut().collect(),
+			RepositoryCommand::Open { name, .. } => name.iter_mut().collect(),
+			_ => vec![],
+		}
+	}
+}
+
+#[derive(Debug, Subcommand)]
+enum GroupCommand {
+	/// Add packages to a group, creating it
+	#[clap(visible_alias = "a")]
+	#[command(arg_required_else_help = true)]
+	Add {
+		/// Group name
+		group: String,
+
+		/// Package names
+		#[clap(num_args = 1.., required = true)]
+		name: Vec<String>,
+	},
+
+	/// Remove packages from a group
+	#[clap(visible_alias = "r")]
+	#[command(arg_required_else_help = true)]
+	Remove {
+		/// Group name
+		group: String,
+
+		/// Package names, omit to delete the group
+		name: Vec<String>,
+	},
+
+	/// List all groups with their packages
+	#[clap(visible_alias = "l")]
+	List,
+}
+
+#[derive(Debug, Subcommand)]
+enum TypeCommand {
+	/// Add a new package type
+	#[clap(visible_alias = "a")]
+	#[command(arg_required_else_help = true)]
+	Add {
+		/// Package type
+		name: String,
+
+		/// Script file extension
+		ext: String,
+
+		/// Shell to use
+		shell: String,
+
+		/// Allow spaces and unicode in the name
+		#[clap(long)]
+		allow_weird_names: bool,
+	},
+
+	/// Remove package types
+	#[clap(visible_alias = "r")]
+	#[command(arg_required_else_help = true)]
+	Remove {
+		/// Type names
+		#[clap(num_args = 1.., required_unless_present = "interactive")]
+		name: Vec<String>,
+
+		/// Remove registry only
+		#[clap(short, long)]
+		registry: bool,
+
+		/// Pick the types to remove from a list
+		#[clap(short, long, conflicts_with = "name")]
+		interactive: bool,
+	},
+
+	/// Change the extension, shell or parameters of a package type
+	#[clap(visible_alias = "e")]
+	#[command(arg_required_else_help = true)]
+	Edit {
+		/// Package type
+		name: String,
+
+		/// Script file extension, the script is renamed
+		#[clap(long)]
+		ext: Option<String>,
+
+		/// Shell to use
+		#[clap(long)]
+		shell: Option<String>,
+
+		/// Arguments the script expects in order, `name?` for optional ones, none to clear
+		#[clap(long, num_args = 0.., value_name = "PARAM")]
+		params: Option<Vec<Param>>,
+
+		/// Set an environment variable of the script, repeatable
+		#[clap(long, value_name = "KEY=VALUE", value_parser = r#type::parse_env)]
+		env: Vec<(String, String)>,
+
+		/// Remove an environment variable of the script, repeatable
+		#[clap(long, value_name = "KEY")]
+		unset_env: Vec<String>,
+
+		/// Whether the script installs outside the repository, creating nothing there
+		#[clap(long, value_name = "BOOL")]
+		no_artifact: Option<bool>,
+	},
+
+	/// List all package types
+	#[clap(visible_alias = "l")]
+	List {
+		/// Print only sorted names, one per line
+		#[clap(long)]
+		names_only: bool,
+
+		/// Include the parameters of each type
+		#[clap(short, long, conflicts_with = "names_only")]
+		long: bool,
+
+		/// Print CSV or TSV with a header line
+		#[clap(long, conflicts_with_all = ["names_only", "long"])]
+		format: Option<TableFormat>,
+
+		/// Print tab separated lines in a format kept stable for scripts, see the docs
+		#[clap(long, conflicts_with_all = ["names_only", "long", "format"])]
+		porcelain: bool,
+
+		/// End porcelain lines with NUL instead of a newline
+		#[clap(short = 'z', requires = "porcelain")]
+		nul: bool,
+	},
+
+	/// Show how the script of a type runs, failing when the script or shell is missing
+	#[command(arg_required_else_help = true)]
+	Which {
+		/// Package type
+		name: String,
+
+		/// Output as JSON
+		#[clap(long)]
+		json: bool,
+	},
+
+	/// Manage the shells running the scripts
+	#[command(subcommand, arg_required_else_help = true)]
+	Shell(ShellCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum ShellCommand {
+	/// Register a shell
+	#[clap(visible_alias = "a")]
+	#[command(arg_required_else_help = true)]
+	Add {
+		/// Shell program
+		name: String,
+
+		/// Arguments passed before the script
+		#[clap(allow_hyphen_values = true)]
+		args: Vec<String>,
+	},
+
+	/// List shells grouped by program, with the types using each
+	#[clap(visible_alias = "l")]
+	List,
+
+	/// Look for installed shells and offer to register the missing ones
+	Detect,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+	/// Create a new profile
+	#[clap(visible_alias = "c")]
+	#[command(arg_required_else_help = true)]
+	Create {
+		/// Profile name
+		name: String,
+	},
+
+	/// Remove profiles
+	#[clap(visible_alias = "r")]
+	#[command(arg_required_else_help = true)]
+	Remove {
+		/// Profile names
+		#[clap(num_args = 1..)]
+		name: Vec<String>,
+	},
+
+	/// List all profiles
+	#[clap(visible_alias = "l")]
+	List,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+	/// Open config.toml or types.toml in $EDITOR
+	Edit {
+		/// Edit types.toml instead of config.toml
+		#[clap(short, long)]
+		types: bool,
+	},
+
+	/// Restore config.toml or types.toml from a backup
+	Restore {
+		/// Backup number, 1 is the newest
+		#[clap(required_unless_present = "list")]
+		index: Option<usize>,
+
+		/// List available backups
+		#[clap(short, long)]
+		list: bool,
+
+		/// Restore types.toml instead of config.toml
+		#[clap(short, long)]
+		types: bool,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigSyncCommand {
+	/// Initialize a git repository at the gpm home directory
+	#[command(arg_required_else_help = true)]
+	Init {
+		/// Remote git URL
+		url: String,
+	},
+
+	/// Commit and push configuration changes
+	Push,
+
+	/// Pull and merge configuration changes
+	Pull,
+}
+
+#[derive(Debug, Subcommand)]
+enum BinCommand {
+	/// Create a shim for an executable of a package
+	#[command(arg_required_else_help = true)]
+	Link {
+		/// Repository name
+		repo: String,
+
+		/// Package name
+		package: String,
+
+		/// Shim name, defaults to the executable name
+		#[clap(long = "as", value_name = "NAME")]
+		name: Option<String>,
+
+		/// Executable relative to the package directory, defaults to the package itself or a file
+		/// named after it
+		#[clap(long)]
+		exe: Option<String>,
+	},
+
+	/// Remove shims
+	#[command(arg_required_else_help = true)]
+	Unlink {
+		/// Shim names
+		#[clap(num_args = 1.., required = true)]
+		name: Vec<String>,
+	},
+
+	/// List all shims
+	#[clap(visible_alias = "l")]
+	List,
+
+	/// Print the bin directory, to add to PATH
+	Path,
+}
+
+#[derive(Debug, Subcommand)]
+enum AliasCommand {
+	/// Define an alias expanding to the given arguments
+	#[command(arg_required_else_help = true)]
+	Set {
+		/// Alias name
+		name: String,
+
+		/// Arguments the alias expands to, arguments given when invoking it are appended
+		#[clap(num_args = 1.., required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+		args: Vec<String>,
+	},
+
+	/// Remove an alias
+	#[command(arg_required_else_help = true)]
+	Unset {
+		/// Alias name
+		name: String,
+	},
+
+	/// List all aliases
+	#[clap(visible_alias = "l")]
+	List,
+}
+
+#[derive(Debug, Subcommand)]
+enum LogCommand {
+	/// Print the last entries of the log file
+	Tail {
+		/// Number of entries to print
+		#[clap(short = 'n', long, default_value_t = 20)]
+		lines: usize,
+	},
+}
+// endregion
+
+fn get_styles() -> clap::builder::Styles {
+	clap::builder::Styles::default()
+		.usage(styling::AnsiColor::BrightGreen.on_default())
+		.header(styling::AnsiColor::BrightGreen.on_default())
+		.literal(styling::AnsiColor::BrightCyan.on_default())
+		.invalid(styling::AnsiColor::BrightYellow.on_default())
+		.error(styling::AnsiColor::BrightRed.on_default().bold())
+		.valid(styling::AnsiColor::BrightGreen.on_default())
+		.placeholder(styling::AnsiColor::Cyan.on_default())
+}
+
+/// Prefix of a message, `plain` in plain mode.
+pub fn label(styled: &'static str, plain: &'static str) -> &'static str {
+	if self::plain() {
+		plain
+	} else {
+		styled
+	}
+}
+
+/// Print a notice to stderr, unless quiet.
+#[macro_export]
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Normal {
+            eprintln!("{} {}", $crate::label("note:", "[note]").bright_blue().bold(), format!($($arg)*))
+        }
+    };
+}
+
+/// Print a labeled status line to stdout, unless quiet, `label` being already styled.
+#[macro_export]
+macro_rules! status {
+    ($label:expr, $($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Normal {
+            println!("{} {}", $label, format!($($arg)*))
+        }
+    };
+}
+
+/// Print details to stderr, only when verbose.
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Verbose {
+            eprintln!("{} {}", $crate::label("verbose:", "[verbose]").bright_black(), format!($($arg)*).bright_black())
+        }
+    };
+}
+
+/// Print an error message to stderr.
+#[macro_export]
+macro_rules! error {
+    ($msg:expr) => {
+        if $crate::json_errors() {
+            eprintln!("{}", $crate::config::code::to_json(&$msg.to_string(), &[]))
+        } else {
+            eprintln!("{} {}", $crate::theme::Themed::themed($crate::label("error:", "[error]"), $crate::theme::Role::Error).bold(), $msg)
+        }
+    };
+    ($fmt:expr, $($arg:tt)*) => {
+        $crate::error!(format!($fmt, $($arg)*))
+    };
+}
+
+/// Find the `--color` value before parsing, so clap's own output follows it too.
+fn color_from_args(args: &[OsString]) -> ColorChoice {
+	match alias::flag_value(args, "--color").as_deref() {
+		Some("always") => ColorChoice::Always,
+		Some("never") => ColorChoice::Never,
+		_ => ColorChoice::Auto,
+	}
+}
+
+/// Find `--ascii`, `GPM_PLAIN` or `TERM=dumb` before parsing, so clap's own output is plain too.
+fn plain_from_args(args: &[OsString]) -> bool {
+	args.iter()
+		.skip(1)
+		.take_while(|arg| *arg != "--")
+		.any(|arg| arg == "--ascii" || arg == "--plain")
+		|| env::var("GPM_PLAIN").is_ok_and(|v| !matches!(&*v, "" | "0" | "false" | "no" | "off"))
+		|| env::var("TERM").is_ok_and(|term| term == "dumb")
+}
+
+/// Names and aliases of the top level subcommands.
+fn command_names() -> Vec<String> {
+	App::command()
+		.get_subcommands()
+		.flat_map(|c| {
+			std::iter::once(c.get_name())
+				.chain(c.get_all_aliases())
+				.map(str::to_string)
+				.collect::<Vec<_>>()
+		})
+		.chain(["help".to_string()])
+		.collect()
+}
+
+/// Prefix of a repository name escaped by `escape_repo_name`.
+const REPO_NAME_ESCAPE: char = '\0';
+
+/// Escape the name in `gpm repo <NAME> ...` when it's also the name or alias of a `repo`
+/// subcommand, such as a repository named `u` or `list`, which clap would take for the
+/// subcommand.
+fn escape_repo_name(mut args: Vec<OsString>) -> Vec<OsString> {
+	let app = App::command();
+	let Some(index) = alias::positional_index(&args, 1) else {
+		return args;
+	};
+	let Some(repo) = args[index]
+		.to_str()
+		.and_then(|name| app.find_subcommand(name))
+		.filter(|c| c.get_name() == "repo")
+	else {
+		return args;
+	};
+	let Some(index) = alias::positional_index(&args, index + 1) else {
+		return args;
+	};
+	// The value of `--path` isn't a name, and no name comes with it.
+	if args[index - 1] == "--path" {
+		return args;
+	}
+	if args[index]
+		.to_str()
+		.is_some_and(|name| repo.find_subcommand(name).is_some())
+	{
+		let mut escaped = OsString::from(REPO_NAME_ESCAPE.to_string());
+		escaped.push(&args[index]);
+		args[index] = escaped;
+	}
+	args
+}
+
+fn unescape_repo_name(name: &str) -> Result<String, Infallible> {
+	Ok(name
+		.strip_prefix(REPO_NAME_ESCAPE)
+		.unwrap_or(name)
+		.to_string())
+}
+
+/// Enable or disable colored output for the whole process.
+fn set_colors(choice: ColorChoice) {
+	let enabled = !plain()
+		&& !json_errors()
+		&& match choice {
+			ColorChoice::Always => true,
+			ColorChoice::Never => false,
+			ColorChoice::Auto => {
+				env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stdout().is_terminal()
+			}
+		};
+	colored::control::set_override(enabled);
+}
+
+/// Print an error, with the chain of causes when verbose, and pick the exit code for it.
+fn report(e: anyhow::Error) -> ExitCode {
+	tracing::error!("{:#}", e);
+	if json_errors() {
+		let context: Vec<String> = e.chain().skip(1).map(ToString::to_string).collect();
+		eprintln!("{}", config::code::to_json(&e.to_string(), &context));
+	} else if verbosity() >= Verbosity::Verbose {
+		error!("{:#}", e);
+	} else {
+		error!(e);
+	}
+	ExitCode::from(exit_code(&e))
+}
+
+/// Exit code for an error, by the kind of error in its chain.
+fn exit_code(e: &anyhow::Error) -> u8 {
+	match e.chain().find_map(|c| c.downcast_ref::<PromptError>()) {
+		Some(PromptError::Interrupted) => return EXIT_INTERRUPTED,
+		Some(PromptError::Eof) => return EXIT_USAGE,
+		None => {}
+	}
+	if e.chain().any(|c| c.is::<ConfigError>()) {
+		EXIT_CONFIG
+	} else if e.chain().any(|c| c.is::<io::Error>()) {
+		EXIT_IO
+	} else {
+		EXIT_FAILURE
+	}
+}
+
+/// Print bare names one per line, for use in shell loops.
+fn print_names<'a>(names: impl IntoIterator<Item = &'a str>) {
+	for name in names {
+		println!("{}", name);
+	}
+}
+
+/// Exit code of a batch, a failure when some items of it failed.
+fn exit_status(failed: bool) -> ExitCode {
+	if failed {
+		ExitCode::from(EXIT_FAILURE)
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+fn main() -> ExitCode {
+	let raw = match alias::expand(env::args_os().collect(), &command_names()) {
+		Ok(raw) => raw,
+		Err(e) => {
+			error!(e);
+			return ExitCode::from(EXIT_USAGE);
+		}
+	};
+	let matches = App::command()
+		.color(if plain_from_args(&raw) {
+			ColorChoice::Never
+		} else {
+			color_from_args(&raw)
+		})
+		.get_matches_from(escape_repo_name(raw.clone()));
+	let args = App::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+	PLAIN
+		.set(args.ascii || env::var("TERM").is_ok_and(|term| term == "dumb"))
+		.unwrap();
+	ERRORS.set(args.errors).unwrap();
+	set_colors(args.color);
+	if let Some(profile) = &args.profile {
+		let create = matches!(
+			args.command,
+			TopCommand::Init { .. } | TopCommand::Profile(ProfileCommand::Create { .. })
+		);
+		if let Err(e) = config::profile::select(profile, create) {
+			return report(e);
+		}
+	}
+	PROFILE.set(args.profile).unwrap();
+	theme::init();
+	config::names::init();
+	LENIENT.set(args.lenient).unwrap();
+	OFFLINE.set(args.offline).unwrap();
+	VERBOSITY
+		.set(if args.quiet {
+			Verbosity::Quiet
+		} else if args.verbose {
+			Verbosity::Verbose
+		} else {
+			Verbosity::Normal
+		})
+		.unwrap();
+	util::ASSUME
+		.set(if args.yes {
+			Some(true)
+		} else if args.no {
+			Some(false)
+		} else {
+			None
+		})
+		.unwrap();
+	redact::SHOW_SECRETS.set(args.show_secrets).unwrap();
+	hook::DISABLED.set(args.no_hooks).unwrap();
+	if let Err(e) = logging::init(args.log_level) {
+		note!("logging disabled: {}", e);
+	}
+	if let Err(e) = util::handle_interrupts() {
+		note!("Ctrl+C handling disabled: {}", e);
+	}
+	tracing::info!(
+		args = ?redact::mask(
+			&raw.iter()
+				.skip(1)
+				.map(|arg| arg.to_string_lossy())
+				.collect::<Vec<_>>()
+		),
+		profile = ?PROFILE.get().and_then(Option::as_deref),
+		"invoked"
+	);
+
+	match run(args.command) {
+		// Whatever was consistent has been saved, but the command didn't finish.
+		Ok(_) if util::interrupted() => ExitCode::from(EXIT_INTERRUPTED),
+		Ok(code) => code,
+		Err(e) => report(e),
+	}
+}
+
+/// Run a command, returning the exit code, errors are rendered by `report`.
+fn run(command: TopCommand) -> Result<ExitCode> {
+	if GPM_HOME.as_os_str().is_empty() {
+		bail!("failed to find the home directory, set 'GPM_HOME' to the gpm directory");
+	}
+	match command {
+		TopCommand::Init { migrate } => {
+			if !PROFILE_ROOT.exists() {
+				fs::create_dir_all(&*PROFILE_ROOT)?;
+			}
+			if !REPO_PATH.exists() {
+				fs::create_dir(&*REPO_PATH)?;
+			}
 			if !SCRIPT_ROOT.exists() {
-				fs::create_dir(&*SCRIPT_ROOT).unwrap_or_else(error_exit0);
-			}
-		}
-		TopCommand::Add { name, path } => match Config::load() {
-			Ok(mut gpm_cfg) => {
-				gpm_cfg
-					.add(
-						name.clone(),
-						&match path {
-							Some(p) => env::current_dir().unwrap().join(p).clean(),
-							None => REPO_PATH.join(&name),
-						},
-					)
-					.unwrap_or_else(error_exit0);
-				gpm_cfg.save().unwrap_or_else(error_exit0);
-			}
-			Err(e) => error_exit0(e),
-		},
-		TopCommand::Remove { name, registry } => match Config::load() {
-			Ok(mut gpm_cfg) => {
+				fs::create_dir(&*SCRIPT_ROOT)?;
+			}
+			if TYPES_CONFIG.exists() {
+				let mut type_cfg = TypeConfig::load()?;
+				if type_cfg.upgrade_powershell()? {
+					type_cfg.save()?;
+				}
+			}
+			if migrate::detect() {
+				if migrate {
+					migrate::run()?;
+				} else {
+					note!(
+						"'{}' is from a release with namespaces, run 'gpm init --migrate' to convert it",
+						GPM_CONFIG.display().to_string().themed(Role::Warning)
+					);
+				}
+			} else if migrate {
+				note!("no configuration with namespaces found, nothing to migrate");
+			} else if GPM_CONFIG.exists() {
+				let gpm_cfg = Config::load()?;
+				for name in gpm_cfg.names() {
+					let repo_cfg_path = gpm_cfg.get_repo_path(name)?.join(REPO_CONFIG);
+					let Ok(mut repo_cfg) = RepoConfig::load(name, &repo_cfg_path) else {
+						continue;
+					};
+					let changed = repo_cfg.contract_cwds();
+					if changed > 0 {
+						repo_cfg.save(&repo_cfg_path)?;
+						note!(
+							"stored {} cwd{} of repository '{}' relative to the home directory",
+							changed,
+							if changed == 1 { "" } else { "s" },
+							name.themed(Role::Name)
+						);
+					}
+				}
+			}
+		}
+		TopCommand::Add {
+			name,
+			path,
+			description,
+			readonly,
+			allow_weird_names,
+			force,
+		} => {
+			let mut gpm_cfg = Config::load()?;
+			let path = match path {
+				Some(p) => resolve_path(&p)?,
+				None => REPO_PATH.join(&name),
+			};
+			let hooks = gpm_cfg.hooks().clone();
+			let result = gpm_cfg
+				.add(
+					name.clone(),
+					&path,
+					description,
+					readonly,
+					allow_weird_names,
+					force,
+				)
+				.and_then(|()| gpm_cfg.save());
+			hooks.run(Event::Add, result.is_ok(), &name, &[]);
+			result?;
+		}
+		TopCommand::SetReadonly { name, unset } => {
+			let mut gpm_cfg = Config::load()?;
+			gpm_cfg.set_readonly(&name, !unset)?;
+			gpm_cfg.save()?;
+		}
+		TopCommand::Describe { name, description } => {
+			let mut gpm_cfg = Config::load()?;
+			gpm_cfg.describe(&name, description)?;
+			gpm_cfg.save()?;
+		}
+		TopCommand::Remove {
+			name,
+			registry,
+			interactive,
+			all,
+		} => {
+			let mut gpm_cfg = Config::load()?;
+			let name = if interactive {
+				select_for_removal("repositories", &gpm_cfg.names())?
+			} else if all {
+				if gpm_cfg.names().is_empty() {
+					note!("no repositories registered");
+					return Ok(ExitCode::SUCCESS);
+				}
+				if !gpm_cfg.confirm_remove_all(registry)? {
+					note!("removal cancelled");
+					return Ok(ExitCode::SUCCESS);
+				}
+				gpm_cfg.names().into_iter().map(String::from).collect()
+			} else {
+				name
+			};
+			let hooks = gpm_cfg.hooks().clone();
+			let repos = name.join(" ");
+			let failed = if registry {
+				gpm_cfg.remove_registry(name);
+				false
+			} else {
+				gpm_cfg.remove(name).has_failures()
+			};
+			gpm_cfg.save()?;
+			hooks.run(Event::Remove, !failed, &repos, &[]);
+			return Ok(exit_status(failed));
+		}
+		TopCommand::List {
+			names_only,
+			tree,
+			format,
+			verify,
+			porcelain,
+			nul,
+		} => {
+			let gpm_cfg = Config::load()?;
+			if names_only {
+				print_names(gpm_cfg.names());
+			} else if porcelain {
+				print!("{}", gpm_cfg.porcelain(Porcelain { nul }, verify));
+			} else if let Some(format) = format {
+				print!("{}", gpm_cfg.table(format, verify));
+			} else if tree {
+				print!("{}", gpm_cfg.tree());
+			} else {
+				print!("{}", gpm_cfg.list(verify));
+			}
+		}
+		TopCommand::Repo(repo) => return run_repo(repo),
+		TopCommand::Type(t) => match t {
+			TypeCommand::Add {
+				name,
+				ext,
+				shell,
+				allow_weird_names,
+			} => {
+				let mut type_cfg = TypeConfig::load()?;
+				type_cfg.add(name, ext, shell, allow_weird_names)?;
+				type_cfg.save()?;
+			}
+			TypeCommand::Remove {
+				name,
+				registry,
+				interactive,
+			} => {
+				let mut type_cfg = TypeConfig::load()?;
+				let name = if interactive {
+					select_for_removal("types", &type_cfg.names())?
+				} else {
+					name
+				};
 				if registry {
-					gpm_cfg.remove_registry(name);
+					type_cfg.remove_registry(name);
+				} else {
+					type_cfg.remove(name);
+				}
+				type_cfg.save()?;
+			}
+			TypeCommand::Edit {
+				name,
+				ext,
+				shell,
+				params,
+				env,
+				unset_env,
+				no_artifact,
+			} => {
+				let mut type_cfg = TypeConfig::load()?;
+				let edit = TypeEdit {
+					ext,
+					shell,
+					params,
+					env,
+					unset_env,
+					no_artifact,
+				};
+				type_cfg.modify(&name, edit)?;
+				type_cfg.save()?;
+			}
+			TypeCommand::List {
+				names_only,
+				long,
+				format,
+				porcelain,
+				nul,
+			} => {
+				let type_cfg = TypeConfig::load()?;
+				if names_only {
+					print_names(type_cfg.names());
+				} else if porcelain {
+					print!("{}", type_cfg.porcelain(Porcelain { nul }));
+				} else if let Some(format) = format {
+					print!("{}", type_cfg.table(format));
+				} else {
+					print!("{}", type_cfg.list(long));
+				}
+			}
+			TypeCommand::Which { name, json } => {
+				let (report, ok) = TypeConfig::load()?.which(&name, json)?;
+				print!("{}", report);
+				return Ok(exit_status(!ok));
+			}
+			TypeCommand::Shell(ShellCommand::Add { name, args }) => {
+				let mut type_cfg = TypeConfig::load()?;
+				type_cfg.add_shell(name, args)?;
+				type_cfg.save()?;
+			}
+			TypeCommand::Shell(ShellCommand::List) => {
+				print!("{}", TypeConfig::load()?.shell_list());
+			}
+			TypeCommand::Shell(ShellCommand::Detect) => {
+				let mut type_cfg = TypeConfig::load()?;
+				if type_cfg.detect()? {
+					type_cfg.save()?;
+				}
+			}
+		},
+		TopCommand::Profile(p) => match p {
+			ProfileCommand::Create { name } => config::profile::create(&name)?,
+			ProfileCommand::Remove { name } => config::profile::remove(name),
+			ProfileCommand::List => print!("{}", config::profile::list()?),
+		},
+		TopCommand::Config(c) => match c {
+			ConfigCommand::Edit { types } => {
+				if types {
+					TypeConfig::edit()?
 				} else {
-					gpm_cfg.remove(name);
+					Config::edit()?
 				}
-				gpm_cfg.save().unwrap_or_else(error_exit0);
 			}
-			Err(e) => error_exit0(e),
+			ConfigCommand::Restore { index, list, types } => {
+				let path = if types { &*TYPES_CONFIG } else { &*GPM_CONFIG };
+				restore_backup(path, index, list)?;
+			}
 		},
-		TopCommand::List => match Config::load() {
-			Ok(gpm_cfg) => print!("{}", gpm_cfg),
-			Err(e) => error_exit0(e),
+		TopCommand::ConfigSync(c) => match c {
+			ConfigSyncCommand::Init { url } => config::sync::init(&url)?,
+			ConfigSyncCommand::Push => config::sync::push()?,
+			ConfigSyncCommand::Pull => config::sync::pull()?,
 		},
-		TopCommand::Repo(repo) => {
-			let repo_cfg_path = &config::main::get_repo_path(&repo.name).join(REPO_CONFIG);
-			match RepoConfig::load(repo_cfg_path) {
-				Ok(mut repo_cfg) => {
-					match repo.command {
-						RepositoryCommand::Add {
-							name,
-							r#type,
-							args,
-							cwd,
-						} => repo_cfg
-							.add(name, r#type, args.into_boxed_slice(), cwd)
-							.unwrap_or_else(error_exit0),
-						RepositoryCommand::Remove { name, registry } => {
-							if registry {
-								repo_cfg.remove_registry(name);
-							} else {
-								repo_cfg.remove(name)
-							}
-						}
-						RepositoryCommand::RemoveTag => repo_cfg.remove_tag(),
-						RepositoryCommand::Update { name, all } => {
-							if all {
-								repo_cfg.update_all();
-							} else {
-								repo_cfg.update(name);
-							}
-						}
-						RepositoryCommand::Clone { name } => repo_cfg.copy(name),
-						RepositoryCommand::List => {
-							print!("{}", repo_cfg);
-							return;
-						}
+		TopCommand::Export { output } => config::export::export(output.as_deref())?,
+		TopCommand::Import {
+			file,
+			overwrite,
+			skip_existing,
+		} => {
+			let conflict = if overwrite {
+				Conflict::Overwrite
+			} else if skip_existing {
+				Conflict::Skip
+			} else {
+				Conflict::Report
+			};
+			let failed = config::export::import(&file, conflict)?.has_failures();
+			return Ok(exit_status(failed));
+		}
+		TopCommand::Relocate { name, path } => {
+			Config::load()?.relocate(&name, &resolve_path(&path)?)?;
+		}
+		TopCommand::Which { repo, package } => {
+			let path = Config::load()?.get_repo_path(&repo)?.join(REPO_CONFIG);
+			println!(
+				"{}",
+				RepoConfig::load(&repo, &path)?
+					.package_path(&package)?
+					.display()
+			);
+		}
+		TopCommand::Du { repo, json } => {
+			print!("{}", du::report(&Config::load()?, repo.as_deref(), json)?);
+		}
+		TopCommand::Clean { dry_run } => clean::run(&Config::load()?, dry_run)?,
+		TopCommand::Doctor => {
+			let gpm_cfg = Config::load()?;
+			let mut problems = gpm_cfg.check();
+			problems.extend(TypeConfig::load()?.check());
+			for name in gpm_cfg.names() {
+				let path = gpm_cfg.get_repo_path(name)?.join(REPO_CONFIG);
+				match RepoConfig::load(name, &path) {
+					Ok(repo_cfg) => problems.extend(repo_cfg.check()),
+					Err(e) => {
+						problems.push(format!("repository '{}' {}", name.themed(Role::Warning), e))
 					}
-					repo_cfg.save(repo_cfg_path).unwrap_or_else(error_exit0);
 				}
-				Err(e) => error_exit0(e),
+			}
+			for problem in &problems {
+				error!(problem);
+			}
+			if problems.is_empty() {
+				println!("{}", "No problems found".themed(Role::Success));
 			}
 		}
-		TopCommand::Type(t) => match t {
-			TypeCommand::Add { name, ext, shell } => match TypeConfig::load() {
-				Ok(mut type_cfg) => {
-					type_cfg.add(name, ext, shell).unwrap_or_else(error_exit0);
-					type_cfg.save().unwrap_or_else(error_exit0);
+		TopCommand::Env { json } => print!("{}", environment::report(json)?),
+		TopCommand::Alias(a) => {
+			let mut gpm_cfg = Config::load()?;
+			match a {
+				AliasCommand::Set { name, args } => {
+					gpm_cfg.set_alias(name, args, &command_names())?
 				}
-				Err(e) => error_exit0(e),
-			},
-			TypeCommand::Remove { name, registry } => match TypeConfig::load() {
-				Ok(mut type_cfg) => {
-					if registry {
-						type_cfg.remove_registry(name);
-					} else {
-						type_cfg.remove(name);
-					}
-					type_cfg.save().unwrap_or_else(error_exit0);
+				AliasCommand::Unset { name } => gpm_cfg.unset_alias(&name)?,
+				AliasCommand::List => {
+					print!("{}", gpm_cfg.aliases());
+					return Ok(ExitCode::SUCCESS);
+				}
+			}
+			gpm_cfg.save()?;
+		}
+		TopCommand::Bin(b) => {
+			let mut shim_cfg = ShimConfig::load()?;
+			match b {
+				BinCommand::Link {
+					repo,
+					package,
+					name,
+					exe,
+				} => shim_cfg.link(&repo, &package, name, exe.as_deref())?,
+				BinCommand::Unlink { name } => shim_cfg.unlink(&name),
+				BinCommand::List => {
+					print!("{}", shim_cfg.list()?);
+					return Ok(ExitCode::SUCCESS);
 				}
-				Err(e) => error_exit0(e),
-			},
-			TypeCommand::List => match TypeConfig::load() {
-				Ok(type_cfg) => print!("{}", type_cfg),
-				Err(e) => error_exit0(e),
-			},
+				BinCommand::Path => {
+					println!("{}", BIN_PATH.display());
+					return Ok(ExitCode::SUCCESS);
+				}
+			}
+			shim_cfg.save()?;
+		}
+		#[cfg(feature = "self-update")]
+		TopCommand::SelfUpdate { check } => self_update::run(check)?,
+		TopCommand::History {
+			repo,
+			package,
+			limit,
+			json,
+		} => {
+			let filter = HistoryFilter {
+				repo: repo.as_deref(),
+				package: package.as_deref(),
+				limit,
+			};
+			if json {
+				print!("{}", config::history::list_json(&filter)?);
+			} else {
+				print!("{}", config::history::list(&filter)?);
+			}
+		}
+		TopCommand::Undo => config::history::undo()?,
+		TopCommand::Log(l) => match l {
+			LogCommand::Tail { lines } => print!("{}", logging::tail(lines)?),
+		},
+		TopCommand::Generate { shell, output } => match output {
+			Some(path) => {
+				let mut script = vec![];
+				completion::generate(shell, &mut App::command(), &mut script);
+				if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+					fs::create_dir_all(parent)?;
+				}
+				fs::write(&path, script)?;
+				note!("{}", completion::source_hint(shell, &path));
+			}
+			None => completion::generate(shell, &mut App::command(), &mut io::stdout()),
 		},
-		TopCommand::Generate { shell } => {
-			clap_complete::generate(shell, &mut App::command(), "gpm", &mut io::stdout())
+		TopCommand::GenerateMan { output } => {
+			for path in completion::generate_man(App::command(), &output)? {
+				add!("{}", path.display());
+			}
 		}
 	}
+	Ok(ExitCode::SUCCESS)
+}
+
+/// List the backups of `path` or restore backup `index`.
+fn restore_backup(path: &Path, index: Option<usize>, list: bool) -> Result<()> {
+	if list {
+		print!("{}", config::backup::list(path)?);
+	} else if let Some(index) = index {
+		config::backup::restore(path, index)?;
+	}
+	Ok(())
+}
+
+/// Name and registry path of the repository in `dir`, which isn't registered in config.toml.
+///
+/// The name, the directory name, only appears in messages and the history journal.
+fn unregistered_repo(dir: &Path) -> Result<(String, PathBuf)> {
+	let dir = resolve_path(dir)?;
+	let path = dir.join(REPO_CONFIG);
+	if !path.is_file() {
+		bail!(
+			"{} '{}' is not a repository, it has no {}",
+			Code::RepoNotFound,
+			dir.display().to_string().themed(Role::Warning),
+			REPO_CONFIG
+		);
+	}
+	let name = dir.file_name().map_or_else(
+		|| dir.display().to_string(),
+		|n| n.to_string_lossy().into_owned(),
+	);
+	Ok((name, path))
+}
+
+/// Run a `repo` subcommand.
+fn run_repo(mut repo: Repository) -> Result<ExitCode> {
+	let gpm_cfg = Config::load()?;
+	let unregistered = repo.path.is_some();
+	let (repo_name, repo_cfg_path) = match (&repo.path, &repo.name) {
+		(Some(dir), _) => unregistered_repo(dir)?,
+		(None, Some(name)) => {
+			// Names keep the spelling they were registered with, even when matched regardless of
+			// case.
+			let name = gpm_cfg.registered(name);
+			let path = gpm_cfg.get_repo_path(&name)?.join(REPO_CONFIG);
+			(name, path)
+		}
+		(None, None) => bail!("the repository name is required"),
+	};
+	let repo_cfg_path = &repo_cfg_path;
+	if repo.command.is_mutating() && !repo.force && !unregistered && gpm_cfg.is_readonly(&repo_name)
+	{
+		bail!(
+			"{} repository '{}' is read-only, pass '--force' to modify it",
+			Code::RepoReadonly,
+			repo_name.themed(Role::Warning)
+		);
+	}
+	let mut repo_cfg = match RepoConfig::load(&repo_name, repo_cfg_path) {
+		Ok(repo_cfg) => repo_cfg,
+		// Editing and restoring must work even when the registry no longer loads.
+		Err(e) => {
+			match repo.command {
+				RepositoryCommand::EditFile => RepoConfig::edit(repo_cfg_path)?,
+				RepositoryCommand::Restore { index, list } => {
+					restore_backup(repo_cfg_path, index, list)?
+				}
+				_ => return Err(e),
+			}
+			return Ok(ExitCode::SUCCESS);
+		}
+	};
+	for name in repo.command.package_names() {
+		*name = repo_cfg.registered(name);
+	}
+	let mut failed = false;
+	// Hook to run once the registry is saved, with the packages it concerns.
+	let mut hook = None;
+	match repo.command {
+		RepositoryCommand::Add {
+			name,
+			r#type,
+			args,
+			cwd,
+			description,
+			allow_weird_names,
+			no_interactive,
+			register_only,
+			no_verify,
+			name_from_arg,
+			..
+		} if name.is_some() || !name_from_arg.is_empty() => {
+			let (name, r#type, args) = match (name, name_from_arg.split_first()) {
+				(Some(name), _) => (name, r#type, args),
+				(None, Some((r#type, args))) => {
+					let Some(first) = args.first() else {
+						bail!("'--name-from-arg' needs an arg to infer the package name from");
+					};
+					let inferred = spec::infer_name(first)?;
+					note!(
+						"inferred package name '{}' from '{}'",
+						inferred.themed(Role::Name),
+						first
+					);
+					(inferred, Some(r#type.clone()), args.to_vec())
+				}
+				(None, None) => bail!("the package name is required"),
+			};
+			let cwd = cwd.map(|path| resolve_cwd(path.as_deref())).transpose()?;
+			let input = match r#type {
+				Some(r#type) => wizard::PackageInput {
+					r#type,
+					args,
+					cwd,
+					description,
+				},
+				None if no_interactive || !io::stdin().is_terminal() => {
+					bail!("the package type is required when not prompting")
+				}
+				None => wizard::run(&repo_name, &name)?,
+			};
+			let result = repo_cfg.add(
+				name.clone(),
+				input.r#type,
+				input.args.into_boxed_slice(),
+				input.cwd,
+				input.description,
+				AddOptions {
+					allow_weird_names,
+					register_only,
+					no_verify,
+				},
+			);
+			if let Err(e) = result {
+				gpm_cfg.hooks().run(Event::Add, false, &repo_name, &[name]);
+				return Err(e);
+			}
+			hook = Some((Event::Add, vec![name]));
+		}
+		RepositoryCommand::Add {
+			cwd,
+			allow_weird_names,
+			register_only,
+			no_verify,
+			pkg,
+			pkg_type,
+			spec,
+			from_file,
+			..
+		} => {
+			let cwd = cwd.map(|path| resolve_cwd(path.as_deref())).transpose()?;
+			// Lists skip what's already registered, specs fail like the positional form.
+			let (specs, skip_existing) = if let Some(pkg_type) = pkg_type {
+				let specs = pkg
+					.iter()
+					.enumerate()
+					.map(|(i, p)| spec::parse_pkg(p, &pkg_type, cwd.as_deref(), i + 1))
+					.collect::<Result<_>>()?;
+				(specs, false)
+			} else if spec.is_empty() {
+				(spec::read(from_file.as_deref())?, true)
+			} else {
+				let specs = spec
+					.iter()
+					.enumerate()
+					.map(|(i, s)| spec::parse_compact(s, i + 1))
+					.collect::<Result<_>>()?;
+				(specs, false)
+			};
+			let options = AddOptions {
+				allow_weird_names,
+				register_only,
+				no_verify,
+			};
+			let (summary, added) = repo_cfg.add_batch(specs, options, skip_existing)?;
+			summary.print();
+			failed = summary.has_failures();
+			if !added.is_empty() {
+				hook = Some((Event::Add, added));
+			}
+		}
+		RepositoryCommand::Group { command } => match command {
+			GroupCommand::Add { group, name } => repo_cfg.group_add(&group, name)?,
+			GroupCommand::Remove { group, name } => repo_cfg.group_remove(&group, name)?,
+			GroupCommand::List => {
+				print!("{}", repo_cfg.group_list()?);
+				return Ok(ExitCode::SUCCESS);
+			}
+		},
+		RepositoryCommand::Describe { name, description } => {
+			repo_cfg.describe(&name, description)?
+		}
+		RepositoryCommand::Show { name } => {
+			print!("{}", repo_cfg.show(&name)?);
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::Remove {
+			name,
+			registry,
+			interactive,
+			all,
+			fail_fast,
+		} => {
+			let names = repo_cfg.names(&ListOptions::default());
+			let name = if interactive {
+				select_for_removal("packages", &names)?
+			} else if all {
+				if names.is_empty() {
+					note!("no packages in '{}'", repo_name.themed(Role::Name));
+					return Ok(ExitCode::SUCCESS);
+				}
+				if !repo_cfg.confirm_remove_all(registry)? {
+					note!("removal cancelled");
+					return Ok(ExitCode::SUCCESS);
+				}
+				names.into_iter().map(String::from).collect()
+			} else {
+				name
+			};
+			hook = Some((Event::Remove, name.clone()));
+			if registry {
+				repo_cfg.remove_registry(name);
+			} else {
+				failed = repo_cfg.remove(name, fail_fast).has_failures();
+			}
+		}
+		RepositoryCommand::RemoveTag => repo_cfg.remove_tag(),
+		RepositoryCommand::SetEtag { name, value, .. } => repo_cfg.set_tag(&name, value)?,
+		RepositoryCommand::Tag { name, tags } => repo_cfg.tag(&name, tags)?,
+		RepositoryCommand::Untag { name, tags } => repo_cfg.untag(&name, tags)?,
+		RepositoryCommand::Update {
+			name,
+			all,
+			tag,
+			r#type,
+			group,
+			timings,
+			fail_fast,
+			older_than,
+			reinstall,
+			except,
+		} => {
+			let name = repo_cfg.with_groups(name, &group)?;
+			// `None` selects all packages.
+			let mut selected = match &tag {
+				Some(tag) => Some(repo_cfg.filter_tag(if all { vec![] } else { name }, tag)),
+				None if all || (r#type.is_some() && name.is_empty()) => None,
+				None => Some(name),
+			};
+			if let Some(r#type) = &r#type {
+				selected = Some(repo_cfg.filter_type(selected, r#type)?);
+			}
+			if let Some(age) = older_than {
+				selected = Some(repo_cfg.filter_stale(selected, age));
+			}
+			let mut excluded = vec![];
+			if !except.is_empty() {
+				let (kept, left_out) = repo_cfg.filter_except(selected, &except);
+				selected = Some(kept);
+				excluded = left_out;
+			}
+			if all
+				&& gpm_cfg.confirms_update_all(&repo_name)
+				&& !repo_cfg.confirm_update_all(selected.as_deref())?
+			{
+				note!("update cancelled");
+				return Ok(ExitCode::SUCCESS);
+			}
+			let options = UpdateOptions {
+				timings,
+				fail_fast,
+				reinstall,
+			};
+			let packages = match &selected {
+				Some(name) => name.clone(),
+				None => repo_cfg
+					.names(&ListOptions::default())
+					.into_iter()
+					.map(String::from)
+					.collect(),
+			};
+			hook = Some((Event::Update, packages));
+			let summary = match selected {
+				Some(name) if !except.is_empty() => {
+					repo_cfg.update_excluding(name, excluded, options)
+				}
+				Some(name) => repo_cfg.update(name, options),
+				None => repo_cfg.update_all(options),
+			};
+			failed = summary.has_failures();
+		}
+		RepositoryCommand::Install {
+			name,
+			timings,
+			fail_fast,
+		} => {
+			let name = if name.is_empty() {
+				repo_cfg.with_state(State::Pending)
+			} else {
+				name
+			};
+			if name.is_empty() {
+				note!("no pending packages");
+				return Ok(ExitCode::SUCCESS);
+			}
+			let options = UpdateOptions {
+				timings,
+				fail_fast,
+				..Default::default()
+			};
+			hook = Some((Event::Update, name.clone()));
+			failed = repo_cfg.update(name, options).has_failures();
+		}
+		RepositoryCommand::RetryFailed { timings, fail_fast } => {
+			let name = repo_cfg.with_state(State::Failed);
+			if name.is_empty() {
+				note!("no failed packages");
+				return Ok(ExitCode::SUCCESS);
+			}
+			let options = UpdateOptions {
+				timings,
+				fail_fast,
+				..Default::default()
+			};
+			hook = Some((Event::Update, name.clone()));
+			failed = repo_cfg.update(name, options).has_failures();
+		}
+		RepositoryCommand::Env {
+			name,
+			format,
+			export,
+			output,
+		} => {
+			let vars = repo_cfg.env(name, format.unwrap_or_else(EnvFormat::native), export)?;
+			match output {
+				Some(path) => {
+					fs::write(&path, vars)?;
+					note!("wrote '{}'", path.display());
+				}
+				None => print!("{}", vars),
+			}
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::Info { refresh_sizes } => {
+			if !refresh_sizes {
+				print!("{}", repo_cfg.info()?);
+				return Ok(ExitCode::SUCCESS);
+			}
+			repo_cfg.refresh_sizes();
+			print!("{}", repo_cfg.info()?);
+		}
+		RepositoryCommand::Verify { name, all } => {
+			let name = if all { vec![] } else { name };
+			return Ok(exit_status(repo_cfg.verify(name).has_failures()));
+		}
+		RepositoryCommand::History { name, limit, json } => {
+			if json {
+				print!(
+					"{}",
+					config::history::package_json(&repo_name, &name, limit)?
+				);
+			} else {
+				print!("{}", config::history::package(&repo_name, &name, limit)?);
+			}
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::Status { porcelain, nul } => {
+			if porcelain {
+				print!("{}", repo_cfg.status_porcelain(Porcelain { nul }));
+			} else {
+				print!("{}", repo_cfg.status()?);
+			}
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::ExportScript { shell, output } => {
+			setup::export(
+				&repo_name,
+				&repo_cfg,
+				shell.unwrap_or_default(),
+				output.as_deref(),
+			)?;
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::CheckTypes { reassign } => {
+			for pair in &reassign {
+				let Some((old, new)) = pair.split_once('=') else {
+					bail!(
+						"invalid reassignment '{}', expected OLD=NEW",
+						pair.themed(Role::Warning)
+					);
+				};
+				repo_cfg.reassign_type(old, new)?;
+			}
+			let (report, missing) = repo_cfg.check_types()?;
+			print!("{}", report);
+			if reassign.is_empty() {
+				return Ok(exit_status(missing));
+			}
+			failed = missing;
+		}
+		RepositoryCommand::Clone {
+			name,
+			tag,
+			group,
+			fail_fast,
+			reflink,
+			link_files,
+		} => {
+			let name = repo_cfg.with_groups(name, &group)?;
+			let copier = Copier::new(reflink, link_files);
+			let summary = match tag {
+				Some(tag) => repo_cfg.copy(repo_cfg.filter_tag(name, &tag), fail_fast, &copier),
+				None => repo_cfg.copy(name, fail_fast, &copier),
+			};
+			return Ok(exit_status(summary.has_failures()));
+		}
+		RepositoryCommand::Open { name, print } => {
+			let path = match name {
+				Some(name) => repo_cfg.package_path(&name)?,
+				None if repo_cfg.path().exists() => repo_cfg.path().to_path_buf(),
+				None => bail!(
+					"repository directory '{}' does not exist",
+					repo_cfg.path().display()
+				),
+			};
+			if print {
+				println!("{}", path.display());
+			} else {
+				open_in_file_manager(&path)?;
+			}
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::List {
+			tag,
+			r#type,
+			sort,
+			long,
+			full,
+			json,
+			format,
+			args_delimiter,
+			names_only,
+			porcelain,
+			nul,
+		} => {
+			if args_delimiter.is_some() && format.is_none() && !porcelain {
+				bail!("'--args-delimiter' only applies to '--format' and '--porcelain'");
+			}
+			let options = ListOptions {
+				tag: tag.as_deref(),
+				r#type: r#type.as_deref(),
+				sort,
+				long,
+				full,
+			};
+			if names_only {
+				print_names(repo_cfg.names(&options));
+			} else if porcelain {
+				let delimiter = args_delimiter.as_deref().unwrap_or(";");
+				print!(
+					"{}",
+					repo_cfg.list_porcelain(&options, Porcelain { nul }, delimiter)
+				);
+			} else if json {
+				print!("{}", repo_cfg.list_json(&options)?);
+			} else if let Some(format) = format {
+				let delimiter = args_delimiter.as_deref().unwrap_or(";");
+				print!("{}", repo_cfg.list_table(&options, format, delimiter));
+			} else {
+				print!("{}", repo_cfg.list(&options));
+			}
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::EditFile => {
+			RepoConfig::edit(repo_cfg_path)?;
+			return Ok(ExitCode::SUCCESS);
+		}
+		RepositoryCommand::Restore { index, list } => {
+			restore_backup(repo_cfg_path, index, list)?;
+			return Ok(ExitCode::SUCCESS);
+		}
+	}
+	repo_cfg.save(repo_cfg_path)?;
+	if let Some((event, packages)) = hook {
+		gpm_cfg.hooks().run(event, !failed, &repo_name, &packages);
+	}
+	Ok(exit_status(failed))
 }
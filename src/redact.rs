@@ -0,0 +1,172 @@
+//! Masking of secrets in arguments before they are printed or persisted.
+
+use crate::config::main::Config;
+
+use once_cell::sync::{Lazy, OnceCell};
+
+/// Replacement of a redacted value.
+const MASK: &str = "***";
+/// Flags whose value is redacted when `redact_flags` isn't configured, `*` matches anything.
+const DEFAULT_FLAGS: [&str; 6] = [
+	"-token",
+	"--token",
+	"-password",
+	"--password",
+	"--auth*",
+	"--*secret*",
+];
+/// Keys of `key=value` pairs, also inside URLs, whose long values are redacted.
+const SECRET_KEYS: [&str; 7] = [
+	"token",
+	"access_token",
+	"password",
+	"secret",
+	"sig",
+	"signature",
+	"apikey",
+];
+/// Parts of environment variable names whose values are redacted, ignoring case.
+const SECRET_ENV_PARTS: [&str; 6] = ["token", "password", "secret", "key", "auth", "credential"];
+/// Values shorter than this after a secret key are left alone, they are unlikely to be secrets.
+const MIN_SECRET_LEN: usize = 16;
+
+/// Print secrets as they are. Set once in `main`.
+pub static SHOW_SECRETS: OnceCell<bool> = OnceCell::new();
+
+static FLAGS: Lazy<Vec<String>> = Lazy::new(|| {
+	Config::load()
+		.ok()
+		.and_then(|cfg| cfg.redact_flags().map(<[String]>::to_vec))
+		.unwrap_or_else(|| DEFAULT_FLAGS.map(String::from).to_vec())
+});
+
+/// Whether `text` matches `pattern`, where `*` matches any sequence of characters, ignoring case.
+fn matches(pattern: &str, text: &str) -> bool {
+	let (pattern, text) = (pattern.to_lowercase(), text.to_lowercase());
+	let mut parts = pattern.split('*');
+	let first = parts.next().unwrap_or_default();
+	let Some(mut rest) = text.strip_prefix(first) else {
+		return false;
+	};
+	let parts: Vec<&str> = parts.collect();
+	let Some((last, middle)) = parts.split_last() else {
+		return rest.is_empty();
+	};
+	for part in middle {
+		match rest.find(part) {
+			Some(i) => rest = &rest[i + part.len()..],
+			None => return false,
+		}
+	}
+	rest.len() >= last.len() && rest.ends_with(last)
+}
+
+fn is_secret_flag(flag: &str) -> bool {
+	FLAGS.iter().any(|pattern| matches(pattern, flag))
+}
+
+/// Mask long values of secret keys in `key=value` pairs, such as the query of a signed URL.
+fn mask_pairs(arg: &str) -> String {
+	let mut out = String::with_capacity(arg.len());
+	let mut rest = arg;
+	while let Some(eq) = rest.find('=') {
+		let key_start = rest[..eq]
+			.rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+			.map_or(0, |i| i + 1);
+		let key = &rest[key_start..eq];
+		let value_len = rest[eq + 1..]
+			.find(['&', ';', ' '])
+			.unwrap_or(rest.len() - eq - 1);
+		let value = &rest[eq + 1..eq + 1 + value_len];
+		out.push_str(&rest[..=eq]);
+		let secret = SECRET_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key))
+			&& value.len() >= MIN_SECRET_LEN
+			&& value
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || "+/=-_.%".contains(c));
+		out.push_str(if secret { MASK } else { value });
+		rest = &rest[eq + 1 + value_len..];
+	}
+	out.push_str(rest);
+	out
+}
+
+/// Value of environment variable `key` to print, masked when the name looks like a secret's
+/// unless `--show-secrets` is given.
+pub fn env_value(key: &str, value: &str) -> String {
+	let key = key.to_lowercase();
+	let secret = SECRET_ENV_PARTS.iter().any(|part| key.contains(part));
+	if secret && !SHOW_SECRETS.get().copied().unwrap_or_default() {
+		MASK.to_string()
+	} else {
+		value.to_string()
+	}
+}
+
+/// Whether some of `args` were masked by `mask`.
+pub fn is_masked<S: AsRef<str>>(args: &[S]) -> bool {
+	args.iter().any(|arg| arg.as_ref().contains(MASK))
+}
+
+/// Copy of `args` to print, with secrets masked unless `--show-secrets` is given.
+pub fn args<S: AsRef<str>>(args: &[S]) -> Vec<String> {
+	if SHOW_SECRETS.get().copied().unwrap_or_default() {
+		args.iter().map(|a| a.as_ref().to_string()).collect()
+	} else {
+		mask(args)
+	}
+}
+
+/// Copy of `args` with secret values replaced by `***`, always, for what is persisted.
+///
+/// Values following a flag that matches `redact_flags` in config.toml are masked, in both the
+/// `--flag value` and `--flag=value` forms, as are long values of keys like `token=`.
+pub fn mask<S: AsRef<str>>(args: &[S]) -> Vec<String> {
+	let mut masked = vec![];
+	let mut after_flag = false;
+	for arg in args.iter().map(AsRef::as_ref) {
+		if after_flag {
+			masked.push(MASK.to_string());
+			after_flag = false;
+			continue;
+		}
+		match arg.split_once('=') {
+			Some((flag, _)) if flag.starts_with('-') && is_secret_flag(flag) => {
+				masked.push(format!("{}={}", flag, MASK))
+			}
+			None if arg.starts_with('-') && is_secret_flag(arg) => {
+				masked.push(arg.to_string());
+				after_flag = true;
+			}
+			_ => masked.push(mask_pairs(arg)),
+		}
+	}
+	masked
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn flag_patterns() {
+		assert!(matches("--auth*", "--auth-header"));
+		assert!(matches("--*secret*", "--client-SECRET-file"));
+		assert!(matches("-token", "-Token"));
+		assert!(!matches("-token", "-tokens"));
+		assert!(!matches("--auth*", "--oauth"));
+	}
+
+	#[test]
+	fn secret_pairs() {
+		assert_eq!(
+			mask_pairs("https://host/f.zip?sig=0123456789abcdef0123&se=2024"),
+			"https://host/f.zip?sig=***&se=2024"
+		);
+		assert_eq!(mask_pairs("token=short"), "token=short");
+		assert_eq!(
+			mask_pairs("name=0123456789abcdef0123"),
+			"name=0123456789abcdef0123"
+		);
+	}
+}
@@ -0,0 +1,152 @@
+//! Updating gpm itself from the GitHub releases, built with the `self-update` feature.
+
+use crate::checksum;
+use crate::config::repository::styled_progress_bar;
+use crate::theme::{Role, Themed};
+use crate::{add, status};
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use semver::Version;
+use serde::Deserialize;
+use std::env::{self, consts::EXE_SUFFIX};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/8LWXpg/gpm/releases/latest";
+
+#[derive(Deserialize)]
+struct Release {
+	tag_name: String,
+	assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+	name: String,
+	browser_download_url: String,
+}
+
+impl Release {
+	fn asset(&self, name: &str) -> Option<&Asset> {
+		self.assets.iter().find(|a| a.name == name)
+	}
+}
+
+/// Download `url`, with a progress bar when the size is known.
+fn download(url: &str) -> Result<Vec<u8>> {
+	let response = ureq::get(url)
+		.set("User-Agent", "gpm")
+		.call()
+		.with_context(|| format!("failed to download '{}'", url))?;
+	let progress = response
+		.header("Content-Length")
+		.and_then(|len| len.parse().ok())
+		.and_then(|len| {
+			styled_progress_bar(len, "[{bar:30}] {bytes}/{total_bytes} {bytes_per_sec}")
+		});
+	let mut bytes = vec![];
+	match &progress {
+		Some(bar) => bar
+			.wrap_read(response.into_reader())
+			.read_to_end(&mut bytes)?,
+		None => response.into_reader().read_to_end(&mut bytes)?,
+	};
+	if let Some(bar) = progress {
+		bar.finish_and_clear();
+	}
+	Ok(bytes)
+}
+
+/// Extract the gpm executable from a release archive.
+fn extract(archive: &[u8]) -> Result<Vec<u8>> {
+	let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+	let binary = format!("gpm{}", EXE_SUFFIX);
+	for i in 0..zip.len() {
+		let mut file = zip.by_index(i)?;
+		if file.is_file()
+			&& file.enclosed_name().and_then(|p| p.file_name()) == Some(binary.as_ref())
+		{
+			let mut bytes = vec![];
+			file.read_to_end(&mut bytes)?;
+			return Ok(bytes);
+		}
+	}
+	bail!("'{}' not found in the release archive", binary)
+}
+
+/// Update the running executable to the latest release, or only report it when `check_only`.
+pub fn run(check_only: bool) -> Result<()> {
+	crate::ensure_online("self-update")?;
+	let current = Version::parse(env!("CARGO_PKG_VERSION"))?;
+	let release: Release = ureq::get(LATEST_RELEASE)
+		.set("User-Agent", "gpm")
+		.set("Accept", "application/vnd.github+json")
+		.call()
+		.context("failed to fetch the latest release")?
+		.into_json()?;
+	let latest = Version::parse(release.tag_name.trim_start_matches('v'))
+		.with_context(|| format!("invalid release version '{}'", release.tag_name))?;
+	if latest <= current {
+		println!("gpm {} is up to date", current);
+		return Ok(());
+	}
+	if check_only {
+		println!(
+			"gpm {} is available, current version is {}",
+			latest.to_string().themed(Role::Success),
+			current
+		);
+		return Ok(());
+	}
+
+	let name = format!("gpm-{}.zip", env!("TARGET"));
+	let Some(asset) = release.asset(&name) else {
+		bail!("release {} has no asset for '{}'", latest, env!("TARGET"));
+	};
+	let Some(checksum) = release.asset(&format!("{}.sha256", name)) else {
+		bail!(
+			"release {} has no checksum for '{}', refusing to update",
+			latest,
+			name
+		);
+	};
+	status!(
+		"downloading:".bright_blue(),
+		"{}",
+		asset.browser_download_url
+	);
+	let archive = download(&asset.browser_download_url)?;
+	let expected = String::from_utf8(download(&checksum.browser_download_url)?)?;
+	let expected = expected.split_whitespace().next().unwrap_or_default();
+	let actual = checksum::sha256_hex(&archive);
+	if !actual.eq_ignore_ascii_case(expected) {
+		bail!(
+			"checksum mismatch for '{}', expected {} but got {}",
+			name,
+			expected,
+			actual
+		);
+	}
+
+	let binary = extract(&archive)?;
+	let temp = env::current_exe()?.with_file_name(format!(".gpm-update{}", EXE_SUFFIX));
+	let mut file = fs::File::create(&temp)?;
+	file.write_all(&binary)?;
+	drop(file);
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		fs::set_permissions(&temp, fs::Permissions::from_mode(0o755))?;
+	}
+	// Renames the running executable out of the way first where it can't be overwritten.
+	let replaced = self_replace::self_replace(&temp);
+	fs::remove_file(&temp)?;
+	replaced?;
+	add!(
+		"gpm\t{} -> {}",
+		current,
+		latest.to_string().themed(Role::Success)
+	);
+	Ok(())
+}
@@ -0,0 +1,163 @@
+//! Colors of the roles in the output, configured in the `[theme]` section of config.toml.
+
+use crate::config::util::raw_config;
+
+use colored::{Color, ColoredString, Colorize};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+/// Color names accepted in `[theme]`, `normal` leaves the text uncolored.
+const COLORS: [(&str, Option<Color>); 19] = [
+	("normal", None),
+	("black", Some(Color::Black)),
+	("red", Some(Color::Red)),
+	("green", Some(Color::Green)),
+	("yellow", Some(Color::Yellow)),
+	("blue", Some(Color::Blue)),
+	("purple", Some(Color::Magenta)),
+	("magenta", Some(Color::Magenta)),
+	("cyan", Some(Color::Cyan)),
+	("white", Some(Color::White)),
+	("bright_black", Some(Color::BrightBlack)),
+	("bright_red", Some(Color::BrightRed)),
+	("bright_green", Some(Color::BrightGreen)),
+	("bright_yellow", Some(Color::BrightYellow)),
+	("bright_blue", Some(Color::BrightBlue)),
+	("bright_purple", Some(Color::BrightMagenta)),
+	("bright_magenta", Some(Color::BrightMagenta)),
+	("bright_cyan", Some(Color::BrightCyan)),
+	("bright_white", Some(Color::BrightWhite)),
+];
+
+/// Theme in use. Set once in `main`.
+static THEME: OnceCell<Theme> = OnceCell::new();
+
+/// What a piece of colored output is.
+#[derive(Debug, Clone, Copy)]
+pub enum Role {
+	/// Names of repositories, packages, shims and aliases
+	Name,
+	/// Types and groups
+	Type,
+	Path,
+	/// Errors and failed items
+	Error,
+	/// Warnings and the values highlighted in messages
+	Warning,
+	/// Headers and succeeded items
+	Success,
+	/// Questions asked before acting
+	Prompt,
+}
+
+/// A color by its name in config.toml, stored as its index in `COLORS`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ThemeColor(usize);
+
+impl ThemeColor {
+	fn named(name: &str) -> Option<Self> {
+		COLORS.iter().position(|(n, _)| *n == name).map(Self)
+	}
+
+	fn color(self) -> Option<Color> {
+		COLORS[self.0].1
+	}
+}
+
+impl TryFrom<String> for ThemeColor {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		match Self::named(&value) {
+			Some(color) => Ok(color),
+			None => Err(format!(
+				"unknown color '{}', supported colors: {}",
+				value,
+				COLORS.map(|(name, _)| name).join(", ")
+			)),
+		}
+	}
+}
+
+impl From<ThemeColor> for String {
+	fn from(color: ThemeColor) -> Self {
+		COLORS[color.0].0.to_string()
+	}
+}
+
+/// Colors of the roles, unset roles keep the default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	name: Option<ThemeColor>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	r#type: Option<ThemeColor>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	path: Option<ThemeColor>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<ThemeColor>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	warning: Option<ThemeColor>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	success: Option<ThemeColor>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	prompt: Option<ThemeColor>,
+}
+
+impl Theme {
+	pub fn is_default(&self) -> bool {
+		*self == Self::default()
+	}
+
+	fn color(&self, role: Role) -> Option<Color> {
+		let (configured, default) = match role {
+			Role::Name => (self.name, "bright_cyan"),
+			Role::Type => (self.r#type, "bright_purple"),
+			Role::Path => (self.path, "normal"),
+			Role::Error => (self.error, "bright_red"),
+			Role::Warning => (self.warning, "bright_yellow"),
+			Role::Success => (self.success, "bright_green"),
+			Role::Prompt => (self.prompt, "normal"),
+		};
+		configured
+			.or_else(|| ThemeColor::named(default))
+			.and_then(ThemeColor::color)
+	}
+}
+
+/// Read the theme of the selected profile, the default if config.toml doesn't parse.
+pub fn init() {
+	let theme = raw_config()
+		.and_then(|mut table| table.remove("theme"))
+		.and_then(|theme| theme.try_into().ok())
+		.unwrap_or_default();
+	THEME.set(theme).unwrap();
+}
+
+/// Style text by its role in the configured theme.
+pub trait Themed: Colorize + Sized {
+	fn themed(self, role: Role) -> ColoredString {
+		match THEME.get_or_init(Theme::default).color(role) {
+			Some(color) => self.color(color),
+			None => self.normal(),
+		}
+	}
+}
+
+impl<T: Colorize> Themed for T {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn color_names() {
+		let color = ThemeColor::try_from("bright_purple".to_string()).unwrap();
+		assert_eq!(color.color(), Some(Color::BrightMagenta));
+		assert_eq!(ThemeColor::named("normal").unwrap().color(), None);
+		let error = ThemeColor::try_from("orange".to_string()).unwrap_err();
+		assert!(error.starts_with("unknown color 'orange', supported colors: normal, black"));
+	}
+}
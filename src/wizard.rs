@@ -0,0 +1,115 @@
+//! Interactive prompts for `repo add` when no type is given.
+
+use crate::config::r#type::{self, TypeConfig};
+use crate::config::util::{prompt, read_input, shell_quote};
+use crate::theme::{Role, Themed};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// Package details collected by the wizard.
+pub struct PackageInput {
+	pub r#type: String,
+	pub args: Vec<String>,
+	pub cwd: Option<PathBuf>,
+	pub description: Option<String>,
+}
+
+/// Leading comment lines of a script, skipping the shebang, used as its documentation.
+fn script_header(path: &Path) -> Vec<String> {
+	let Ok(content) = fs::read_to_string(path) else {
+		return vec![];
+	};
+	content
+		.lines()
+		.skip_while(|line| line.starts_with("#!"))
+		.map_while(|line| {
+			let line = line.trim();
+			["#", "//", "::", "REM ", "rem "]
+				.iter()
+				.find_map(|prefix| line.strip_prefix(prefix))
+				.map(|rest| rest.trim().to_string())
+		})
+		.collect()
+}
+
+/// Ask for the type, args, cwd flag and description of package `name`.
+pub fn run(repo: &str, name: &str) -> Result<PackageInput> {
+	let type_cfg = TypeConfig::load()?;
+	let types = type_cfg.names();
+	if types.is_empty() {
+		bail!("no types registered, add one with 'gpm type add'");
+	}
+	println!("{}", "Types:".themed(Role::Success));
+	for (i, t) in types.iter().enumerate() {
+		println!("  {}\t{}", i + 1, t.themed(Role::Name));
+	}
+	let r#type = loop {
+		let input = read_input("Type (number or name)")?;
+		let selected = match input.parse::<usize>() {
+			Ok(i) => i.checked_sub(1).and_then(|i| types.get(i)),
+			Err(_) => types.iter().find(|t| **t == input),
+		};
+		match selected {
+			Some(t) => break t.to_string(),
+			None => eprintln!("{} '{}'", "unknown type".themed(Role::Error), input),
+		}
+	};
+
+	let params = type_cfg.params(&r#type);
+	if !params.is_empty() {
+		println!(
+			"{} {}",
+			"Usage:".themed(Role::Success),
+			r#type::usage(params)
+		);
+	}
+	if let Some(script) = type_cfg.script_path(&r#type) {
+		let header = script_header(&script);
+		if !header.is_empty() {
+			println!("{}", "Parameters:".themed(Role::Success));
+			for line in header {
+				println!("  {}", line);
+			}
+		}
+	}
+
+	let mut args = vec![];
+	loop {
+		let label = match params.get(args.len()) {
+			Some(param) => format!("Arg {} {}", args.len() + 1, param.usage()),
+			None if !params.is_empty() => break,
+			None => format!("Arg {} (empty to finish)", args.len() + 1),
+		};
+		let arg = read_input(&label)?;
+		if arg.is_empty() {
+			break;
+		}
+		args.push(arg);
+	}
+	type_cfg.check_args(&r#type, &args)?;
+	let cwd = prompt("Pass the current directory to the script?")?
+		.then(env::current_dir)
+		.transpose()?;
+	let description = Some(read_input("Description (optional)")?).filter(|d| !d.is_empty());
+
+	let mut command = vec!["gpm", "repo", repo, "add", name, &r#type];
+	command.extend(args.iter().map(String::as_str));
+	if cwd.is_some() {
+		command.push("--cwd");
+	}
+	if let Some(description) = &description {
+		command.extend(["--description", description]);
+	}
+	let command: Vec<String> = command.into_iter().map(shell_quote).collect();
+	println!("{} {}", "command:".bright_blue(), command.join(" "));
+
+	Ok(PackageInput {
+		r#type,
+		args,
+		cwd,
+		description,
+	})
+}
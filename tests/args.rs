@@ -0,0 +1,126 @@
+mod common;
+
+use common::Gpm;
+use std::env;
+use std::fs;
+
+/// Args that shells would split, expand or run if they were passed unquoted.
+const ARGS: [&str; 11] = [
+	"a b",
+	"it's",
+	"say \"hi\"",
+	"$HOME",
+	"100%",
+	"a&b",
+	"line1\nline2",
+	"*.zip",
+	"https://example.com/?q=1&r=2",
+	r"back\slash",
+	"",
+];
+
+/// Shell entries with their arguments, and the extension and script writing the args it gets
+/// to `<NAME>/args`, each followed by a NUL.
+#[cfg(not(windows))]
+const SHELLS: [(&str, &[&str], &str, &str); 5] = [
+	("sh", &[], "sh", ECHO_ARGS),
+	("bash", &[], "sh", ECHO_ARGS),
+	("bash:c", &["-c"], "sh", ECHO_ARGS),
+	("dash:c", &["-c"], "sh", ECHO_ARGS),
+	("zsh:c", &["-c"], "sh", ECHO_ARGS),
+];
+#[cfg(not(windows))]
+const ECHO_ARGS: &str = r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+	case "$1" in
+		-n) name=$2; shift 2 ;;
+		-t|-d) shift 2 ;;
+		*) break ;;
+	esac
+done
+mkdir -p "$name"
+for arg in "$@"; do printf '%s\0' "$arg"; done > "$name/args"
+echo v1
+"#;
+#[cfg(windows)]
+const SHELLS: [(&str, &[&str], &str, &str); 2] = [
+	(
+		"powershell",
+		&["-NoProfile", "-ExecutionPolicy", "Bypass", "-Command"],
+		"ps1",
+		ECHO_ARGS,
+	),
+	(
+		"pwsh",
+		&["-NoProfile", "-ExecutionPolicy", "Bypass", "-Command"],
+		"ps1",
+		ECHO_ARGS,
+	),
+];
+#[cfg(windows)]
+const ECHO_ARGS: &str = r#"param([string]$n, [string]$t, [string]$d)
+$dir = New-Item -ItemType Directory -Force -Path $n
+[IO.File]::WriteAllText((Join-Path $dir.FullName 'args'), -join ($args | ForEach-Object { "$_`0" }))
+Write-Output v1
+"#;
+
+/// Whether `program` is found in `PATH`.
+fn installed(program: &str) -> bool {
+	let names = [program.to_string(), format!("{}.exe", program)];
+	env::var_os("PATH").is_some_and(|path| {
+		env::split_paths(&path).any(|dir| names.iter().any(|name| dir.join(name).is_file()))
+	})
+}
+
+#[test]
+fn args_reach_scripts_unchanged() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	let mut types = gpm.toml("types.toml");
+	let shells = types["shell"].as_table_mut().unwrap();
+	for (shell, args, ..) in SHELLS {
+		shells.insert(shell.into(), args.to_vec().into());
+	}
+	fs::write(gpm.home().join("types.toml"), types.to_string()).unwrap();
+
+	let repo = gpm.home().join("repositories").join("tools");
+	for (i, (shell, _, ext, script)) in SHELLS.into_iter().enumerate() {
+		let program = shell.split(':').next().unwrap();
+		if !installed(program) {
+			eprintln!("skipped '{}', not installed", shell);
+			continue;
+		}
+		let r#type = format!("echo-{}", i);
+		gpm.run(&["type", "add", &r#type, ext, shell]);
+		let path = gpm
+			.home()
+			.join("scripts")
+			.join(format!("{}.{}", r#type, ext));
+		fs::write(&path, script).unwrap();
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+		}
+
+		let name = format!("pkg-{}", i);
+		let mut add = vec!["repo", "tools", "add", &name, &r#type];
+		add.extend(ARGS);
+		gpm.run(&add);
+		let received = fs::read_to_string(repo.join(&name).join("args")).unwrap();
+		let received: Vec<&str> = received.split_terminator('\0').collect();
+		assert_eq!(received, ARGS, "args passed by '{}'", shell);
+
+		let registry = gpm.toml("repositories/tools/version.toml");
+		let stored: Vec<&str> = registry["packages"][&name]["args"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|arg| arg.as_str().unwrap())
+			.collect();
+		assert_eq!(stored, ARGS);
+	}
+
+	let list = gpm.stdout(&["repo", "tools", "list"]);
+	assert!(list.contains(r#"'a b' 'it'\''s' 'say "hi"' '$HOME' 100%"#));
+}
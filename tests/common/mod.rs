@@ -0,0 +1,178 @@
+//! Harness running the gpm binary against a temporary `GPM_HOME`.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+/// Type registered by `Gpm::with_fake_type`, its script records each invocation.
+pub const FAKE_TYPE: &str = "fake";
+
+/// Space separated names of the packages the fake type script fails for, see `Gpm::failing`.
+const FAKE_FAIL: &str = "GPM_TEST_FAIL";
+
+/// Extension, shell and script of the fake type, with the arguments of the shell.
+///
+/// Appends `name=<NAME> tag=<TAG> args=<ARGS>` to `$GPM_HOME/invocations.log`, creates the
+/// package directory with an `args` file and prints the next tag, `v1`, `v2`, ...
+///
+/// For the packages named in `FAKE_FAIL` it exits with 1 afterwards.
+#[cfg(not(windows))]
+const FAKE_SCRIPT: (&str, &str, &[&str], &str) = (
+	"sh",
+	"sh",
+	&[],
+	r#"#!/bin/sh
+name= tag=
+while [ $# -gt 0 ]; do
+	case "$1" in
+		-n) name=$2; shift 2 ;;
+		-t) tag=$2; shift 2 ;;
+		-d) shift 2 ;;
+		*) break ;;
+	esac
+done
+echo "name=$name tag=$tag args=$*" >> "$GPM_HOME/invocations.log"
+mkdir -p "$name"
+echo "$*" > "$name/args"
+echo "v$(( ${tag#v} + 1 ))"
+case " $GPM_TEST_FAIL " in
+	*" $name "*) exit 1 ;;
+esac
+"#,
+);
+#[cfg(windows)]
+const FAKE_SCRIPT: (&str, &str, &[&str], &str) = (
+	"ps1",
+	"powershell",
+	&["-c"],
+	r#"param([string]$n, [string]$t, [string]$d)
+Add-Content -Path (Join-Path $env:GPM_HOME 'invocations.log') -Value "name=$n tag=$t args=$($args -join ' ')"
+New-Item -ItemType Directory -Force -Path $n | Out-Null
+Set-Content -Path (Join-Path $n 'args') -Value ($args -join ' ')
+$next = if ($t) { [int]$t.TrimStart('v') + 1 } else { 1 }
+Write-Output "v$next"
+if (" $env:GPM_TEST_FAIL " -like "* $n *") { exit 1 }
+"#,
+);
+
+/// A temporary gpm home with a working directory for commands.
+pub struct Gpm {
+	dir: TempDir,
+}
+
+impl Gpm {
+	/// An empty home, nothing is initialized.
+	pub fn new() -> Self {
+		let dir = tempfile::tempdir().expect("failed to create temporary directory");
+		fs::create_dir(dir.path().join("work")).unwrap();
+		Self { dir }
+	}
+
+	/// An initialized home with the fake type registered.
+	pub fn with_fake_type() -> Self {
+		let gpm = Self::new();
+		let (ext, shell, shell_args, script) = FAKE_SCRIPT;
+		gpm.run(&["init"]);
+		let mut shells = toml::Table::new();
+		shells.insert(shell.into(), shell_args.to_vec().into());
+		let mut types = toml::Table::new();
+		types.insert("shell".into(), shells.into());
+		fs::write(gpm.home().join("types.toml"), types.to_string()).unwrap();
+		gpm.run(&["type", "add", FAKE_TYPE, ext, shell]);
+		let path = gpm
+			.home()
+			.join("scripts")
+			.join(format!("{}.{}", FAKE_TYPE, ext));
+		fs::write(&path, script).unwrap();
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+		}
+		gpm
+	}
+
+	/// The `GPM_HOME` passed to gpm.
+	pub fn home(&self) -> PathBuf {
+		self.dir.path().join("gpm")
+	}
+
+	/// Working directory of commands, e.g. where packages are cloned to.
+	pub fn work(&self) -> PathBuf {
+		self.dir.path().join("work")
+	}
+
+	/// A gpm command isolated from the user's environment, with stdin closed.
+	pub fn command(&self, args: &[&str]) -> Command {
+		let mut cmd = Command::new(env!("CARGO_BIN_EXE_gpm"));
+		cmd.args(args)
+			.current_dir(self.work())
+			.env("GPM_HOME", self.home())
+			.env("HOME", self.dir.path())
+			.env("NO_COLOR", "1")
+			.env_remove("GPM_PROFILE")
+			.env_remove("GPM_LENIENT")
+			.env_remove("GPM_LOG_LEVEL")
+			.env_remove(FAKE_FAIL)
+			.stdin(Stdio::null());
+		cmd
+	}
+
+	/// Run gpm, panicking with its output unless it succeeds.
+	pub fn run(&self, args: &[&str]) -> Output {
+		let output = self.command(args).output().expect("failed to run gpm");
+		assert!(
+			output.status.success(),
+			"gpm {:?} failed with {}\nstdout:\n{}\nstderr:\n{}",
+			args,
+			output.status,
+			String::from_utf8_lossy(&output.stdout),
+			String::from_utf8_lossy(&output.stderr)
+		);
+		output
+	}
+
+	/// Run gpm and return its exit code.
+	pub fn code(&self, args: &[&str]) -> i32 {
+		let output = self.command(args).output().expect("failed to run gpm");
+		output.status.code().expect("gpm was killed by a signal")
+	}
+
+	/// Run gpm with the fake type script failing for `packages`, returning the exit code and
+	/// stdout.
+	pub fn failing(&self, packages: &[&str], args: &[&str]) -> (i32, String) {
+		let output = self
+			.command(args)
+			.env(FAKE_FAIL, packages.join(" "))
+			.output()
+			.expect("failed to run gpm");
+		(
+			output.status.code().expect("gpm was killed by a signal"),
+			String::from_utf8(output.stdout).unwrap(),
+		)
+	}
+
+	/// Run gpm and return its stdout.
+	pub fn stdout(&self, args: &[&str]) -> String {
+		String::from_utf8(self.run(args).stdout).unwrap()
+	}
+
+	/// Invocations of the fake type script, oldest first.
+	pub fn invocations(&self) -> Vec<String> {
+		fs::read_to_string(self.home().join("invocations.log"))
+			.unwrap_or_default()
+			.lines()
+			.map(str::to_string)
+			.collect()
+	}
+
+	/// Parse a TOML file relative to the home.
+	pub fn toml(&self, path: impl AsRef<Path>) -> toml::Table {
+		let path = self.home().join(path);
+		let content = fs::read_to_string(&path)
+			.unwrap_or_else(|e| panic!("failed to read '{}': {}", path.display(), e));
+		content.parse().unwrap()
+	}
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{Gpm, FAKE_TYPE};
+use std::fs;
+
+/// Code of the last JSON error gpm printed for `args`.
+fn code(gpm: &Gpm, args: &[&str]) -> String {
+	let mut full = vec!["--errors", "json"];
+	full.extend(args);
+	let output = gpm.command(&full).output().unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	let line = stderr
+		.lines()
+		.rev()
+		.find(|line| line.starts_with('{'))
+		.unwrap_or_else(|| panic!("gpm {:?} printed no JSON error:\n{}", args, stderr));
+	let error: serde_json::Value = serde_json::from_str(line).unwrap();
+	assert!(!error["message"].as_str().unwrap().contains("[E"));
+	error["code"].as_str().unwrap_or("none").to_string()
+}
+
+#[test]
+fn errors_have_stable_codes() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE]);
+
+	assert_eq!(code(&gpm, &["repo", "missing", "list"]), "E001");
+	assert_eq!(code(&gpm, &["add", "tools", "--force"]), "E002");
+	assert_eq!(
+		code(&gpm, &["repo", "tools", "describe", "nope", "x"]),
+		"E004"
+	);
+	assert_eq!(
+		code(&gpm, &["repo", "tools", "add", "pkg", FAKE_TYPE]),
+		"E005"
+	);
+	assert_eq!(
+		code(&gpm, &["repo", "tools", "group", "remove", "nope", "pkg"]),
+		"E006"
+	);
+	assert_eq!(
+		code(&gpm, &["repo", "tools", "add", "other", "nope"]),
+		"E010"
+	);
+	assert_eq!(code(&gpm, &["type", "add", FAKE_TYPE, "sh", "sh"]), "E011");
+	assert_eq!(
+		code(&gpm, &["type", "edit", FAKE_TYPE, "--shell", "nope"]),
+		"E012"
+	);
+	assert_eq!(code(&gpm, &["type", "add", "other", "sh", "nope"]), "E012");
+	assert_eq!(code(&gpm, &["add", "a/b"]), "E030");
+
+	gpm.run(&["type", "edit", FAKE_TYPE, "--params", "url"]);
+	assert_eq!(
+		code(&gpm, &["repo", "tools", "add", "other", FAKE_TYPE]),
+		"E013"
+	);
+	gpm.run(&["type", "edit", FAKE_TYPE, "--params"]);
+
+	let mut types = gpm.toml("types.toml");
+	types["shell"]
+		.as_table_mut()
+		.unwrap()
+		.insert("gpm-missing-shell".into(), toml::Value::Array(vec![]));
+	fs::write(gpm.home().join("types.toml"), types.to_string()).unwrap();
+	gpm.run(&["type", "edit", FAKE_TYPE, "--shell", "gpm-missing-shell"]);
+	assert_eq!(code(&gpm, &["repo", "tools", "update", "pkg"]), "E020");
+
+	gpm.run(&["set-readonly", "tools"]);
+	assert_eq!(code(&gpm, &["repo", "tools", "remove", "pkg"]), "E003");
+
+	fs::write(gpm.home().join("config.toml"), "repositories = [").unwrap();
+	assert_eq!(code(&gpm, &["list"]), "E040");
+}
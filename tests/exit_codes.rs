@@ -0,0 +1,51 @@
+mod common;
+
+use common::{Gpm, FAKE_TYPE};
+use std::fs;
+
+#[test]
+fn errors_map_to_exit_codes() {
+	let gpm = Gpm::with_fake_type();
+	assert_eq!(gpm.code(&["list"]), 0);
+	assert_eq!(gpm.code(&["repo", "missing", "list"]), 1);
+	assert_eq!(gpm.code(&["no-such-command"]), 2);
+
+	fs::write(gpm.home().join("config.toml"), "repositories = [").unwrap();
+	assert_eq!(gpm.code(&["list"]), 3);
+}
+
+#[test]
+fn invalid_types_are_config_errors() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	let types = gpm.home().join("types.toml");
+	let content = fs::read_to_string(&types).unwrap();
+	fs::write(&types, format!("bogus = 1\n{}", content)).unwrap();
+
+	let output = gpm.command(&["repo", "tools", "list"]).output().unwrap();
+	assert_eq!(output.status.code(), Some(3));
+	assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+	assert_eq!(gpm.code(&["add", "other"]), 3);
+}
+
+#[test]
+fn prompts_without_input_fail_unless_answered() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE]);
+	let config = gpm.home().join("config.toml");
+	let content = fs::read_to_string(&config).unwrap();
+	fs::write(&config, format!("confirm_update_all = true\n{}", content)).unwrap();
+
+	let output = gpm
+		.command(&["repo", "tools", "update", "--all"])
+		.output()
+		.unwrap();
+	assert_eq!(output.status.code(), Some(2));
+	assert!(String::from_utf8_lossy(&output.stderr).contains("pass --yes or --no"));
+	assert_eq!(gpm.code(&["repo", "tools", "update", "--all", "--no"]), 0);
+	assert_eq!(gpm.invocations().len(), 1);
+
+	gpm.run(&["repo", "tools", "update", "--all", "--yes"]);
+	assert_eq!(gpm.invocations().len(), 2);
+}
@@ -0,0 +1,640 @@
+mod common;
+
+use common::{Gpm, FAKE_TYPE};
+use std::fs;
+
+#[test]
+fn package_lifecycle() {
+	let gpm = Gpm::with_fake_type();
+	let home = gpm.home();
+	assert!(home.join("repositories").is_dir());
+	assert!(home.join("scripts").is_dir());
+
+	gpm.run(&["add", "tools"]);
+	let repo = home.join("repositories").join("tools");
+	let config = gpm.toml("config.toml");
+	assert_eq!(
+		config["repositories"]["tools"]["path"].as_str(),
+		repo.to_str()
+	);
+	assert!(repo.join("version.toml").is_file());
+
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE, "one", "two"]);
+	assert_eq!(gpm.invocations(), ["name=pkg tag= args=one two"]);
+	let package = &gpm.toml("repositories/tools/version.toml")["packages"]["pkg"];
+	assert_eq!(package["type"].as_str(), Some(FAKE_TYPE));
+	assert_eq!(
+		package["args"].as_array().unwrap(),
+		&vec!["one".into(), "two".into()]
+	);
+	assert_eq!(package["tag"].as_str(), Some("v1"));
+	assert_eq!(
+		fs::read_to_string(repo.join("pkg").join("args"))
+			.unwrap()
+			.trim(),
+		"one two"
+	);
+
+	gpm.run(&["repo", "tools", "update", "pkg"]);
+	assert_eq!(
+		gpm.invocations().last().unwrap(),
+		"name=pkg tag=v1 args=one two"
+	);
+	let version = gpm.toml("repositories/tools/version.toml");
+	assert_eq!(version["packages"]["pkg"]["tag"].as_str(), Some("v2"));
+
+	gpm.run(&["repo", "tools", "clone", "pkg"]);
+	assert!(gpm.work().join("pkg").join("args").is_file());
+	assert!(repo.join("pkg").is_dir());
+
+	gpm.run(&["repo", "tools", "remove", "pkg"]);
+	assert!(!repo.join("pkg").exists());
+	let version = gpm.toml("repositories/tools/version.toml");
+	assert!(version["packages"].as_table().unwrap().is_empty());
+
+	gpm.run(&["remove", "tools"]);
+	assert!(!repo.exists());
+	let config = gpm.toml("config.toml");
+	assert!(config
+		.get("repositories")
+		.and_then(|r| r.as_table())
+		.is_none_or(|r| r.is_empty()));
+	assert_eq!(gpm.invocations().len(), 2);
+}
+
+#[test]
+fn registry_removal_is_undone() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "a", FAKE_TYPE, "x"]);
+	gpm.run(&["repo", "tools", "add", "b", FAKE_TYPE, "y"]);
+
+	gpm.run(&["repo", "tools", "remove", "--registry", "a", "b"]);
+	let version = gpm.toml("repositories/tools/version.toml");
+	assert!(version["packages"].as_table().unwrap().is_empty());
+	assert!(gpm.home().join("repositories/tools/a").is_dir());
+
+	gpm.run(&["undo"]);
+	let version = gpm.toml("repositories/tools/version.toml");
+	let packages = version["packages"].as_table().unwrap();
+	assert_eq!(packages["a"]["args"].as_array().unwrap(), &vec!["x".into()]);
+	assert_eq!(packages["b"]["tag"].as_str(), Some("v1"));
+
+	let history = gpm.stdout(&["history", "--package", "a", "--json"]);
+	let history: serde_json::Value = serde_json::from_str(&history).unwrap();
+	let operations: Vec<_> = history
+		.as_array()
+		.unwrap()
+		.iter()
+		.map(|e| e["operation"].as_str().unwrap())
+		.collect();
+	assert_eq!(operations, ["add", "remove-registry"]);
+
+	gpm.run(&[
+		"repo", "tools", "add", "c", FAKE_TYPE, "--", "--token", "s3cret",
+	]);
+	gpm.run(&["repo", "tools", "remove", "--registry", "c"]);
+	let journal = fs::read_to_string(gpm.home().join("history.jsonl")).unwrap();
+	assert!(!journal.contains("s3cret"));
+
+	// The masked args can't be restored, so the package isn't either.
+	assert_eq!(gpm.code(&["undo"]), 1);
+	let version = gpm.toml("repositories/tools/version.toml");
+	assert!(version["packages"].get("c").is_none());
+	assert!(
+		!fs::read_to_string(gpm.home().join("repositories/tools/version.toml"))
+			.unwrap()
+			.contains("***")
+	);
+}
+
+#[test]
+fn type_params_are_checked() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["type", "edit", FAKE_TYPE, "--params", "url", "pattern?"]);
+
+	let output = gpm
+		.command(&["repo", "tools", "add", "pkg", FAKE_TYPE])
+		.output()
+		.unwrap();
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).contains("requires <url>"));
+	assert_eq!(
+		gpm.code(&["repo", "tools", "add", "pkg", FAKE_TYPE, "a", "b", "c"]),
+		1
+	);
+	assert!(gpm.invocations().is_empty());
+
+	gpm.run(&[
+		"repo",
+		"tools",
+		"add",
+		"pkg",
+		FAKE_TYPE,
+		"https://example.com",
+	]);
+	assert_eq!(gpm.invocations().len(), 1);
+	assert!(gpm
+		.stdout(&["type", "list", "--long"])
+		.contains("<url> [pattern]"));
+}
+
+#[test]
+fn registered_packages_install_later() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&[
+		"repo",
+		"tools",
+		"add",
+		"a",
+		FAKE_TYPE,
+		"x",
+		"--register-only",
+	]);
+	gpm.run(&[
+		"repo",
+		"tools",
+		"add",
+		"b",
+		FAKE_TYPE,
+		"y",
+		"--register-only",
+	]);
+	assert!(gpm.invocations().is_empty());
+	let version = gpm.toml("repositories/tools/version.toml");
+	assert_eq!(version["packages"]["a"]["state"].as_str(), Some("pending"));
+	assert!(version["packages"]["a"].get("tag").is_none());
+
+	gpm.run(&["repo", "tools", "update", "a"]);
+	gpm.run(&["repo", "tools", "install"]);
+	assert_eq!(
+		gpm.invocations(),
+		["name=a tag= args=x", "name=b tag= args=y"]
+	);
+	let version = gpm.toml("repositories/tools/version.toml");
+	for name in ["a", "b"] {
+		assert!(version["packages"][name].get("state").is_none());
+		assert_eq!(version["packages"][name]["tag"].as_str(), Some("v1"));
+	}
+}
+
+#[cfg(not(windows))]
+#[test]
+fn scripts_must_produce_files() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	let script = gpm.home().join("scripts").join(format!("{}.sh", FAKE_TYPE));
+	fs::write(&script, "#!/bin/sh\necho v1\n").unwrap();
+
+	let output = gpm
+		.command(&["repo", "tools", "add", "pkg", FAKE_TYPE])
+		.output()
+		.unwrap();
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).contains("produced no files"));
+	assert!(!gpm.stdout(&["repo", "tools", "list"]).contains("pkg"));
+
+	gpm.run(&["type", "edit", FAKE_TYPE, "--no-artifact", "true"]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE]);
+	assert!(gpm.stdout(&["repo", "tools", "list"]).contains("pkg"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn single_file_artifacts_are_cloned_and_removed() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	let script = gpm.home().join("scripts").join(format!("{}.sh", FAKE_TYPE));
+	fs::write(&script, "#!/bin/sh\necho tool > \"$2.exe\"\n").unwrap();
+	gpm.run(&["repo", "tools", "add", "tool", FAKE_TYPE]);
+	let artifact = gpm.home().join("repositories/tools/tool.exe");
+	assert!(artifact.is_file());
+
+	gpm.run(&["repo", "tools", "clone", "tool"]);
+	assert!(gpm.work().join("tool.exe").is_file());
+	gpm.run(&["repo", "tools", "remove", "tool"]);
+	assert!(!artifact.exists());
+	assert!(!gpm.stdout(&["repo", "tools", "list"]).contains("tool"));
+}
+
+#[test]
+fn cwd_takes_a_path() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	fs::create_dir(gpm.work().join("project")).unwrap();
+	gpm.run(&[
+		"repo",
+		"tools",
+		"add",
+		"a",
+		FAKE_TYPE,
+		"--cwd",
+		"project/../project",
+	]);
+	gpm.run(&["repo", "tools", "add", "b", FAKE_TYPE, "x", "--cwd"]);
+	assert_eq!(
+		gpm.code(&["repo", "tools", "add", "c", FAKE_TYPE, "--cwd", "missing"]),
+		1
+	);
+
+	// Stored relative to the home directory, listed expanded.
+	let list = gpm.stdout(&["repo", "tools", "list", "--json"]);
+	let list: serde_json::Value = serde_json::from_str(&list).unwrap();
+	let cwd = |i: usize| fs::canonicalize(list[i]["cwd"].as_str().unwrap()).unwrap();
+	assert_eq!(
+		cwd(0),
+		fs::canonicalize(gpm.work().join("project")).unwrap()
+	);
+	assert_eq!(cwd(1), fs::canonicalize(gpm.work()).unwrap());
+	assert_eq!(list.as_array().unwrap().len(), 2);
+	#[cfg(target_os = "linux")]
+	{
+		let version = gpm.toml("repositories/tools/version.toml");
+		assert_eq!(
+			version["packages"]["a"]["cwd"].as_str(),
+			Some("~/work/project")
+		);
+	}
+}
+
+#[cfg(not(windows))]
+#[test]
+fn export_script_installs_without_gpm() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "a", FAKE_TYPE, "x y", "it's"]);
+	gpm.run(&["repo", "tools", "add", "b", FAKE_TYPE, "--register-only"]);
+	let script = gpm.work().join("setup.sh");
+	gpm.run(&[
+		"repo",
+		"tools",
+		"export-script",
+		"--shell",
+		"bash",
+		"-o",
+		"setup.sh",
+	]);
+	assert!(fs::read_to_string(&script)
+		.unwrap()
+		.contains("# Generated by gpm"));
+
+	let dest = gpm.work().join("elsewhere");
+	let run = || {
+		let output = std::process::Command::new("bash")
+			.arg(&script)
+			.arg(&dest)
+			.env("GPM_HOME", gpm.home())
+			.output()
+			.unwrap();
+		assert!(output.status.success(), "{:?}", output);
+		String::from_utf8(output.stdout).unwrap()
+	};
+	run();
+	assert_eq!(
+		fs::read_to_string(dest.join("a/args")).unwrap(),
+		"x y it's\n"
+	);
+	assert!(dest.join("b").is_dir());
+	assert!(!dest.join(".gpm-setup").exists());
+	assert!(run().contains("a is installed, skipped"));
+}
+
+#[test]
+fn unregistered_repository_by_path() {
+	let gpm = Gpm::with_fake_type();
+	let shared = gpm.work().join("shared");
+	fs::create_dir(&shared).unwrap();
+	fs::write(shared.join("version.toml"), "").unwrap();
+	gpm.run(&["repo", "--path", "shared", "add", "pkg", FAKE_TYPE]);
+	assert!(shared.join("pkg").is_dir());
+	assert!(gpm
+		.stdout(&["repo", "--path", shared.to_str().unwrap(), "list"])
+		.contains("pkg"));
+	assert!(!gpm.stdout(&["list"]).contains("shared"));
+	assert_eq!(gpm.code(&["repo", "--path", "missing", "list"]), 1);
+	assert_eq!(gpm.code(&["repo", "shared", "list"]), 1);
+	assert_eq!(gpm.code(&["repo", "--path", "shared", "shared", "list"]), 2);
+	assert_eq!(gpm.code(&["repo", "list"]), 2);
+
+	// Names of subcommands and their aliases are still repository names.
+	gpm.run(&["add", "u"]);
+	gpm.run(&["add", "r"]);
+	gpm.run(&["repo", "u", "list"]);
+	gpm.run(&["repo", "r", "add", "pkg", FAKE_TYPE]);
+	assert!(gpm.home().join("repositories/r/pkg").is_dir());
+}
+
+#[test]
+fn profiles_must_exist() {
+	let gpm = Gpm::new();
+	assert_eq!(gpm.code(&["--profile", "../escape", "init"]), 1);
+	assert!(!gpm.home().join("escape").exists());
+	assert_eq!(gpm.code(&["--profile", "default", "list"]), 1);
+	assert_eq!(gpm.code(&["--profile", "work", "list"]), 1);
+	gpm.run(&["--profile", "work", "init"]);
+	gpm.run(&["--profile", "work", "list"]);
+}
+
+#[test]
+fn relocate_keeps_repositories_apart() {
+	let gpm = Gpm::new();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["add", "other"]);
+	let repos = gpm.home().join("repositories");
+	let inside = repos.join("other").join("tools");
+	assert_eq!(
+		gpm.code(&["relocate", "tools", inside.to_str().unwrap()]),
+		1
+	);
+	assert!(repos.join("tools").is_dir());
+	assert!(!inside.exists());
+
+	let moved = gpm.work().join("moved");
+	gpm.run(&["relocate", "tools", moved.to_str().unwrap()]);
+	assert!(moved.is_dir());
+	assert!(!repos.join("tools").exists());
+	assert!(gpm.stdout(&["list"]).contains("moved"));
+}
+
+#[test]
+fn failing_scripts_are_recorded() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "ok", FAKE_TYPE]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE]);
+
+	let (code, stdout) = gpm.failing(&["pkg"], &["repo", "tools", "update", "ok", "pkg"]);
+	assert_eq!(code, 1);
+	assert!(stdout.contains("1 succeeded"));
+	assert!(stdout.contains("1 failed"));
+	let registry = gpm.toml("repositories/tools/version.toml");
+	assert_eq!(registry["packages"]["ok"]["tag"].as_str(), Some("v2"));
+	assert_eq!(
+		registry["packages"]["pkg"]["state"].as_str(),
+		Some("failed")
+	);
+	assert_eq!(registry["packages"]["pkg"]["tag"].as_str(), Some("v1"));
+
+	gpm.run(&["repo", "tools", "retry-failed"]);
+	let registry = gpm.toml("repositories/tools/version.toml");
+	assert!(registry["packages"]["pkg"].get("state").is_none());
+	assert_eq!(registry["packages"]["pkg"]["tag"].as_str(), Some("v2"));
+
+	let (code, _) = gpm.failing(&["bad"], &["repo", "tools", "add", "bad", FAKE_TYPE]);
+	assert_eq!(code, 1);
+	let repo = gpm.home().join("repositories").join("tools");
+	assert!(!repo.join("bad").exists());
+	let registry = gpm.toml("repositories/tools/version.toml");
+	assert!(registry["packages"].get("bad").is_none());
+
+	let (code, stdout) = gpm.failing(
+		&["b"],
+		&[
+			"repo",
+			"tools",
+			"add",
+			"--pkg-type",
+			FAKE_TYPE,
+			"--pkg",
+			"a=x",
+			"--pkg",
+			"b=y",
+		],
+	);
+	assert_eq!(code, 1);
+	assert!(stdout.contains("1 succeeded, 1 failed"));
+	assert!(!repo.join("b").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn offline_skips_only_network_failures() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "net", FAKE_TYPE]);
+	gpm.run(&["repo", "tools", "add", "gone", FAKE_TYPE]);
+	let repo = gpm.home().join("repositories").join("tools");
+	fs::remove_dir_all(repo.join("gone")).unwrap();
+	// Exits with the offline code for `net` and produces no files for `gone`.
+	let script = gpm.home().join("scripts").join(format!("{}.sh", FAKE_TYPE));
+	fs::write(
+		script,
+		"#!/bin/sh\nif [ \"$2\" = net ] && [ \"$GPM_OFFLINE\" = 1 ]; then exit 75; fi\n",
+	)
+	.unwrap();
+
+	assert_eq!(
+		gpm.code(&["--offline", "repo", "tools", "update", "net", "gone"]),
+		1
+	);
+	let registry = gpm.toml("repositories/tools/version.toml");
+	assert!(registry["packages"]["net"].get("state").is_none());
+	assert_eq!(
+		registry["packages"]["gone"]["state"].as_str(),
+		Some("failed")
+	);
+}
+
+#[test]
+fn namespaces_are_migrated() {
+	let gpm = Gpm::new();
+	let namespace = gpm.home().join("namespaces").join("tools");
+	fs::create_dir_all(namespace.join("fzf")).unwrap();
+	let config = "[namespaces.tools]\npath = \"namespaces/tools\"\n\
+		[namespaces.vendor]\npath = \"blocker/vendor\"\n";
+	fs::write(gpm.home().join("config.toml"), config).unwrap();
+	// The directory of `vendor` can't be created, which fails after `tools` is written.
+	fs::write(gpm.home().join("blocker"), "").unwrap();
+	fs::write(
+		namespace.join("namespace.toml"),
+		"[packages.fzf]\nurl = { Zip = \"https://example.com/fzf.zip\" }\nversion = \"0.55.0\"\n\
+		 [packages.dots]\nurl = { Git = \"https://example.com/dots.git\" }\n\
+		 [packages.odd]\nurl = { Svn = \"svn://example.com/odd\" }\n",
+	)
+	.unwrap();
+	gpm.run(&["init"]);
+	assert_eq!(
+		fs::read_to_string(gpm.home().join("config.toml")).unwrap(),
+		config
+	);
+
+	assert_ne!(gpm.code(&["init", "--migrate"]), 0);
+	assert_eq!(
+		fs::read_to_string(gpm.home().join("config.toml")).unwrap(),
+		config
+	);
+	assert!(namespace.join("namespace.toml").exists());
+	assert!(!namespace.join("version.toml").exists());
+	assert!(!gpm.home().join("types.toml").exists());
+	assert!(!gpm.home().join("backups").join("namespaces").exists());
+
+	fs::remove_file(gpm.home().join("blocker")).unwrap();
+	let output = gpm.command(&["init", "--migrate"]).output().unwrap();
+	assert!(output.status.success());
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(stderr.contains("'tools/odd' is not converted"));
+	assert!(stderr.contains("type 'zip' is not registered"));
+	let repos = gpm.toml("config.toml");
+	assert!(repos["repositories"]["tools"]["path"]
+		.as_str()
+		.unwrap()
+		.ends_with("tools"));
+	let packages = &gpm.toml("namespaces/tools/version.toml")["packages"];
+	assert_eq!(packages["fzf"]["type"].as_str(), Some("zip"));
+	assert_eq!(packages["fzf"]["tag"].as_str(), Some("0.55.0"));
+	assert!(packages["fzf"].get("state").is_none());
+	assert_eq!(packages["dots"]["type"].as_str(), Some("git"));
+	assert_eq!(packages["dots"]["state"].as_str(), Some("pending"));
+	assert!(packages.get("odd").is_none());
+	let types = gpm.toml("types.toml");
+	assert!(types["types"].get("git").is_some());
+	assert!(types["types"].get("zip").is_none());
+	let backup = gpm.home().join("backups").join("namespaces");
+	assert_eq!(
+		fs::read_to_string(backup.join("config.toml")).unwrap(),
+		config
+	);
+	assert!(backup.join("tools").join("namespace.toml").exists());
+	assert!(!namespace.join("namespace.toml").exists());
+}
+
+#[test]
+fn names_from_args_and_pkg_lists() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	let url = "https://example.com/dl/ripgrep-14.1.0-x86_64.zip";
+	gpm.run(&["repo", "tools", "add", "--name-from-arg", FAKE_TYPE, url]);
+	gpm.run(&[
+		"repo",
+		"tools",
+		"add",
+		"--pkg-type",
+		FAKE_TYPE,
+		"--pkg",
+		"a=1",
+		"--pkg",
+		"b=2",
+	]);
+	let registry = gpm.toml("repositories/tools/version.toml");
+	assert_eq!(
+		registry["packages"]["ripgrep"]["args"][0].as_str(),
+		Some(url)
+	);
+	assert_eq!(registry["packages"]["b"]["args"][0].as_str(), Some("2"));
+
+	// The name is already taken, or too generic to be a name.
+	assert_eq!(
+		gpm.code(&["repo", "tools", "add", "--name-from-arg", FAKE_TYPE, url]),
+		1
+	);
+	let main = "https://github.com/o/r/archive/refs/heads/main.zip";
+	assert_eq!(
+		gpm.code(&["repo", "tools", "add", "--name-from-arg", FAKE_TYPE, main]),
+		1
+	);
+	assert_eq!(gpm.code(&["repo", "tools", "add", "--pkg", "c=3"]), 2);
+}
+
+#[test]
+fn missing_types_are_reassigned() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE]);
+	assert_eq!(gpm.code(&["repo", "tools", "check-types"]), 0);
+
+	let version = gpm.home().join("repositories/tools/version.toml");
+	let registry = fs::read_to_string(&version).unwrap();
+	let typed = format!("type = \"{}\"", FAKE_TYPE);
+	fs::write(&version, registry.replace(&typed, "type = \"gone\"")).unwrap();
+	assert_eq!(gpm.code(&["repo", "tools", "check-types"]), 1);
+	let reassign = format!("gone={}", FAKE_TYPE);
+	assert!(gpm
+		.stdout(&["repo", "tools", "check-types", "--reassign", &reassign])
+		.contains("1 package with a registered type"));
+	assert_eq!(
+		gpm.code(&["repo", "tools", "check-types", "--reassign", "x=nope"]),
+		1
+	);
+	assert_eq!(
+		gpm.toml("repositories/tools/version.toml")["packages"]["pkg"]["type"].as_str(),
+		Some(FAKE_TYPE)
+	);
+}
+
+#[test]
+fn type_which_checks_the_script() {
+	let gpm = Gpm::with_fake_type();
+	let output = gpm.stdout(&["type", "which", FAKE_TYPE, "--json"]);
+	let which: serde_json::Value = serde_json::from_str(&output).unwrap();
+	assert_eq!(which["exists"], true);
+	assert!(which["command_line"].as_str().unwrap().contains("'<name>'"));
+
+	let script = which["script"].as_str().unwrap();
+	fs::remove_file(script).unwrap();
+	assert_eq!(gpm.code(&["type", "which", FAKE_TYPE]), 1);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn failed_add_leaves_nothing_behind() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	let repo = gpm.home().join("repositories").join("tools");
+	fs::create_dir(repo.join("pkg.old")).unwrap();
+	let script = gpm.home().join("scripts").join(format!("{}.sh", FAKE_TYPE));
+	fs::write(&script, "#!/bin/sh\nmkdir other\ntouch pkg.zip.part\n").unwrap();
+
+	assert_eq!(gpm.code(&["repo", "tools", "add", "pkg", FAKE_TYPE]), 1);
+	assert!(!repo.join("pkg.zip.part").exists());
+	assert!(repo.join("pkg.old").exists());
+	assert!(repo.join("other").exists());
+}
+
+#[test]
+fn case_collisions_are_reported() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "Pkg", FAKE_TYPE, "--register-only"]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE, "--register-only"]);
+
+	let mut config = gpm.toml("config.toml");
+	config.insert("case_insensitive_names".into(), true.into());
+	fs::write(gpm.home().join("config.toml"), config.to_string()).unwrap();
+	let output = gpm.command(&["doctor"]).output().unwrap();
+	assert!(String::from_utf8_lossy(&output.stderr).contains("differ only by case"));
+	assert_eq!(
+		gpm.code(&["repo", "tools", "add", "PKG", FAKE_TYPE, "--register-only"]),
+		1
+	);
+}
+
+#[test]
+fn package_history_shows_etag_changes() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools"]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE]);
+	gpm.run(&["repo", "tools", "update", "pkg"]);
+	gpm.run(&["repo", "tools", "remove", "pkg"]);
+	gpm.run(&["repo", "tools", "add", "pkg", FAKE_TYPE]);
+
+	let output = gpm.stdout(&["repo", "tools", "history", "pkg", "--json"]);
+	let entries: serde_json::Value = serde_json::from_str(&output).unwrap();
+	let events: Vec<_> = entries
+		.as_array()
+		.unwrap()
+		.iter()
+		.map(|e| {
+			format!(
+				"{} {}",
+				e["operation"].as_str().unwrap(),
+				e["etag"].as_str().unwrap_or("-")
+			)
+		})
+		.collect();
+	assert_eq!(events, ["add v1", "update v2", "remove -", "add v1"]);
+	assert!(gpm
+		.stdout(&["repo", "tools", "history", "pkg"])
+		.contains("v1 -> v2"));
+}
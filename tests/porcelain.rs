@@ -0,0 +1,51 @@
+//! Pins the exact porcelain output, which scripts rely on staying the same between versions.
+mod common;
+
+use common::{Gpm, FAKE_TYPE};
+
+/// Porcelain output of `args` with the gpm home replaced by `$HOME`.
+fn porcelain(gpm: &Gpm, args: &[&str]) -> String {
+	gpm.stdout(args)
+		.replace(&gpm.home().display().to_string(), "$HOME")
+}
+
+#[test]
+fn porcelain_output_is_stable() {
+	let gpm = Gpm::with_fake_type();
+	gpm.run(&["add", "tools", "-d", "my\ttools"]);
+	gpm.run(&[
+		"repo",
+		"tools",
+		"add",
+		"b pkg",
+		FAKE_TYPE,
+		"x",
+		"y z",
+		"--register-only",
+		"--allow-weird-names",
+	]);
+	gpm.run(&["repo", "tools", "add", "a", FAKE_TYPE, "--register-only"]);
+
+	let sep = std::path::MAIN_SEPARATOR;
+	assert_eq!(
+		porcelain(&gpm, &["list", "--porcelain"]),
+		format!("tools\t$HOME{sep}repositories{sep}tools\tmy tools\tfalse\tok\n")
+	);
+	assert_eq!(
+		porcelain(&gpm, &["repo", "tools", "list", "--porcelain"]),
+		"a\tfake\t\t\t\t\nb pkg\tfake\tx;y z\t\t\t\n"
+	);
+	assert_eq!(
+		porcelain(&gpm, &["repo", "tools", "list", "--porcelain", "-z"]),
+		"a\tfake\t\t\t\t\0b pkg\tfake\tx;y z\t\t\t\0"
+	);
+	assert_eq!(
+		porcelain(&gpm, &["repo", "tools", "status", "--porcelain"]),
+		"a\tpending\t\t\nb pkg\tpending\t\t\n"
+	);
+	#[cfg(not(windows))]
+	assert_eq!(
+		porcelain(&gpm, &["type", "list", "--porcelain", "-z"]),
+		"fake\tsh\tsh\t\0"
+	);
+}